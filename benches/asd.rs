@@ -1,16 +1,50 @@
 use criterion::{black_box, criterion_group, criterion_main, Criterion};
 
 pub fn benchmark(c: &mut Criterion) {
-    let expr = match calc::parser::parse(include_str!("expr.txt")) {
+    let mut db = calc::source::SourceDb::default();
+    // generated with `calc gen --seed 2 --nodes 1024 --safe`
+    let file = db.add("expr.txt", include_str!("expr.txt"));
+    let src = db.get(file);
+
+    c.bench_function("parse_boxed", |c| {
+        c.iter(|| black_box(calc::parser::parse(src).unwrap()))
+    });
+    c.bench_function("parse_arena", |c| {
+        c.iter(|| black_box(calc::parser::parse_ast(src).unwrap()))
+    });
+    c.bench_function("parse_compile_stack_boxed", |c| {
+        c.iter(|| {
+            let expr = calc::parser::parse(src).unwrap();
+            black_box(calc::stack::compiler::compile(&expr))
+        })
+    });
+    c.bench_function("parse_compile_stack_arena", |c| {
+        c.iter(|| {
+            let ast = calc::parser::parse_ast(src).unwrap();
+            black_box(calc::stack::compiler::compile_ast(&ast))
+        })
+    });
+
+    let expr = match calc::parser::parse(src) {
         Ok(expr) => expr,
         Err(e) => {
-            eprintln!("{}", e.report());
+            eprintln!("{}", e.report(&db));
             panic!();
         }
     };
 
     c.bench_function("fold", |c| c.iter(|| black_box(calc::folder::fold(&expr))));
 
+    let ast = calc::parser::parse_ast(src).unwrap();
+    c.bench_function("fold_arena", |c| {
+        c.iter(|| black_box(calc::folder::fold_ast(&ast)))
+    });
+
+    let closure = calc::closure::compile(&expr);
+    c.bench_function("closure", |c| {
+        c.iter(|| black_box(calc::closure::eval(&closure)))
+    });
+
     let ops = calc::rpn::compiler::compile(&expr);
     c.bench_function("rpn", |c| c.iter(|| black_box(calc::rpn::vm::eval(&ops))));
 
@@ -22,20 +56,48 @@ pub fn benchmark(c: &mut Criterion) {
         c.iter(|| black_box(calc::unsafe_stack::vm::eval(&ops, &pool)))
     });
 
+    let ops = calc::stack::peephole::optimize(&ops);
+    c.bench_function("stack_peephole", |c| {
+        c.iter(|| black_box(calc::stack::vm::eval(&ops, &pool)))
+    });
+    c.bench_function("unsafe_stack_peephole", |c| {
+        c.iter(|| black_box(calc::unsafe_stack::vm::eval(&ops, &pool)))
+    });
+
     let (ops, pool, stack_size) = calc::alloc_exact_stack::compiler::compile(&expr);
     c.bench_function("alloc_exact_stack", |c| {
         c.iter(|| black_box(calc::alloc_exact_stack::vm::eval(&ops, &pool, stack_size)))
     });
 
-    let (ops, pool, stack_size) = calc::register::compiler::compile(&expr);
+    let (ops, pool, stack_size) = calc::register::compiler::compile(&expr).unwrap();
     c.bench_function("register", |c| {
         c.iter(|| black_box(calc::register::vm::eval(&ops, &pool, stack_size)))
     });
 
-    let (ops, pool, stack_size) = calc::unsafe_register::compiler::compile(&expr);
+    let (ops, pool, stack_size) = calc::unsafe_register::compiler::compile(&expr).unwrap();
     c.bench_function("unsafe_register", |c| {
         c.iter(|| black_box(calc::unsafe_register::vm::eval(&ops, &pool, stack_size)))
     });
+
+    let (ops, pool, stack_size) = calc::threaded_register::compiler::compile(&expr).unwrap();
+    let code = calc::threaded_register::vm::thread(&ops);
+    c.bench_function("threaded_register", |c| {
+        c.iter(|| black_box(calc::threaded_register::vm::eval(&code, &pool, stack_size)))
+    });
+
+    let (ops, pool, stack_size) = calc::jit::compiler::compile(&expr).unwrap();
+    let program = calc::jit::vm::jit(ops, pool, stack_size);
+    c.bench_function("jit", |c| {
+        c.iter(|| black_box(calc::jit::vm::eval(&program)))
+    });
+
+    #[cfg(feature = "cranelift")]
+    {
+        let program = calc::cranelift::compile(&expr);
+        c.bench_function("cranelift", |c| {
+            c.iter(|| black_box(calc::cranelift::eval(&program)))
+        });
+    }
 }
 
 criterion_group!(benches, benchmark);
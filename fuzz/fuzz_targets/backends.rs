@@ -0,0 +1,14 @@
+#![no_main]
+
+use calc::differential;
+use calc::expr::Expr;
+use libfuzzer_sys::fuzz_target;
+
+fuzz_target!(|expr: Expr| {
+    if differential::depth(&expr) > differential::MAX_DEPTH {
+        return;
+    }
+    if let Err(mismatch) = differential::check(&expr) {
+        panic!("{mismatch}");
+    }
+});
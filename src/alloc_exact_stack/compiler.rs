@@ -1,5 +1,7 @@
+use crate::arena::Ast;
 use crate::expr::BinaryOp;
 use crate::expr::Expr;
+use crate::expr::Folder;
 use crate::expr::UnaryOp;
 
 use super::op::Op;
@@ -33,41 +35,53 @@ impl StackAlloc {
 }
 
 pub fn compile(expr: &Expr) -> (Bytecode, ConstPool, StackSize) {
-    fn emit(expr: &Expr, ops: &mut Bytecode, pool: &mut ConstPool, stack: &mut StackAlloc) {
-        match expr {
-            Expr::Binary(expr) => {
-                emit(&expr.left, ops, pool, stack);
-                emit(&expr.right, ops, pool, stack);
-                stack.pop();
-                match expr.op {
-                    BinaryOp::Add => ops.push(Op::BAdd),
-                    BinaryOp::Sub => ops.push(Op::BSub),
-                    BinaryOp::Mul => ops.push(Op::BMul),
-                    BinaryOp::Div => ops.push(Op::BDiv),
-                }
-            }
-            Expr::Unary(expr) => {
-                emit(&expr.right, ops, pool, stack);
-                match expr.op {
-                    UnaryOp::Plus => {}
-                    UnaryOp::Minus => ops.push(Op::UMinus),
-                }
-            }
-            Expr::Int(value) => {
-                match value {
-                    MIN_INLINE_INT..=MAX_INLINE_INT => ops.push(Op::LInt(*value as i16)),
-                    _ => {
-                        let i = pool.len() as u16;
-                        pool.push(*value);
-                        ops.push(Op::LConst(i));
-                    }
-                }
-                stack.push();
-            }
+    let mut emitter = Emitter::default();
+    expr.fold(&mut emitter);
+    (emitter.ops, emitter.pool, emitter.stack.finish())
+}
+
+pub fn compile_ast(ast: &Ast) -> (Bytecode, ConstPool, StackSize) {
+    let mut emitter = Emitter::default();
+    ast.fold(&mut emitter);
+    (emitter.ops, emitter.pool, emitter.stack.finish())
+}
+
+#[derive(Default)]
+struct Emitter {
+    ops: Bytecode,
+    pool: ConstPool,
+    stack: StackAlloc,
+}
+
+impl Folder for Emitter {
+    type Output = ();
+
+    fn fold_binary(&mut self, op: BinaryOp, _: (), _: ()) {
+        self.stack.pop();
+        match op {
+            BinaryOp::Add => self.ops.push(Op::BAdd),
+            BinaryOp::Sub => self.ops.push(Op::BSub),
+            BinaryOp::Mul => self.ops.push(Op::BMul),
+            BinaryOp::Div => self.ops.push(Op::BDiv),
+        }
+    }
+
+    fn fold_unary(&mut self, op: UnaryOp, _: ()) {
+        match op {
+            UnaryOp::Plus => {}
+            UnaryOp::Minus => self.ops.push(Op::UMinus),
         }
     }
 
-    let (mut ops, mut pool, mut stack) = (Vec::new(), Vec::new(), StackAlloc::default());
-    emit(expr, &mut ops, &mut pool, &mut stack);
-    (ops, pool, stack.finish())
+    fn fold_int(&mut self, value: i64) {
+        match value {
+            MIN_INLINE_INT..=MAX_INLINE_INT => self.ops.push(Op::LInt(value as i16)),
+            _ => {
+                let i = self.pool.len() as u16;
+                self.pool.push(value);
+                self.ops.push(Op::LConst(i));
+            }
+        }
+        self.stack.push();
+    }
 }
@@ -34,6 +34,22 @@ pub fn eval(ops: &Bytecode, pool: &ConstPool, stack_size: StackSize) -> i64 {
                 let right = stack.pop();
                 stack.push(-right);
             }
+            Op::AddImm(value) => {
+                let left = stack.pop();
+                stack.push(left + *value as i64);
+            }
+            Op::SubImm(value) => {
+                let left = stack.pop();
+                stack.push(left - *value as i64);
+            }
+            Op::MulImm(value) => {
+                let left = stack.pop();
+                stack.push(left * *value as i64);
+            }
+            Op::DivImm(value) => {
+                let left = stack.pop();
+                stack.push(left / *value as i64);
+            }
         }
     }
 
@@ -65,9 +81,8 @@ impl Stack {
     #[inline(always)]
     fn pop(&mut self) -> i64 {
         unsafe {
-            let slot = self.buffer.get_unchecked(self.ptr);
             self.ptr -= 1;
-            *slot
+            *self.buffer.get_unchecked(self.ptr)
         }
     }
 }
@@ -0,0 +1,85 @@
+use crate::error::Result;
+use crate::expr::BinaryOp;
+use crate::expr::Folder;
+use crate::expr::UnaryOp;
+use crate::parser::Builder;
+use crate::span::Span;
+
+pub type NodeId = u32;
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Node {
+    Binary {
+        left: NodeId,
+        op: BinaryOp,
+        right: NodeId,
+    },
+    Unary {
+        op: UnaryOp,
+        right: NodeId,
+    },
+    Int(i64),
+}
+
+/// Flat alternative to `Expr`, with all nodes stored in a single `Vec`.
+///
+/// Nodes are only ever pushed after their children,
+/// so the last node is the root and a linear scan visits the tree in post-order.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Ast {
+    nodes: Vec<Node>,
+}
+
+impl Ast {
+    pub fn nodes(&self) -> &[Node] {
+        &self.nodes
+    }
+
+    pub fn root(&self) -> NodeId {
+        (self.nodes.len() - 1) as NodeId
+    }
+
+    /// Same as `Expr::fold`, calling the folder in the same order.
+    pub fn fold<F: Folder + ?Sized>(&self, folder: &mut F) -> F::Output {
+        // every node has exactly one parent, so each value is taken exactly once
+        let mut values: Vec<Option<F::Output>> = Vec::with_capacity(self.nodes.len());
+        for node in &self.nodes {
+            let value = match *node {
+                Node::Binary { left, op, right } => {
+                    let left = values[left as usize].take().unwrap();
+                    let right = values[right as usize].take().unwrap();
+                    folder.fold_binary(op, left, right)
+                }
+                Node::Unary { op, right } => {
+                    let right = values[right as usize].take().unwrap();
+                    folder.fold_unary(op, right)
+                }
+                Node::Int(value) => folder.fold_int(value),
+            };
+            values.push(Some(value));
+        }
+        values.pop().unwrap().unwrap()
+    }
+
+    fn push(&mut self, node: Node) -> NodeId {
+        let id = self.nodes.len() as NodeId;
+        self.nodes.push(node);
+        id
+    }
+}
+
+impl Builder for Ast {
+    type Node = NodeId;
+
+    fn binary(&mut self, left: NodeId, op: BinaryOp, right: NodeId, _: Span) -> Result<NodeId> {
+        Ok(self.push(Node::Binary { left, op, right }))
+    }
+
+    fn unary(&mut self, op: UnaryOp, right: NodeId, _: Span) -> Result<NodeId> {
+        Ok(self.push(Node::Unary { op, right }))
+    }
+
+    fn int(&mut self, value: i64, _: Span) -> Result<NodeId> {
+        Ok(self.push(Node::Int(value)))
+    }
+}
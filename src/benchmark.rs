@@ -0,0 +1,308 @@
+use std::hint::black_box;
+use std::time::Duration;
+use std::time::Instant;
+
+use crate::expr::BinaryOp;
+use crate::generator::Config;
+use crate::generator::Generator;
+use crate::generator::Shape;
+use crate::source::SourceDb;
+
+/// Expressions with a particular shape, to see how backends cope with each.
+pub struct Workload {
+    pub name: &'static str,
+    pub config: Config,
+}
+
+/// The standard workloads with `nodes` nodes each.
+///
+/// All of them are generated in safe mode, so every backend can evaluate them,
+/// and nesting is only limited by the node count.
+pub fn workloads(nodes: usize) -> Vec<Workload> {
+    let config = Config {
+        depth: nodes,
+        nodes,
+        safe: true,
+        ..Default::default()
+    };
+    let chain = Config {
+        ops: vec![BinaryOp::Add, BinaryOp::Sub],
+        unary: false,
+        ..config.clone()
+    };
+    vec![
+        Workload {
+            name: "random",
+            config: config.clone(),
+        },
+        Workload {
+            name: "balanced",
+            config: Config {
+                shape: Shape::Balanced,
+                unary: false,
+                ..config.clone()
+            },
+        },
+        Workload {
+            name: "left_deep",
+            config: Config {
+                shape: Shape::LeftDeep,
+                ..chain.clone()
+            },
+        },
+        Workload {
+            name: "right_deep",
+            config: Config {
+                shape: Shape::RightDeep,
+                ..chain
+            },
+        },
+        Workload {
+            name: "literal_heavy",
+            config: Config {
+                unary: false,
+                ..config.clone()
+            },
+        },
+        Workload {
+            name: "large_constants",
+            config: Config {
+                literals: -(1 << 40)..=1 << 40,
+                ..config
+            },
+        },
+    ]
+}
+
+/// Average time per run of each phase, or why the backend couldn't run the workload.
+pub struct Row {
+    pub workload: &'static str,
+    pub backend: &'static str,
+    pub parse: Duration,
+    /// `None` for interpreters working on the tree directly.
+    pub compile: Option<Duration>,
+    pub eval: Result<Duration, String>,
+}
+
+#[derive(Default)]
+pub struct Report {
+    pub rows: Vec<Row>,
+}
+
+/// Generates each workload from `seed` and times every backend on it,
+/// running each phase for about `budget`.
+pub fn run(workloads: &[Workload], seed: u64, budget: Duration) -> Report {
+    let mut report = Report::default();
+    for workload in workloads {
+        let expr = Generator::new(seed, workload.config.clone()).generate();
+        let mut db = SourceDb::default();
+        let file = db.add(workload.name, crate::formatter::format(&expr, usize::MAX));
+        let file = db.get(file);
+        let parse = |arena| {
+            time(budget, || {
+                if arena {
+                    drop(crate::parser::parse_ast_with_max_depth(file, usize::MAX));
+                } else {
+                    drop(crate::parser::parse_with_max_depth(file, usize::MAX));
+                }
+            })
+        };
+        let (parse_boxed, parse_arena) = (parse(false), parse(true));
+        let expr = crate::parser::parse_with_max_depth(file, usize::MAX).unwrap();
+        let ast = crate::parser::parse_ast_with_max_depth(file, usize::MAX).unwrap();
+
+        let mut measure = |backend, parse, compile, eval: Result<&dyn Fn() -> i64, String>| {
+            report.rows.push(Row {
+                workload: workload.name,
+                backend,
+                parse,
+                compile,
+                eval: eval.map(|eval| time(budget, eval)),
+            })
+        };
+        // Some backends have limits, like the number of registers,
+        // so their compilers are marked `fallible` and return a `Result`.
+        macro_rules! compiled {
+            ($backend:expr, $parse:expr, fallible || $compile:expr, |$compiled:pat_param| $eval:expr) => {{
+                match $compile {
+                    Ok(compiled) => {
+                        let compile = time(budget, || $compile);
+                        let $compiled = &compiled;
+                        measure($backend, $parse, Some(compile), Ok(&|| $eval));
+                    }
+                    Err(e) => measure($backend, $parse, None, Err(e.message().to_string())),
+                }
+            }};
+            ($backend:expr, $parse:expr, || $compile:expr, |$compiled:pat_param| $eval:expr) => {
+                compiled!(
+                    $backend,
+                    $parse,
+                    fallible || Ok::<_, crate::error::Error>($compile),
+                    |$compiled| $eval
+                )
+            };
+        }
+
+        measure(
+            "fold",
+            parse_boxed,
+            None,
+            Ok(&|| crate::folder::fold(&expr)),
+        );
+        measure(
+            "fold_arena",
+            parse_arena,
+            None,
+            Ok(&|| crate::folder::fold_ast(&ast)),
+        );
+        compiled!(
+            "closure",
+            parse_boxed,
+            || crate::closure::compile(&expr),
+            |closure| crate::closure::eval(closure)
+        );
+        compiled!(
+            "rpn",
+            parse_boxed,
+            || crate::rpn::compiler::compile(&expr),
+            |ops| crate::rpn::vm::eval(ops)
+        );
+        compiled!(
+            "stack",
+            parse_boxed,
+            || crate::stack::compiler::compile(&expr),
+            |(ops, pool)| crate::stack::vm::eval(ops, pool)
+        );
+        compiled!(
+            "stack_arena",
+            parse_arena,
+            || crate::stack::compiler::compile_ast(&ast),
+            |(ops, pool)| crate::stack::vm::eval(ops, pool)
+        );
+        compiled!(
+            "unsafe_stack",
+            parse_boxed,
+            || crate::stack::compiler::compile(&expr),
+            |(ops, pool)| crate::unsafe_stack::vm::eval(ops, pool)
+        );
+        compiled!(
+            "stack_pointer",
+            parse_boxed,
+            || crate::stack::compiler::compile(&expr),
+            |(ops, pool)| crate::stack_pointer::vm::eval(ops, pool)
+        );
+        compiled!(
+            "stack_peephole",
+            parse_boxed,
+            || {
+                let (ops, pool) = crate::stack::compiler::compile(&expr);
+                (crate::stack::peephole::optimize(&ops), pool)
+            },
+            |(ops, pool)| crate::stack::vm::eval(ops, pool)
+        );
+        compiled!(
+            "alloc_exact_stack",
+            parse_boxed,
+            || crate::alloc_exact_stack::compiler::compile(&expr),
+            |(ops, pool, stack_size)| crate::alloc_exact_stack::vm::eval(ops, pool, *stack_size)
+        );
+        compiled!(
+            "register",
+            parse_boxed,
+            fallible || crate::register::compiler::compile(&expr),
+            |(ops, pool, stack_size)| crate::register::vm::eval(ops, pool, *stack_size)
+        );
+        compiled!(
+            "unsafe_register",
+            parse_boxed,
+            fallible || crate::unsafe_register::compiler::compile(&expr),
+            |(ops, pool, stack_size)| crate::unsafe_register::vm::eval(ops, pool, *stack_size)
+        );
+        compiled!(
+            "threaded_register",
+            parse_boxed,
+            fallible || {
+                crate::threaded_register::compiler::compile(&expr).map(|(ops, pool, stack_size)| {
+                    (crate::threaded_register::vm::thread(&ops), pool, stack_size)
+                })
+            },
+            |(code, pool, stack_size)| crate::threaded_register::vm::eval(code, pool, *stack_size)
+        );
+        compiled!(
+            "jit",
+            parse_boxed,
+            fallible || {
+                crate::jit::compiler::compile(&expr)
+                    .map(|(ops, pool, stack_size)| crate::jit::vm::jit(ops, pool, stack_size))
+            },
+            |program| crate::jit::vm::eval(program)
+        );
+        #[cfg(feature = "cranelift")]
+        compiled!(
+            "cranelift",
+            parse_boxed,
+            || crate::cranelift::compile(&expr),
+            |program| crate::cranelift::eval(program)
+        );
+    }
+    report
+}
+
+/// Average time of one call to `f`, calling it repeatedly for about `budget`.
+fn time<T>(budget: Duration, mut f: impl FnMut() -> T) -> Duration {
+    let start = Instant::now();
+    let mut runs = 0;
+    while runs == 0 || start.elapsed() < budget {
+        black_box(f());
+        runs += 1;
+    }
+    start.elapsed() / runs
+}
+
+impl Report {
+    pub fn markdown(&self) -> String {
+        let mut out = String::from(
+            "| workload | backend | parse (µs) | compile (µs) | eval (µs) |\n\
+             |---|---|--:|--:|--:|\n",
+        );
+        for row in &self.rows {
+            let eval = match &row.eval {
+                Ok(eval) => micros(*eval),
+                Err(e) => format!("failed: {e}"),
+            };
+            out.push_str(&format!(
+                "| {} | {} | {} | {} | {eval} |\n",
+                row.workload,
+                row.backend,
+                micros(row.parse),
+                row.compile.map_or("-".to_string(), micros),
+            ));
+        }
+        out
+    }
+
+    /// Times are in nanoseconds, with empty fields for phases that didn't run.
+    pub fn csv(&self) -> String {
+        let mut out = String::from("workload,backend,parse_ns,compile_ns,eval_ns,error\n");
+        for row in &self.rows {
+            let compile = row
+                .compile
+                .map_or(String::new(), |d| d.as_nanos().to_string());
+            let (eval, error) = match &row.eval {
+                Ok(eval) => (eval.as_nanos().to_string(), String::new()),
+                Err(e) => (String::new(), format!("\"{}\"", e.replace('"', "\"\""))),
+            };
+            out.push_str(&format!(
+                "{},{},{},{compile},{eval},{error}\n",
+                row.workload,
+                row.backend,
+                row.parse.as_nanos(),
+            ));
+        }
+        out
+    }
+}
+
+fn micros(duration: Duration) -> String {
+    format!("{:.2}", duration.as_secs_f64() * 1e6)
+}
@@ -0,0 +1,51 @@
+use crate::arena::Ast;
+use crate::expr::BinaryOp;
+use crate::expr::Expr;
+use crate::expr::Folder;
+use crate::expr::UnaryOp;
+
+pub type Closure = Box<dyn Fn(&mut Env) -> i64>;
+
+/// Evaluation state threaded through every closure.
+///
+/// Expressions have no variables yet, so there is nothing in here.
+#[derive(Default)]
+pub struct Env {}
+
+pub fn compile(expr: &Expr) -> Closure {
+    expr.fold(&mut Compiler)
+}
+
+pub fn compile_ast(ast: &Ast) -> Closure {
+    ast.fold(&mut Compiler)
+}
+
+struct Compiler;
+
+impl Folder for Compiler {
+    type Output = Closure;
+
+    fn fold_binary(&mut self, op: BinaryOp, left: Closure, right: Closure) -> Closure {
+        match op {
+            BinaryOp::Add => Box::new(move |env| left(env) + right(env)),
+            BinaryOp::Sub => Box::new(move |env| left(env) - right(env)),
+            BinaryOp::Mul => Box::new(move |env| left(env) * right(env)),
+            BinaryOp::Div => Box::new(move |env| left(env) / right(env)),
+        }
+    }
+
+    fn fold_unary(&mut self, op: UnaryOp, right: Closure) -> Closure {
+        match op {
+            UnaryOp::Plus => right,
+            UnaryOp::Minus => Box::new(move |env| -right(env)),
+        }
+    }
+
+    fn fold_int(&mut self, value: i64) -> Closure {
+        Box::new(move |_| value)
+    }
+}
+
+pub fn eval(closure: &Closure) -> i64 {
+    closure(&mut Env::default())
+}
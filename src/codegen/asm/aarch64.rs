@@ -0,0 +1,165 @@
+use std::fmt::Write;
+
+use super::lower;
+use super::trap_label;
+use super::Instr;
+use super::Operand;
+use crate::error::Result;
+use crate::expr::BinaryOp;
+use crate::expr::Expr;
+use crate::trap::Trap;
+
+/// Virtual registers which live in machine registers, the rest are spilled.
+/// `x0`..`x3` are kept free as scratch, `x8` holds syscall numbers.
+const REGS: [&str; 17] = [
+    "x19", "x20", "x21", "x22", "x23", "x24", "x25", "x26", "x27", "x28", "x9", "x10", "x11",
+    "x12", "x13", "x14", "x15",
+];
+
+/// Emits a GNU `as` program for Linux which prints the result of `expr`
+/// to stdout, or exits with `101` after printing the trap message.
+/// Fails like `register::compiler::compile`.
+pub fn source(expr: &Expr) -> Result<String> {
+    let (ops, pool, stack_size) = crate::register::compiler::compile(expr)?;
+    // `sp` must stay 16-byte aligned
+    let frame = (stack_size.saturating_sub(REGS.len()) * 8).next_multiple_of(16);
+
+    let mut out = String::new();
+    macro_rules! asm {
+        ($($arg:tt)*) => { writeln!(out, $($arg)*).unwrap() };
+    }
+
+    let load = |out: &mut String, scratch: &str, reg: u8| match REGS.get(reg as usize) {
+        Some(name) => writeln!(out, "    mov {scratch}, {name}").unwrap(),
+        None => writeln!(out, "    ldr {scratch}, [sp, #{}]", spill(reg)).unwrap(),
+    };
+    let store = |out: &mut String, reg: u8| match REGS.get(reg as usize) {
+        Some(name) => writeln!(out, "    mov {name}, x0").unwrap(),
+        None => writeln!(out, "    str x0, [sp, #{}]", spill(reg)).unwrap(),
+    };
+
+    asm!("    .globl _start");
+    asm!("    .text");
+    asm!("_start:");
+    for reg in 0..stack_size as u8 {
+        match REGS.get(reg as usize) {
+            Some(name) => asm!("    // r{reg} -> {name}"),
+            None => asm!("    // r{reg} -> [sp, #{}]", spill(reg)),
+        }
+    }
+    if frame > 0 {
+        asm!("    sub sp, sp, #{frame}");
+    }
+
+    for (i, instr) in lower(&ops, &pool).iter().enumerate() {
+        asm!("    // {instr}");
+        match instr {
+            Instr::Load(dst, value) => {
+                asm!("    ldr x0, ={value}");
+                store(&mut out, *dst);
+            }
+            Instr::Binary(op, dst, lhs, rhs) => {
+                load(&mut out, "x0", *lhs);
+                match rhs {
+                    Operand::Reg(rhs) => load(&mut out, "x1", *rhs),
+                    Operand::Imm(value) => asm!("    ldr x1, ={value}"),
+                }
+                match op {
+                    BinaryOp::Add => {
+                        asm!("    adds x0, x0, x1");
+                        asm!("    b.vs {}", trap_label(Trap::AddOverflow));
+                    }
+                    BinaryOp::Sub => {
+                        asm!("    subs x0, x0, x1");
+                        asm!("    b.vs {}", trap_label(Trap::SubOverflow));
+                    }
+                    BinaryOp::Mul => {
+                        // overflow iff the high half isn't the sign extension of the low half
+                        asm!("    mul x2, x0, x1");
+                        asm!("    smulh x3, x0, x1");
+                        asm!("    cmp x3, x2, asr #63");
+                        asm!("    b.ne {}", trap_label(Trap::MulOverflow));
+                        asm!("    mov x0, x2");
+                    }
+                    BinaryOp::Div => {
+                        asm!("    cbz x1, {}", trap_label(Trap::DivByZero));
+                        asm!("    cmn x1, #1");
+                        asm!("    b.ne .Ldiv{i}");
+                        asm!("    ldr x2, ={}", i64::MIN);
+                        asm!("    cmp x0, x2");
+                        asm!("    b.eq {}", trap_label(Trap::DivOverflow));
+                        asm!(".Ldiv{i}:");
+                        asm!("    sdiv x0, x0, x1");
+                    }
+                }
+                store(&mut out, *dst);
+            }
+            Instr::Neg(dst, rhs) => {
+                load(&mut out, "x0", *rhs);
+                asm!("    negs x0, x0");
+                asm!("    b.vs {}", trap_label(Trap::NegOverflow));
+                store(&mut out, *dst);
+            }
+        }
+    }
+
+    asm!("    // print r0 in decimal, digits are written backwards from the end of `buf`");
+    load(&mut out, "x0", 0);
+    asm!("    mov x5, x0");
+    asm!("    ldr x1, =buf + 32");
+    asm!("    mov w3, #10");
+    asm!("    strb w3, [x1, #-1]!");
+    asm!("    mov x6, #10");
+    asm!(".Ldigit:");
+    asm!("    sdiv x2, x0, x6");
+    asm!("    msub x3, x2, x6, x0");
+    asm!("    cmp x3, #0");
+    asm!("    cneg x3, x3, lt");
+    asm!("    add x3, x3, #'0'");
+    asm!("    strb w3, [x1, #-1]!");
+    asm!("    mov x0, x2");
+    asm!("    cbnz x0, .Ldigit");
+    asm!("    tbz x5, #63, .Lwrite");
+    asm!("    mov w3, #'-'");
+    asm!("    strb w3, [x1, #-1]!");
+    asm!(".Lwrite:");
+    asm!("    ldr x2, =buf + 32");
+    asm!("    sub x2, x2, x1");
+    asm!("    mov x0, #1 // stdout");
+    asm!("    mov x8, #64 // write");
+    asm!("    svc #0");
+    asm!("    mov x0, #0");
+    asm!("    mov x8, #93 // exit");
+    asm!("    svc #0");
+
+    for trap in Trap::ALL {
+        asm!("{}:", trap_label(trap));
+        asm!("    ldr x1, ={}_msg", trap_label(trap));
+        asm!("    mov x2, #{}", trap.to_string().len() + 1);
+        asm!("    b .Lpanic");
+    }
+    asm!(".Lpanic:");
+    asm!("    mov x0, #2 // stderr");
+    asm!("    mov x8, #64 // write");
+    asm!("    svc #0");
+    asm!("    mov x0, #101");
+    asm!("    mov x8, #93 // exit");
+    asm!("    svc #0");
+    asm!("    .ltorg");
+
+    asm!();
+    asm!("    .section .rodata");
+    for trap in Trap::ALL {
+        asm!("{}_msg:", trap_label(trap));
+        asm!("    .ascii \"{trap}\\n\"");
+    }
+    asm!();
+    asm!("    .bss");
+    asm!("buf:");
+    asm!("    .zero 32");
+    Ok(out)
+}
+
+fn spill(reg: u8) -> usize {
+    (reg as usize - REGS.len()) * 8
+}
@@ -0,0 +1,67 @@
+pub mod aarch64;
+pub mod x86_64;
+
+use crate::expr::BinaryOp;
+use crate::register::compiler::ConstPool;
+use crate::register::op::Op;
+use crate::trap::Trap;
+
+// Both emitters start from `register` bytecode, so virtual register `rN`
+// is the one picked by the register compiler's allocator. Each target maps
+// the first few onto machine registers and spills the rest to the stack.
+
+enum Operand {
+    Reg(u8),
+    Imm(i64),
+}
+
+/// A register op with constant pool references resolved.
+enum Instr {
+    Load(u8, i64),
+    Binary(BinaryOp, u8, u8, Operand),
+    Neg(u8, u8),
+}
+
+impl std::fmt::Display for Instr {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Instr::Load(dst, value) => write!(f, "r{dst} = {value}"),
+            Instr::Binary(op, dst, lhs, Operand::Reg(rhs)) => {
+                write!(f, "r{dst} = r{lhs} {op} r{rhs}")
+            }
+            Instr::Binary(op, dst, lhs, Operand::Imm(value)) => {
+                write!(f, "r{dst} = r{lhs} {op} {value}")
+            }
+            Instr::Neg(dst, rhs) => write!(f, "r{dst} = -r{rhs}"),
+        }
+    }
+}
+
+fn lower(ops: &[Op], pool: &ConstPool) -> Vec<Instr> {
+    use BinaryOp::*;
+    use Operand::*;
+
+    ops.iter()
+        .map(|op| match op {
+            Op::LInt(n) => Instr::Load(n.dst, n.val as i64),
+            Op::LConst(n) => Instr::Load(n.dst, pool[n.idx as usize]),
+            Op::BAdd(n) => Instr::Binary(Add, n.dst, n.lhs, Reg(n.rhs)),
+            Op::BSub(n) => Instr::Binary(Sub, n.dst, n.lhs, Reg(n.rhs)),
+            Op::BMul(n) => Instr::Binary(Mul, n.dst, n.lhs, Reg(n.rhs)),
+            Op::BDiv(n) => Instr::Binary(Div, n.dst, n.lhs, Reg(n.rhs)),
+            Op::UMinus(n) => Instr::Neg(n.dst, n.rhs),
+            Op::BAddI(n) => Instr::Binary(Add, n.dst, n.lhs, Imm(n.imm as i64)),
+            Op::BSubI(n) => Instr::Binary(Sub, n.dst, n.lhs, Imm(n.imm as i64)),
+            Op::BMulI(n) => Instr::Binary(Mul, n.dst, n.lhs, Imm(n.imm as i64)),
+            Op::BDivI(n) => Instr::Binary(Div, n.dst, n.lhs, Imm(n.imm as i64)),
+            Op::BAddK(n) => Instr::Binary(Add, n.dst, n.lhs, Imm(pool[n.kidx as usize])),
+            Op::BSubK(n) => Instr::Binary(Sub, n.dst, n.lhs, Imm(pool[n.kidx as usize])),
+            Op::BMulK(n) => Instr::Binary(Mul, n.dst, n.lhs, Imm(pool[n.kidx as usize])),
+            Op::BDivK(n) => Instr::Binary(Div, n.dst, n.lhs, Imm(pool[n.kidx as usize])),
+        })
+        .collect()
+}
+
+fn trap_label(trap: Trap) -> String {
+    format!(".Ltrap{}", trap as u32)
+}
@@ -0,0 +1,155 @@
+use std::fmt::Write;
+
+use super::lower;
+use super::trap_label;
+use super::Instr;
+use super::Operand;
+use crate::error::Result;
+use crate::expr::BinaryOp;
+use crate::expr::Expr;
+use crate::trap::Trap;
+
+/// Virtual registers which live in machine registers, the rest are spilled.
+/// `rax`, `rcx` and `rdx` are kept free as scratch for `idiv`.
+const REGS: [&str; 11] = [
+    "rbx", "rsi", "rdi", "r8", "r9", "r10", "r11", "r12", "r13", "r14", "r15",
+];
+
+/// Emits a GNU `as` program (Intel syntax) for Linux which prints the result
+/// of `expr` to stdout, or exits with `101` after printing the trap message.
+/// Fails like `register::compiler::compile`.
+pub fn source(expr: &Expr) -> Result<String> {
+    let (ops, pool, stack_size) = crate::register::compiler::compile(expr)?;
+    let spills = stack_size.saturating_sub(REGS.len());
+
+    let mut out = String::new();
+    macro_rules! asm {
+        ($($arg:tt)*) => { writeln!(out, $($arg)*).unwrap() };
+    }
+
+    asm!("    .intel_syntax noprefix");
+    asm!("    .globl _start");
+    asm!("    .text");
+    asm!("_start:");
+    for reg in 0..stack_size {
+        asm!("    # r{reg} -> {}", loc(reg as u8));
+    }
+    if spills > 0 {
+        asm!("    sub rsp, {}", spills * 8);
+    }
+
+    for (i, instr) in lower(&ops, &pool).iter().enumerate() {
+        asm!("    # {instr}");
+        match instr {
+            Instr::Load(dst, value) => {
+                asm!("    mov rax, {value}");
+                asm!("    mov {}, rax", loc(*dst));
+            }
+            Instr::Binary(op, dst, lhs, rhs) => {
+                asm!("    mov rax, {}", loc(*lhs));
+                match rhs {
+                    Operand::Reg(rhs) => asm!("    mov rcx, {}", loc(*rhs)),
+                    Operand::Imm(value) => asm!("    mov rcx, {value}"),
+                }
+                match op {
+                    BinaryOp::Add => {
+                        asm!("    add rax, rcx");
+                        asm!("    jo {}", trap_label(Trap::AddOverflow));
+                    }
+                    BinaryOp::Sub => {
+                        asm!("    sub rax, rcx");
+                        asm!("    jo {}", trap_label(Trap::SubOverflow));
+                    }
+                    BinaryOp::Mul => {
+                        asm!("    imul rax, rcx");
+                        asm!("    jo {}", trap_label(Trap::MulOverflow));
+                    }
+                    BinaryOp::Div => {
+                        asm!("    test rcx, rcx");
+                        asm!("    jz {}", trap_label(Trap::DivByZero));
+                        asm!("    cmp rcx, -1");
+                        asm!("    jne .Ldiv{i}");
+                        asm!("    mov rdx, {}", i64::MIN);
+                        asm!("    cmp rax, rdx");
+                        asm!("    je {}", trap_label(Trap::DivOverflow));
+                        asm!(".Ldiv{i}:");
+                        asm!("    cqo");
+                        asm!("    idiv rcx");
+                    }
+                }
+                asm!("    mov {}, rax", loc(*dst));
+            }
+            Instr::Neg(dst, rhs) => {
+                asm!("    mov rax, {}", loc(*rhs));
+                asm!("    neg rax");
+                asm!("    jo {}", trap_label(Trap::NegOverflow));
+                asm!("    mov {}, rax", loc(*dst));
+            }
+        }
+    }
+
+    asm!("    # print r0 in decimal, digits are written backwards from the end of `buf`");
+    asm!("    mov rax, {}", loc(0));
+    asm!("    mov r8, rax");
+    asm!("    lea rsi, [rip + buf + 32]");
+    asm!("    dec rsi");
+    asm!("    mov byte ptr [rsi], 10");
+    asm!("    mov rcx, 10");
+    asm!(".Ldigit:");
+    asm!("    cqo");
+    asm!("    idiv rcx");
+    asm!("    mov r9, rdx");
+    asm!("    neg r9");
+    asm!("    cmovl r9, rdx");
+    asm!("    add r9, '0'");
+    asm!("    dec rsi");
+    asm!("    mov byte ptr [rsi], r9b");
+    asm!("    test rax, rax");
+    asm!("    jnz .Ldigit");
+    asm!("    test r8, r8");
+    asm!("    jns .Lwrite");
+    asm!("    dec rsi");
+    asm!("    mov byte ptr [rsi], '-'");
+    asm!(".Lwrite:");
+    asm!("    lea rdx, [rip + buf + 32]");
+    asm!("    sub rdx, rsi");
+    asm!("    mov eax, 1 # write");
+    asm!("    mov edi, 1 # stdout");
+    asm!("    syscall");
+    asm!("    mov eax, 60 # exit");
+    asm!("    xor edi, edi");
+    asm!("    syscall");
+
+    for trap in Trap::ALL {
+        asm!("{}:", trap_label(trap));
+        asm!("    lea rsi, [rip + {}_msg]", trap_label(trap));
+        asm!("    mov edx, {}", trap.to_string().len() + 1);
+        asm!("    jmp .Lpanic");
+    }
+    asm!(".Lpanic:");
+    asm!("    mov eax, 1 # write");
+    asm!("    mov edi, 2 # stderr");
+    asm!("    syscall");
+    asm!("    mov eax, 60 # exit");
+    asm!("    mov edi, 101");
+    asm!("    syscall");
+
+    asm!();
+    asm!("    .section .rodata");
+    for trap in Trap::ALL {
+        asm!("{}_msg:", trap_label(trap));
+        asm!("    .ascii \"{trap}\\n\"");
+    }
+    asm!();
+    asm!("    .bss");
+    asm!("buf:");
+    asm!("    .zero 32");
+    Ok(out)
+}
+
+fn loc(reg: u8) -> String {
+    match REGS.get(reg as usize) {
+        Some(name) => name.to_string(),
+        None => format!("qword ptr [rsp + {}]", (reg as usize - REGS.len()) * 8),
+    }
+}
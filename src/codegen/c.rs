@@ -0,0 +1,102 @@
+use std::fmt::Write;
+
+use crate::expr::BinaryOp;
+use crate::expr::Expr;
+use crate::expr::Folder;
+use crate::expr::UnaryOp;
+use crate::trap::Trap;
+
+/// Emits a self-contained `int64_t eval(void)`.
+///
+/// Every operation is checked regardless of how the output is compiled,
+/// reporting the same message as Rust's debug build before calling `abort`.
+pub fn source(expr: &Expr) -> String {
+    let mut out = String::new();
+    writeln!(out, "#include <stdint.h>").unwrap();
+    writeln!(out, "#include <stdio.h>").unwrap();
+    writeln!(out, "#include <stdlib.h>").unwrap();
+    writeln!(out).unwrap();
+    writeln!(out, "static void calc_trap(const char *message) {{").unwrap();
+    writeln!(out, "    fprintf(stderr, \"%s\\n\", message);").unwrap();
+    writeln!(out, "    abort();").unwrap();
+    writeln!(out, "}}").unwrap();
+    writeln!(out).unwrap();
+    writeln!(out, "int64_t eval(void) {{").unwrap();
+    let mut emitter = Emitter { out, next: 0 };
+    let result = expr.fold(&mut emitter);
+    let mut out = emitter.out;
+    writeln!(out, "    return t{result};").unwrap();
+    writeln!(out, "}}").unwrap();
+    out
+}
+
+/// Writes one statement per node, folding each node into the temporary holding its value.
+struct Emitter {
+    out: String,
+    next: usize,
+}
+
+impl Emitter {
+    fn alloc(&mut self) -> usize {
+        let temp = self.next;
+        self.next += 1;
+        temp
+    }
+
+    fn line(&mut self, line: String) {
+        writeln!(self.out, "    {line}").unwrap();
+    }
+
+    fn overflow(&mut self, builtin: &str, lhs: usize, rhs: usize, trap: Trap) -> usize {
+        let dst = self.alloc();
+        self.line(format!(
+            "int64_t t{dst};\n    \
+             if (__builtin_{builtin}_overflow(t{lhs}, t{rhs}, &t{dst})) calc_trap(\"{trap}\");"
+        ));
+        dst
+    }
+}
+
+impl Folder for Emitter {
+    type Output = usize;
+
+    fn fold_binary(&mut self, op: BinaryOp, lhs: usize, rhs: usize) -> usize {
+        match op {
+            BinaryOp::Add => self.overflow("add", lhs, rhs, Trap::AddOverflow),
+            BinaryOp::Sub => self.overflow("sub", lhs, rhs, Trap::SubOverflow),
+            BinaryOp::Mul => self.overflow("mul", lhs, rhs, Trap::MulOverflow),
+            BinaryOp::Div => {
+                let dst = self.alloc();
+                self.line(format!(
+                    "if (t{rhs} == 0) calc_trap(\"{}\");\n    \
+                     if (t{lhs} == INT64_MIN && t{rhs} == -1) calc_trap(\"{}\");\n    \
+                     int64_t t{dst} = t{lhs} / t{rhs};",
+                    Trap::DivByZero,
+                    Trap::DivOverflow,
+                ));
+                dst
+            }
+        }
+    }
+
+    fn fold_unary(&mut self, op: UnaryOp, rhs: usize) -> usize {
+        match op {
+            UnaryOp::Plus => rhs,
+            UnaryOp::Minus => {
+                let dst = self.alloc();
+                self.line(format!(
+                    "if (t{rhs} == INT64_MIN) calc_trap(\"{}\");\n    \
+                     int64_t t{dst} = -t{rhs};",
+                    Trap::NegOverflow,
+                ));
+                dst
+            }
+        }
+    }
+
+    fn fold_int(&mut self, value: i64) -> usize {
+        let dst = self.alloc();
+        self.line(format!("int64_t t{dst} = INT64_C({value});"));
+        dst
+    }
+}
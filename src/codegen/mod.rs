@@ -0,0 +1,4 @@
+pub mod asm;
+pub mod c;
+pub mod rust;
+pub mod wasm;
@@ -0,0 +1,88 @@
+use std::fmt::Write;
+
+use crate::expr::BinaryOp;
+use crate::expr::Expr;
+use crate::expr::Folder;
+use crate::expr::UnaryOp;
+use crate::trap::Trap;
+
+/// Emits a self-contained `pub fn eval() -> i64`.
+///
+/// Every operation is checked regardless of the profile the output is built with,
+/// panicking with the same message as Rust's own overflow checks.
+pub fn source(expr: &Expr) -> String {
+    let mut emitter = Emitter {
+        out: String::from("pub fn eval() -> i64 {\n"),
+        next: 0,
+    };
+    let result = expr.fold(&mut emitter);
+    let mut out = emitter.out;
+    writeln!(out, "    t{result}").unwrap();
+    writeln!(out, "}}").unwrap();
+    out
+}
+
+/// Writes one statement per node, folding each node into the temporary holding its value.
+struct Emitter {
+    out: String,
+    next: usize,
+}
+
+impl Emitter {
+    fn alloc(&mut self) -> usize {
+        let temp = self.next;
+        self.next += 1;
+        temp
+    }
+
+    fn line(&mut self, line: String) {
+        writeln!(self.out, "    {line}").unwrap();
+    }
+
+    fn checked(&mut self, method: &str, lhs: usize, rhs: usize, trap: Trap) -> usize {
+        let dst = self.alloc();
+        self.line(format!(
+            "let t{dst} = t{lhs}.checked_{method}(t{rhs}).expect(\"{trap}\");"
+        ));
+        dst
+    }
+}
+
+impl Folder for Emitter {
+    type Output = usize;
+
+    fn fold_binary(&mut self, op: BinaryOp, lhs: usize, rhs: usize) -> usize {
+        match op {
+            BinaryOp::Add => self.checked("add", lhs, rhs, Trap::AddOverflow),
+            BinaryOp::Sub => self.checked("sub", lhs, rhs, Trap::SubOverflow),
+            BinaryOp::Mul => self.checked("mul", lhs, rhs, Trap::MulOverflow),
+            BinaryOp::Div => {
+                self.line(format!(
+                    "if t{rhs} == 0 {{\n        panic!(\"{}\");\n    }}",
+                    Trap::DivByZero
+                ));
+                self.checked("div", lhs, rhs, Trap::DivOverflow)
+            }
+        }
+    }
+
+    fn fold_unary(&mut self, op: UnaryOp, rhs: usize) -> usize {
+        match op {
+            UnaryOp::Plus => rhs,
+            UnaryOp::Minus => {
+                let dst = self.alloc();
+                self.line(format!(
+                    "let t{dst} = t{rhs}.checked_neg().expect(\"{}\");",
+                    Trap::NegOverflow,
+                ));
+                dst
+            }
+        }
+    }
+
+    fn fold_int(&mut self, value: i64) -> usize {
+        let dst = self.alloc();
+        self.line(format!("let t{dst}: i64 = {value};"));
+        dst
+    }
+}
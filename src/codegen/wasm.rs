@@ -0,0 +1,150 @@
+use crate::expr::Binary;
+use crate::expr::BinaryOp;
+use crate::expr::Expr;
+use crate::expr::Unary;
+use crate::expr::UnaryOp;
+use crate::expr::Visitor;
+
+/// Emits a binary module exporting `eval: [] -> [i64]`.
+///
+/// Arithmetic follows wasm semantics: `add`, `sub` and `mul` wrap, even where
+/// the interpreters panic on overflow, while `div_s` traps on division by zero
+/// and on `i64::MIN / -1`.
+pub fn binary(expr: &Expr) -> Vec<u8> {
+    let mut emitter = BinaryEmitter {
+        code: vec![0x00], // no locals
+    };
+    expr.visit(&mut emitter);
+    let mut body = emitter.code;
+    body.push(0x0B); // end
+
+    let mut code = vec![0x01]; // one function body
+    uleb128(body.len() as u64, &mut code);
+    code.extend(body);
+
+    let mut out = Vec::new();
+    out.extend(b"\0asm");
+    out.extend(1u32.to_le_bytes());
+    section(1, &[0x01, 0x60, 0x00, 0x01, 0x7E], &mut out); // type 0: [] -> [i64]
+    section(3, &[0x01, 0x00], &mut out); // func 0: type 0
+                                         // export "eval" as func 0
+    section(
+        7,
+        &[0x01, 0x04, b'e', b'v', b'a', b'l', 0x00, 0x00],
+        &mut out,
+    );
+    section(10, &code, &mut out);
+    out
+}
+
+/// Emits the same module as `binary` in the text format.
+pub fn text(expr: &Expr) -> String {
+    let mut emitter = TextEmitter { out: String::new() };
+    emitter.line("(module");
+    emitter.line("  (func (export \"eval\") (result i64)");
+    expr.visit(&mut emitter);
+    emitter.line("  )");
+    emitter.line(")");
+    emitter.out
+}
+
+struct BinaryEmitter {
+    code: Vec<u8>,
+}
+
+impl Visitor for BinaryEmitter {
+    fn leave_binary(&mut self, expr: &Binary) {
+        self.code.push(match expr.op {
+            BinaryOp::Add => 0x7C,
+            BinaryOp::Sub => 0x7D,
+            BinaryOp::Mul => 0x7E,
+            BinaryOp::Div => 0x7F,
+        });
+    }
+
+    fn enter_unary(&mut self, expr: &Unary) {
+        if expr.op == UnaryOp::Minus {
+            self.code.extend([0x42, 0x00]); // i64.const 0
+        }
+    }
+
+    fn leave_unary(&mut self, expr: &Unary) {
+        if expr.op == UnaryOp::Minus {
+            self.code.push(0x7D); // i64.sub
+        }
+    }
+
+    fn visit_int(&mut self, value: i64) {
+        self.code.push(0x42); // i64.const
+        sleb128(value, &mut self.code);
+    }
+}
+
+struct TextEmitter {
+    out: String,
+}
+
+impl TextEmitter {
+    fn line(&mut self, line: &str) {
+        self.out.push_str(line);
+        self.out.push('\n');
+    }
+}
+
+impl Visitor for TextEmitter {
+    fn leave_binary(&mut self, expr: &Binary) {
+        self.line(match expr.op {
+            BinaryOp::Add => "    i64.add",
+            BinaryOp::Sub => "    i64.sub",
+            BinaryOp::Mul => "    i64.mul",
+            BinaryOp::Div => "    i64.div_s",
+        });
+    }
+
+    fn enter_unary(&mut self, expr: &Unary) {
+        if expr.op == UnaryOp::Minus {
+            self.line("    i64.const 0");
+        }
+    }
+
+    fn leave_unary(&mut self, expr: &Unary) {
+        if expr.op == UnaryOp::Minus {
+            self.line("    i64.sub");
+        }
+    }
+
+    fn visit_int(&mut self, value: i64) {
+        self.line(&format!("    i64.const {value}"));
+    }
+}
+
+fn section(id: u8, contents: &[u8], out: &mut Vec<u8>) {
+    out.push(id);
+    uleb128(contents.len() as u64, out);
+    out.extend(contents);
+}
+
+fn uleb128(mut value: u64, out: &mut Vec<u8>) {
+    loop {
+        let byte = (value & 0x7F) as u8;
+        value >>= 7;
+        if value == 0 {
+            out.push(byte);
+            break;
+        }
+        out.push(byte | 0x80);
+    }
+}
+
+fn sleb128(mut value: i64, out: &mut Vec<u8>) {
+    loop {
+        let byte = (value & 0x7F) as u8;
+        value >>= 7;
+        let done = (value == 0 && byte & 0x40 == 0) || (value == -1 && byte & 0x40 != 0);
+        if done {
+            out.push(byte);
+            break;
+        }
+        out.push(byte | 0x80);
+    }
+}
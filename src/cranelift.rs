@@ -0,0 +1,176 @@
+use cranelift_codegen::ir::condcodes::IntCC;
+use cranelift_codegen::ir::types;
+use cranelift_codegen::ir::AbiParam;
+use cranelift_codegen::ir::Block;
+use cranelift_codegen::ir::InstBuilder;
+use cranelift_codegen::ir::MemFlags;
+use cranelift_codegen::ir::Value;
+use cranelift_codegen::settings;
+use cranelift_codegen::settings::Configurable;
+use cranelift_frontend::FunctionBuilder;
+use cranelift_frontend::FunctionBuilderContext;
+use cranelift_jit::JITBuilder;
+use cranelift_jit::JITModule;
+use cranelift_module::Linkage;
+use cranelift_module::Module;
+
+use crate::expr::BinaryOp;
+use crate::expr::Expr;
+use crate::expr::Folder;
+use crate::expr::UnaryOp;
+use crate::trap::Trap;
+
+/// Compiled function with the signature `extern "C" fn(out: *mut i64) -> u32`,
+/// which returns `0` or a `Trap`.
+pub struct Program {
+    module: Option<JITModule>,
+    func: extern "C" fn(*mut i64) -> u32,
+}
+
+impl Drop for Program {
+    fn drop(&mut self) {
+        if let Some(module) = self.module.take() {
+            unsafe { module.free_memory() };
+        }
+    }
+}
+
+pub fn compile(expr: &Expr) -> Program {
+    let mut flags = settings::builder();
+    flags.set("opt_level", "speed").unwrap();
+    let isa = cranelift_native::builder()
+        .unwrap()
+        .finish(settings::Flags::new(flags))
+        .unwrap();
+    let mut module = JITModule::new(JITBuilder::with_isa(
+        isa,
+        cranelift_module::default_libcall_names(),
+    ));
+
+    let mut ctx = module.make_context();
+    let ptr = module.target_config().pointer_type();
+    ctx.func.signature.params.push(AbiParam::new(ptr));
+    ctx.func.signature.returns.push(AbiParam::new(types::I32));
+    let id = module
+        .declare_function("eval", Linkage::Export, &ctx.func.signature)
+        .unwrap();
+
+    let mut fn_ctx = FunctionBuilderContext::new();
+    let mut b = FunctionBuilder::new(&mut ctx.func, &mut fn_ctx);
+    let entry = b.create_block();
+    b.append_block_params_for_function_params(entry);
+    b.switch_to_block(entry);
+    b.seal_block(entry);
+    let out = b.block_params(entry)[0];
+
+    let mut lower = Lower {
+        b,
+        traps: [None; Trap::ALL.len()],
+    };
+    let value = expr.fold(&mut lower);
+    let Lower { mut b, traps } = lower;
+    b.ins().store(MemFlags::trusted(), value, out, 0);
+    let ok = b.ins().iconst(types::I32, 0);
+    b.ins().return_(&[ok]);
+
+    for (block, trap) in traps.iter().zip(Trap::ALL) {
+        if let Some(block) = block {
+            b.switch_to_block(*block);
+            let code = b.ins().iconst(types::I32, trap as i64);
+            b.ins().return_(&[code]);
+        }
+    }
+    b.seal_all_blocks();
+    b.finalize();
+
+    module.define_function(id, &mut ctx).unwrap();
+    module.clear_context(&mut ctx);
+    module.finalize_definitions().unwrap();
+
+    let func = unsafe {
+        std::mem::transmute::<*const u8, extern "C" fn(*mut i64) -> u32>(
+            module.get_finalized_function(id),
+        )
+    };
+    Program {
+        module: Some(module),
+        func,
+    }
+}
+
+pub fn eval(program: &Program) -> i64 {
+    let mut out = 0;
+    if let Some(trap) = Trap::from_code((program.func)(&mut out)) {
+        panic!("{trap}");
+    }
+    out
+}
+
+struct Lower<'a> {
+    b: FunctionBuilder<'a>,
+    traps: [Option<Block>; Trap::ALL.len()],
+}
+
+impl Folder for Lower<'_> {
+    type Output = Value;
+
+    fn fold_binary(&mut self, op: BinaryOp, left: Value, right: Value) -> Value {
+        match op {
+            BinaryOp::Add if cfg!(debug_assertions) => {
+                let (value, overflow) = self.b.ins().sadd_overflow(left, right);
+                self.trap_if(overflow, Trap::AddOverflow);
+                value
+            }
+            BinaryOp::Sub if cfg!(debug_assertions) => {
+                let (value, overflow) = self.b.ins().ssub_overflow(left, right);
+                self.trap_if(overflow, Trap::SubOverflow);
+                value
+            }
+            BinaryOp::Mul if cfg!(debug_assertions) => {
+                let (value, overflow) = self.b.ins().smul_overflow(left, right);
+                self.trap_if(overflow, Trap::MulOverflow);
+                value
+            }
+            BinaryOp::Add => self.b.ins().iadd(left, right),
+            BinaryOp::Sub => self.b.ins().isub(left, right),
+            BinaryOp::Mul => self.b.ins().imul(left, right),
+            BinaryOp::Div => {
+                let is_zero = self.b.ins().icmp_imm(IntCC::Equal, right, 0);
+                self.trap_if(is_zero, Trap::DivByZero);
+                let is_min = self.b.ins().icmp_imm(IntCC::Equal, left, i64::MIN);
+                let is_neg_one = self.b.ins().icmp_imm(IntCC::Equal, right, -1);
+                let overflow = self.b.ins().band(is_min, is_neg_one);
+                self.trap_if(overflow, Trap::DivOverflow);
+                self.b.ins().sdiv(left, right)
+            }
+        }
+    }
+
+    fn fold_unary(&mut self, op: UnaryOp, right: Value) -> Value {
+        match op {
+            UnaryOp::Plus => right,
+            UnaryOp::Minus if cfg!(debug_assertions) => {
+                let zero = self.b.ins().iconst(types::I64, 0);
+                let (value, overflow) = self.b.ins().ssub_overflow(zero, right);
+                self.trap_if(overflow, Trap::NegOverflow);
+                value
+            }
+            UnaryOp::Minus => self.b.ins().ineg(right),
+        }
+    }
+
+    fn fold_int(&mut self, value: i64) -> Value {
+        self.b.ins().iconst(types::I64, value)
+    }
+}
+
+impl Lower<'_> {
+    fn trap_if(&mut self, cond: Value, trap: Trap) {
+        let slot = &mut self.traps[trap as usize - 1];
+        let target = *slot.get_or_insert_with(|| self.b.create_block());
+        let next = self.b.create_block();
+        self.b.ins().brif(cond, target, &[], next, &[]);
+        self.b.switch_to_block(next);
+        self.b.seal_block(next);
+    }
+}
@@ -0,0 +1,236 @@
+use std::collections::BTreeSet;
+
+use crate::error::Result;
+use crate::expr::BinaryOp;
+use crate::expr::Expr;
+use crate::register;
+use crate::span::Span;
+use crate::stack;
+use crate::trap::Trap;
+
+enum Code {
+    Stack(stack::compiler::Bytecode),
+    Register(register::compiler::Bytecode),
+}
+
+/// Runs stack or register bytecode one instruction at a time.
+///
+/// Arithmetic is checked, so overflow stops the program with a `Trap`
+/// at the failing instruction instead of panicking.
+pub struct Debugger {
+    code: Code,
+    pool: Vec<i64>,
+    /// Source of the node each instruction was compiled from.
+    spans: Vec<Span>,
+    /// Operand stack, or register file.
+    values: Vec<i64>,
+    pc: usize,
+    breakpoints: BTreeSet<usize>,
+}
+
+impl Debugger {
+    /// `spans` are the spans of `expr`'s nodes in post-order, as returned by `parser::parse_spanned`.
+    pub fn stack(expr: &Expr, spans: &[Span]) -> Self {
+        let (ops, pool, origins) = stack::compiler::compile_with_origins(expr);
+        Debugger {
+            code: Code::Stack(ops),
+            pool,
+            spans: origins.into_iter().map(|node| spans[node]).collect(),
+            values: Vec::new(),
+            pc: 0,
+            breakpoints: BTreeSet::new(),
+        }
+    }
+
+    /// Same as `stack`, for the register VM, which can run out of registers.
+    pub fn register(expr: &Expr, spans: &[Span]) -> Result<Self> {
+        let (ops, pool, stack_size, origins) =
+            register::compiler::compile_with_origins(expr, spans)?;
+        Ok(Debugger {
+            code: Code::Register(ops),
+            pool,
+            spans: origins.into_iter().map(|node| spans[node]).collect(),
+            values: vec![0; stack_size],
+            pc: 0,
+            breakpoints: BTreeSet::new(),
+        })
+    }
+
+    /// Index of the next instruction to run.
+    pub fn pc(&self) -> usize {
+        self.pc
+    }
+
+    pub fn finished(&self) -> bool {
+        self.pc == self.spans.len()
+    }
+
+    /// Value of the expression, once every instruction has run.
+    pub fn result(&self) -> Option<i64> {
+        if self.finished() {
+            self.values.first().copied()
+        } else {
+            None
+        }
+    }
+
+    /// Source of the node the instruction at `index` was compiled from.
+    pub fn span(&self, index: usize) -> Span {
+        self.spans[index]
+    }
+
+    /// The instruction at `index`, like `LInt 5` or `BAdd dst=0 lhs=0 rhs=1`.
+    pub fn disassemble(&self, index: usize) -> String {
+        match &self.code {
+            Code::Stack(ops) => ops[index].to_string(),
+            Code::Register(ops) => ops[index].to_string(),
+        }
+    }
+
+    /// Every instruction, marking breakpoints with `*` and the next instruction with `>`.
+    pub fn listing(&self) -> String {
+        let width = self.spans.len().saturating_sub(1).to_string().len();
+        let mut out = String::new();
+        for index in 0..self.spans.len() {
+            let breakpoint = if self.breakpoints.contains(&index) {
+                '*'
+            } else {
+                ' '
+            };
+            let next = if index == self.pc { '>' } else { ' ' };
+            out.push_str(&format!(
+                "{breakpoint}{next} {index:>width$}  {}\n",
+                self.disassemble(index)
+            ));
+        }
+        out
+    }
+
+    /// The operand stack from the bottom up, or every register.
+    pub fn state(&self) -> String {
+        match self.code {
+            Code::Stack(_) => format!("stack: {:?}", self.values),
+            Code::Register(_) => {
+                let mut out = String::from("registers:");
+                for (reg, value) in self.values.iter().enumerate() {
+                    out.push_str(&format!(" r{reg}={value}"));
+                }
+                out
+            }
+        }
+    }
+
+    /// Sets or clears the breakpoint at `index`, returning whether it's now set.
+    pub fn toggle_breakpoint(&mut self, index: usize) -> Result<bool, String> {
+        if index >= self.spans.len() {
+            return Err(format!(
+                "no instruction {index}, the last one is {}",
+                self.spans.len() - 1
+            ));
+        }
+        if self.breakpoints.remove(&index) {
+            Ok(false)
+        } else {
+            self.breakpoints.insert(index);
+            Ok(true)
+        }
+    }
+
+    /// Runs the next instruction. If it traps, nothing changes and the instruction stays next.
+    pub fn step(&mut self) -> Result<(), Trap> {
+        if self.finished() {
+            return Ok(());
+        }
+        match &self.code {
+            Code::Stack(ops) => step_stack(ops[self.pc], &self.pool, &mut self.values)?,
+            Code::Register(ops) => step_register(&ops[self.pc], &self.pool, &mut self.values)?,
+        }
+        self.pc += 1;
+        Ok(())
+    }
+
+    /// Steps until the end or the next breakpoint, always running at least one instruction.
+    pub fn run(&mut self) -> Result<(), Trap> {
+        self.step()?;
+        while !self.finished() && !self.breakpoints.contains(&self.pc) {
+            self.step()?;
+        }
+        Ok(())
+    }
+}
+
+fn step_stack(op: stack::op::Op, pool: &[i64], stack: &mut Vec<i64>) -> Result<(), Trap> {
+    use stack::op::Op;
+
+    match op {
+        Op::LInt(value) => stack.push(value as i64),
+        Op::LConst(index) => stack.push(pool[index as usize]),
+        Op::BAdd | Op::BSub | Op::BMul | Op::BDiv => {
+            let op = match op {
+                Op::BAdd => BinaryOp::Add,
+                Op::BSub => BinaryOp::Sub,
+                Op::BMul => BinaryOp::Mul,
+                _ => BinaryOp::Div,
+            };
+            let right = stack[stack.len() - 1];
+            let left = stack[stack.len() - 2];
+            let value = binary(op, left, right)?;
+            stack.pop();
+            *stack.last_mut().unwrap() = value;
+        }
+        Op::UMinus => {
+            let top = stack.last_mut().unwrap();
+            *top = negate(*top)?;
+        }
+        Op::AddImm(value) | Op::SubImm(value) | Op::MulImm(value) | Op::DivImm(value) => {
+            let op = match op {
+                Op::AddImm(_) => BinaryOp::Add,
+                Op::SubImm(_) => BinaryOp::Sub,
+                Op::MulImm(_) => BinaryOp::Mul,
+                _ => BinaryOp::Div,
+            };
+            let top = stack.last_mut().unwrap();
+            *top = binary(op, *top, value as i64)?;
+        }
+    }
+    Ok(())
+}
+
+fn step_register(op: &register::op::Op, pool: &[i64], regs: &mut [i64]) -> Result<(), Trap> {
+    use register::op::Op;
+
+    let reg = |reg: u8| regs[reg as usize];
+    let value = match op {
+        Op::LInt(n) => n.val as i64,
+        Op::LConst(n) => pool[n.idx as usize],
+        Op::BAdd(n) => binary(BinaryOp::Add, reg(n.lhs), reg(n.rhs))?,
+        Op::BSub(n) => binary(BinaryOp::Sub, reg(n.lhs), reg(n.rhs))?,
+        Op::BMul(n) => binary(BinaryOp::Mul, reg(n.lhs), reg(n.rhs))?,
+        Op::BDiv(n) => binary(BinaryOp::Div, reg(n.lhs), reg(n.rhs))?,
+        Op::UMinus(n) => negate(reg(n.rhs))?,
+        Op::BAddI(n) => binary(BinaryOp::Add, reg(n.lhs), n.imm as i64)?,
+        Op::BSubI(n) => binary(BinaryOp::Sub, reg(n.lhs), n.imm as i64)?,
+        Op::BMulI(n) => binary(BinaryOp::Mul, reg(n.lhs), n.imm as i64)?,
+        Op::BDivI(n) => binary(BinaryOp::Div, reg(n.lhs), n.imm as i64)?,
+        Op::BAddK(n) => binary(BinaryOp::Add, reg(n.lhs), pool[n.kidx as usize])?,
+        Op::BSubK(n) => binary(BinaryOp::Sub, reg(n.lhs), pool[n.kidx as usize])?,
+        Op::BMulK(n) => binary(BinaryOp::Mul, reg(n.lhs), pool[n.kidx as usize])?,
+        Op::BDivK(n) => binary(BinaryOp::Div, reg(n.lhs), pool[n.kidx as usize])?,
+    };
+    regs[op.dst() as usize] = value;
+    Ok(())
+}
+
+fn binary(op: BinaryOp, left: i64, right: i64) -> Result<i64, Trap> {
+    match op {
+        BinaryOp::Add => left.checked_add(right).ok_or(Trap::AddOverflow),
+        BinaryOp::Sub => left.checked_sub(right).ok_or(Trap::SubOverflow),
+        BinaryOp::Mul => left.checked_mul(right).ok_or(Trap::MulOverflow),
+        BinaryOp::Div if right == 0 => Err(Trap::DivByZero),
+        BinaryOp::Div => left.checked_div(right).ok_or(Trap::DivOverflow),
+    }
+}
+
+fn negate(value: i64) -> Result<i64, Trap> {
+    value.checked_neg().ok_or(Trap::NegOverflow)
+}
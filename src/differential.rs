@@ -0,0 +1,221 @@
+use std::cell::Cell;
+use std::panic::AssertUnwindSafe;
+use std::sync::Once;
+
+use crate::expr::Binary;
+use crate::expr::BinaryOp;
+use crate::expr::Expr;
+use crate::expr::Folder;
+use crate::expr::Unary;
+use crate::expr::UnaryOp;
+use crate::int_mode::IntMode;
+use crate::int_mode::IntType;
+use crate::int_mode::Overflow;
+use crate::source::SourceDb;
+
+// Backends report errors by panicking, with the same messages as `Trap`,
+// so outcomes are compared by catching the panic and keeping its message.
+
+/// Value computed by a backend, or the message it panicked with.
+pub type Outcome = Result<i64, String>;
+
+/// Deepest tree `check` and `check_round_trip` should be given.
+///
+/// The closure backend and `Display` recurse once per level.
+pub const MAX_DEPTH: usize = 256;
+
+/// Evaluates `expr` with `folder::fold` followed by every other backend.
+pub fn eval_all(expr: &Expr) -> Vec<(&'static str, Outcome)> {
+    let mut outcomes = Vec::new();
+    let mut run = |name, f: &dyn Fn() -> i64| outcomes.push((name, catch(f)));
+
+    run("fold", &|| crate::folder::fold(expr));
+
+    // `i64 checked` only matches the other backends when they check for overflow too
+    if cfg!(debug_assertions) {
+        let mode = IntMode {
+            ty: IntType::I64,
+            overflow: Overflow::Checked,
+        };
+        run(
+            "fold_with",
+            &|| match crate::folder::fold_with(expr, mode) {
+                Ok(value) => value.to_i128().unwrap() as i64,
+                Err(trap) => panic!("{trap}"),
+            },
+        );
+    }
+
+    let closure = crate::closure::compile(expr);
+    run("closure", &|| crate::closure::eval(&closure));
+
+    let ops = crate::rpn::compiler::compile(expr);
+    run("rpn", &|| crate::rpn::vm::eval(&ops));
+
+    let (ops, pool) = crate::stack::compiler::compile(expr);
+    run("stack", &|| crate::stack::vm::eval(&ops, &pool));
+    run("unsafe_stack", &|| {
+        crate::unsafe_stack::vm::eval(&ops, &pool)
+    });
+    run("stack_pointer", &|| {
+        crate::stack_pointer::vm::eval(&ops, &pool)
+    });
+
+    let ops = crate::stack::peephole::optimize(&ops);
+    run("stack_peephole", &|| crate::stack::vm::eval(&ops, &pool));
+    run("unsafe_stack_peephole", &|| {
+        crate::unsafe_stack::vm::eval(&ops, &pool)
+    });
+
+    let (ops, pool, stack_size) = crate::alloc_exact_stack::compiler::compile(expr);
+    run("alloc_exact_stack", &|| {
+        crate::alloc_exact_stack::vm::eval(&ops, &pool, stack_size)
+    });
+
+    let (ops, pool, stack_size) =
+        crate::register::compiler::compile(expr).expect("`MAX_DEPTH` fits in the registers");
+    run("register", &|| {
+        crate::register::vm::eval(&ops, &pool, stack_size)
+    });
+    let code = crate::threaded_register::vm::thread(&ops);
+    run("threaded_register", &|| {
+        crate::threaded_register::vm::eval(&code, &pool, stack_size)
+    });
+    let program = crate::jit::vm::jit(ops, pool, stack_size);
+    run("jit", &|| crate::jit::vm::eval(&program));
+
+    let (ops, pool, stack_size) =
+        crate::unsafe_register::compiler::compile(expr).expect("`MAX_DEPTH` fits in the registers");
+    run("unsafe_register", &|| {
+        crate::unsafe_register::vm::eval(&ops, &pool, stack_size)
+    });
+
+    #[cfg(feature = "cranelift")]
+    {
+        let program = crate::cranelift::compile(expr);
+        run("cranelift", &|| crate::cranelift::eval(&program));
+    }
+
+    // the arena backends can only be reached through the parser
+    let mut db = SourceDb::default();
+    let file = db.add("<expr>", crate::formatter::format(expr, usize::MAX));
+    match crate::parser::parse_ast(db.get(file)) {
+        Ok(ast) => {
+            run("fold_arena", &|| crate::folder::fold_ast(&ast));
+            let (ops, pool) = crate::stack::compiler::compile_ast(&ast);
+            run("stack_arena", &|| crate::stack::vm::eval(&ops, &pool));
+        }
+        Err(e) => outcomes.push(("parse_arena", Err(e.report(&db)))),
+    }
+
+    outcomes
+}
+
+/// Returns the outcome all backends agree on, or describes the first one that disagrees with `fold`.
+pub fn check(expr: &Expr) -> Result<Outcome, String> {
+    let mut outcomes = eval_all(expr).into_iter();
+    let (_, expected) = outcomes.next().unwrap();
+    for (name, outcome) in outcomes {
+        if outcome != expected {
+            return Err(format!(
+                "`{expr}`: fold gave {expected:?}, but {name} gave {outcome:?}"
+            ));
+        }
+    }
+    Ok(expected)
+}
+
+/// Checks that both `Display` and `formatter::format` output parse back to `expr`.
+///
+/// Negative literals are expected to come back as a unary minus applied to a literal.
+pub fn check_round_trip(expr: &Expr) -> Result<(), String> {
+    let expected = expr.fold(&mut NegateLiterals);
+    for text in [expr.to_string(), crate::formatter::format(expr, 80)] {
+        let mut db = SourceDb::default();
+        let file = db.add("<expr>", text.clone());
+        let parsed = crate::parser::parse(db.get(file)).map_err(|e| e.report(&db))?;
+        if parsed != expected {
+            return Err(format!(
+                "`{text}` parsed as `{parsed}`, expected `{expected}`"
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Number of nodes on the longest path from the root to a leaf.
+pub fn depth(expr: &Expr) -> usize {
+    expr.fold(&mut Depth)
+}
+
+thread_local! {
+    static QUIET: Cell<bool> = const { Cell::new(false) };
+}
+
+/// Runs `f`, catching a panic without printing it.
+pub fn catch<T>(f: impl FnOnce() -> T) -> Result<T, String> {
+    static INSTALL_HOOK: Once = Once::new();
+    INSTALL_HOOK.call_once(|| {
+        let hook = std::panic::take_hook();
+        std::panic::set_hook(Box::new(move |info| {
+            if !QUIET.get() {
+                hook(info);
+            }
+        }));
+    });
+
+    QUIET.set(true);
+    let result = std::panic::catch_unwind(AssertUnwindSafe(f));
+    QUIET.set(false);
+    result.map_err(|payload| match payload.downcast::<String>() {
+        Ok(message) => *message,
+        Err(payload) => match payload.downcast::<&str>() {
+            Ok(message) => message.to_string(),
+            Err(_) => "panicked".to_string(),
+        },
+    })
+}
+
+/// Rewrites negative literals the way the parser reads them back.
+struct NegateLiterals;
+
+impl Folder for NegateLiterals {
+    type Output = Expr;
+
+    fn fold_binary(&mut self, op: BinaryOp, left: Expr, right: Expr) -> Expr {
+        Expr::Binary(Box::new(Binary { left, op, right }))
+    }
+
+    fn fold_unary(&mut self, op: UnaryOp, right: Expr) -> Expr {
+        Expr::Unary(Box::new(Unary { op, right }))
+    }
+
+    fn fold_int(&mut self, value: i64) -> Expr {
+        if value < 0 {
+            Expr::Unary(Box::new(Unary {
+                op: UnaryOp::Minus,
+                right: Expr::Int(value.wrapping_neg()),
+            }))
+        } else {
+            Expr::Int(value)
+        }
+    }
+}
+
+struct Depth;
+
+impl Folder for Depth {
+    type Output = usize;
+
+    fn fold_binary(&mut self, _: BinaryOp, left: usize, right: usize) -> usize {
+        left.max(right) + 1
+    }
+
+    fn fold_unary(&mut self, _: UnaryOp, right: usize) -> usize {
+        right + 1
+    }
+
+    fn fold_int(&mut self, _: i64) -> usize {
+        1
+    }
+}
@@ -1,56 +1,32 @@
-use std::sync::Arc;
-
+use crate::source::SourceDb;
 use crate::span::Span;
 
 #[derive(Debug)]
 pub struct Error {
-    src: Arc<str>,
     span: Span,
     message: String,
 }
 
 impl Error {
-    pub fn new(src: Arc<str>, span: Span, message: String) -> Self {
-        Error { src, span, message }
+    pub fn new(span: Span, message: String) -> Self {
+        Error { span, message }
     }
 
-    pub fn report(&self) -> String {
-        use core::fmt::Write;
+    pub fn span(&self) -> Span {
+        self.span
+    }
 
-        let src = &self.src;
-        let span = &self.span;
-        let message = &self.message;
+    pub fn message(&self) -> &str {
+        &self.message
+    }
 
+    /// Renders the error along with the offending line of its file in `db`.
+    pub fn report(&self, db: &SourceDb) -> String {
         // empty span
-        if span.start == span.end {
-            return message.clone();
+        if self.span.start == self.span.end {
+            return self.message.clone();
         }
-
-        let mut out = String::new();
-        writeln!(&mut out, "{message}:").unwrap();
-        let line_start = src[..span.start].rfind('\n').map(|v| v + 1).unwrap_or(0);
-        let line_end = src[span.start..]
-            .find('\n')
-            .map(|v| v + span.start)
-            .unwrap_or(src.len());
-        writeln!(&mut out, "  {}", &src[line_start..line_end]).unwrap();
-        let cursor_pos = span.start - line_start;
-        let cursor_len = if span.end > line_end {
-            line_end - span.start
-        } else {
-            span.end - span.start
-        };
-        writeln!(
-            &mut out,
-            "  {:w$}{:^<l$}",
-            "",
-            "^",
-            w = cursor_pos,
-            l = cursor_len
-        )
-        .unwrap();
-
-        out
+        format!("{}:\n{}", self.message, db.snippet(self.span))
     }
 }
 
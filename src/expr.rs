@@ -1,4 +1,11 @@
-#[derive(Debug)]
+/// Sequence of expression statements separated by `;` or line breaks.
+/// Its value is the value of the last statement.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Program {
+    pub stmts: Vec<Expr>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
 #[cfg_attr(feature = "random_ast", derive(arbitrary::Arbitrary))]
 pub enum Expr {
     Binary(Box<Binary>),
@@ -6,32 +13,282 @@ pub enum Expr {
     Int(#[cfg_attr(feature = "random_ast", arbitrary(with = small_i64))] i64),
 }
 
+/// Same as `Expr`, but may also contain units and conversions.
+/// Only comes from `parser::parse_units_program`, and only `folder::fold_units` evaluates it.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum UnitExpr {
+    Binary(Box<Binary<UnitExpr>>),
+    Unary(Box<Unary<UnitExpr>>),
+    Int(i64),
+    Unit(Box<UnitPower>),
+    Convert(Box<Convert>),
+}
+
 #[cfg(feature = "random_ast")]
 fn small_i64(u: &mut arbitrary::Unstructured) -> arbitrary::Result<i64> {
     u.int_in_range(i8::MIN..=i8::MAX).map(i64::from)
 }
 
 impl Expr {
-    #[cfg(feature = "random_ast")]
+    /// Random expression with the default `generator::Config`, which all backends can evaluate.
     pub fn generate() -> Expr {
-        use arbitrary::Unstructured;
-        use rand::{seq::SliceRandom, thread_rng};
+        let config = crate::generator::Config {
+            safe: true,
+            ..Default::default()
+        };
+        crate::generator::Generator::new(rand::random(), config).generate()
+    }
+
+    /// Folds the tree bottom-up, see `Folder`.
+    ///
+    /// Uses an explicit stack, so it works on trees of any depth.
+    pub fn fold<F: Folder + ?Sized>(&self, folder: &mut F) -> F::Output {
+        enum Frame<'a> {
+            Enter(&'a Expr),
+            Binary(BinaryOp),
+            Unary(UnaryOp),
+        }
+
+        let mut frames = vec![Frame::Enter(self)];
+        let mut values = Vec::new();
+        while let Some(frame) = frames.pop() {
+            match frame {
+                Frame::Enter(Expr::Binary(expr)) => {
+                    frames.push(Frame::Binary(expr.op));
+                    frames.push(Frame::Enter(&expr.right));
+                    frames.push(Frame::Enter(&expr.left));
+                }
+                Frame::Enter(Expr::Unary(expr)) => {
+                    frames.push(Frame::Unary(expr.op));
+                    frames.push(Frame::Enter(&expr.right));
+                }
+                Frame::Enter(Expr::Int(value)) => values.push(folder.fold_int(*value)),
+                Frame::Binary(op) => {
+                    let right = values.pop().unwrap();
+                    let left = values.pop().unwrap();
+                    values.push(folder.fold_binary(op, left, right));
+                }
+                Frame::Unary(op) => {
+                    let right = values.pop().unwrap();
+                    values.push(folder.fold_unary(op, right));
+                }
+            }
+        }
+        values.pop().unwrap()
+    }
+
+    /// Visits the tree in evaluation order, see `Visitor`.
+    ///
+    /// Uses an explicit stack, so it works on trees of any depth.
+    pub fn visit<V: Visitor + ?Sized>(&self, visitor: &mut V) {
+        enum Frame<'a> {
+            Enter(&'a Expr),
+            Binary(&'a Binary),
+            Unary(&'a Unary),
+        }
+
+        let mut frames = vec![Frame::Enter(self)];
+        while let Some(frame) = frames.pop() {
+            match frame {
+                Frame::Enter(Expr::Binary(expr)) => {
+                    visitor.enter_binary(expr);
+                    frames.push(Frame::Binary(expr));
+                    frames.push(Frame::Enter(&expr.right));
+                    frames.push(Frame::Enter(&expr.left));
+                }
+                Frame::Enter(Expr::Unary(expr)) => {
+                    visitor.enter_unary(expr);
+                    frames.push(Frame::Unary(expr));
+                    frames.push(Frame::Enter(&expr.right));
+                }
+                Frame::Enter(Expr::Int(value)) => visitor.visit_int(*value),
+                Frame::Binary(expr) => visitor.leave_binary(expr),
+                Frame::Unary(expr) => visitor.leave_unary(expr),
+            }
+        }
+    }
+}
+
+impl Drop for Expr {
+    /// The default drop glue recurses once per level,
+    /// so deep trees are taken apart with an explicit stack instead.
+    fn drop(&mut self) {
+        fn is_leaf(expr: &Expr) -> bool {
+            matches!(expr, Expr::Int(_))
+        }
+
+        let shallow = match self {
+            Expr::Binary(expr) => is_leaf(&expr.left) && is_leaf(&expr.right),
+            Expr::Unary(expr) => is_leaf(&expr.right),
+            Expr::Int(_) => true,
+        };
+        if shallow {
+            return;
+        }
 
-        let mut seed: Vec<u8> = (1..4096).map(|v| (v % u8::MAX as i32) as u8).collect();
-        seed.shuffle(&mut thread_rng());
-        Unstructured::new(&seed).arbitrary().unwrap()
+        let mut stack = vec![std::mem::replace(self, Expr::Int(0))];
+        while let Some(mut expr) = stack.pop() {
+            match &mut expr {
+                Expr::Binary(expr) => {
+                    stack.push(std::mem::replace(&mut expr.left, Expr::Int(0)));
+                    stack.push(std::mem::replace(&mut expr.right, Expr::Int(0)));
+                }
+                Expr::Unary(expr) => stack.push(std::mem::replace(&mut expr.right, Expr::Int(0))),
+                Expr::Int(_) => {}
+            }
+            // `expr` only has leaf children left, so dropping it doesn't recurse
+        }
+    }
+}
+
+impl UnitExpr {
+    /// Same as `Expr::fold`, see `UnitFolder`.
+    pub fn fold<F: UnitFolder + ?Sized>(&self, folder: &mut F) -> F::Output {
+        enum Frame<'a> {
+            Enter(&'a UnitExpr),
+            Binary(BinaryOp),
+            Unary(UnaryOp),
+            Convert,
+        }
+
+        let mut frames = vec![Frame::Enter(self)];
+        let mut values = Vec::new();
+        while let Some(frame) = frames.pop() {
+            match frame {
+                Frame::Enter(UnitExpr::Binary(expr)) => {
+                    frames.push(Frame::Binary(expr.op));
+                    frames.push(Frame::Enter(&expr.right));
+                    frames.push(Frame::Enter(&expr.left));
+                }
+                Frame::Enter(UnitExpr::Unary(expr)) => {
+                    frames.push(Frame::Unary(expr.op));
+                    frames.push(Frame::Enter(&expr.right));
+                }
+                Frame::Enter(UnitExpr::Int(value)) => values.push(folder.fold_int(*value)),
+                Frame::Enter(UnitExpr::Unit(unit)) => values.push(folder.fold_unit(unit)),
+                Frame::Enter(UnitExpr::Convert(expr)) => {
+                    frames.push(Frame::Convert);
+                    frames.push(Frame::Enter(&expr.target));
+                    frames.push(Frame::Enter(&expr.value));
+                }
+                Frame::Binary(op) => {
+                    let right = values.pop().unwrap();
+                    let left = values.pop().unwrap();
+                    values.push(folder.fold_binary(op, left, right));
+                }
+                Frame::Unary(op) => {
+                    let right = values.pop().unwrap();
+                    values.push(folder.fold_unary(op, right));
+                }
+                Frame::Convert => {
+                    let target = values.pop().unwrap();
+                    let value = values.pop().unwrap();
+                    values.push(folder.fold_convert(value, target));
+                }
+            }
+        }
+        values.pop().unwrap()
+    }
+}
+
+impl Drop for UnitExpr {
+    /// Same as for `Expr`.
+    fn drop(&mut self) {
+        fn is_leaf(expr: &UnitExpr) -> bool {
+            matches!(expr, UnitExpr::Int(_) | UnitExpr::Unit(_))
+        }
+
+        let shallow = match self {
+            UnitExpr::Binary(expr) => is_leaf(&expr.left) && is_leaf(&expr.right),
+            UnitExpr::Unary(expr) => is_leaf(&expr.right),
+            UnitExpr::Convert(expr) => is_leaf(&expr.value) && is_leaf(&expr.target),
+            UnitExpr::Int(_) | UnitExpr::Unit(_) => true,
+        };
+        if shallow {
+            return;
+        }
+
+        let mut stack = vec![std::mem::replace(self, UnitExpr::Int(0))];
+        while let Some(mut expr) = stack.pop() {
+            match &mut expr {
+                UnitExpr::Binary(expr) => {
+                    stack.push(std::mem::replace(&mut expr.left, UnitExpr::Int(0)));
+                    stack.push(std::mem::replace(&mut expr.right, UnitExpr::Int(0)));
+                }
+                UnitExpr::Unary(expr) => {
+                    stack.push(std::mem::replace(&mut expr.right, UnitExpr::Int(0)))
+                }
+                UnitExpr::Convert(expr) => {
+                    stack.push(std::mem::replace(&mut expr.value, UnitExpr::Int(0)));
+                    stack.push(std::mem::replace(&mut expr.target, UnitExpr::Int(0)));
+                }
+                UnitExpr::Int(_) | UnitExpr::Unit(_) => {}
+            }
+            // `expr` only has leaf children left, so dropping it doesn't recurse
+        }
     }
 }
 
-#[derive(Debug)]
+/// Walks the tree by reference, driven by `Expr::visit`. Every method
+/// defaults to doing nothing, so implementors only override the nodes
+/// they care about: `enter_*` runs before the children of a node are
+/// visited and `leave_*` after them.
+pub trait Visitor {
+    fn enter_binary(&mut self, expr: &Binary) {
+        let _ = expr;
+    }
+
+    fn leave_binary(&mut self, expr: &Binary) {
+        let _ = expr;
+    }
+
+    fn enter_unary(&mut self, expr: &Unary) {
+        let _ = expr;
+    }
+
+    fn leave_unary(&mut self, expr: &Unary) {
+        let _ = expr;
+    }
+
+    fn visit_int(&mut self, value: i64) {
+        let _ = value;
+    }
+}
+
+/// Computes one `Output` per node from the outputs of its children,
+/// driven by `Expr::fold`.
+pub trait Folder {
+    type Output;
+
+    fn fold_binary(
+        &mut self,
+        op: BinaryOp,
+        left: Self::Output,
+        right: Self::Output,
+    ) -> Self::Output;
+
+    fn fold_unary(&mut self, op: UnaryOp, right: Self::Output) -> Self::Output;
+
+    fn fold_int(&mut self, value: i64) -> Self::Output;
+}
+
+/// `Folder` for trees with units, driven by `UnitExpr::fold`.
+pub trait UnitFolder: Folder {
+    fn fold_unit(&mut self, unit: &UnitPower) -> Self::Output;
+
+    fn fold_convert(&mut self, value: Self::Output, target: Self::Output) -> Self::Output;
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
 #[cfg_attr(feature = "random_ast", derive(arbitrary::Arbitrary))]
-pub struct Binary {
-    pub left: Expr,
+pub struct Binary<E = Expr> {
+    pub left: E,
     pub op: BinaryOp,
-    pub right: Expr,
+    pub right: E,
 }
 
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "random_ast", derive(arbitrary::Arbitrary))]
 pub enum BinaryOp {
     Add,
     Sub,
@@ -39,28 +296,34 @@ pub enum BinaryOp {
     Div,
 }
 
-#[cfg(feature = "random_ast")]
-impl<'a> arbitrary::Arbitrary<'a> for BinaryOp {
-    fn arbitrary(u: &mut arbitrary::Unstructured<'a>) -> arbitrary::Result<Self> {
-        let options = [BinaryOp::Add, BinaryOp::Sub, BinaryOp::Mul];
-        u.choose(&options).copied()
-    }
-}
-
-#[derive(Debug)]
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
 #[cfg_attr(feature = "random_ast", derive(arbitrary::Arbitrary))]
-pub struct Unary {
+pub struct Unary<E = Expr> {
     pub op: UnaryOp,
-    pub right: Expr,
+    pub right: E,
 }
 
-#[derive(Clone, Copy, Debug)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
 #[cfg_attr(feature = "random_ast", derive(arbitrary::Arbitrary))]
 pub enum UnaryOp {
     Plus,
     Minus,
 }
 
+/// A unit of measure raised to a power, like `km` or `s^2`.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct UnitPower {
+    pub name: String,
+    pub exponent: i8,
+}
+
+/// `value in target`, where `target` only consists of units.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct Convert {
+    pub value: UnitExpr,
+    pub target: UnitExpr,
+}
+
 impl std::fmt::Display for Expr {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
@@ -71,7 +334,19 @@ impl std::fmt::Display for Expr {
     }
 }
 
-impl std::fmt::Display for Binary {
+impl std::fmt::Display for UnitExpr {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            UnitExpr::Binary(expr) => write!(f, "({expr})"),
+            UnitExpr::Unary(expr) => write!(f, "({expr})"),
+            UnitExpr::Int(value) => write!(f, "{value}"),
+            UnitExpr::Unit(unit) => write!(f, "{unit}"),
+            UnitExpr::Convert(expr) => write!(f, "({} in {})", expr.value, expr.target),
+        }
+    }
+}
+
+impl<E: std::fmt::Display> std::fmt::Display for Binary<E> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         let Self { left, op, right } = self;
         write!(f, "{left} {op} {right}")
@@ -89,7 +364,23 @@ impl std::fmt::Display for BinaryOp {
     }
 }
 
-impl std::fmt::Display for Unary {
+impl std::str::FromStr for BinaryOp {
+    type Err = String;
+
+    fn from_str(op: &str) -> Result<Self, Self::Err> {
+        match op {
+            "+" => Ok(BinaryOp::Add),
+            "-" => Ok(BinaryOp::Sub),
+            "*" => Ok(BinaryOp::Mul),
+            "/" => Ok(BinaryOp::Div),
+            _ => Err(format!(
+                "unknown operator `{op}`, expected `+`, `-`, `*` or `/`"
+            )),
+        }
+    }
+}
+
+impl<E: std::fmt::Display> std::fmt::Display for Unary<E> {
     fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         let Self { op, right } = self;
         write!(f, "{op} {right}")
@@ -104,3 +395,13 @@ impl std::fmt::Display for UnaryOp {
         }
     }
 }
+
+impl std::fmt::Display for UnitPower {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.name)?;
+        if self.exponent != 1 {
+            write!(f, "^{}", self.exponent)?;
+        }
+        Ok(())
+    }
+}
@@ -1,26 +1,275 @@
+use crate::arena::Ast;
+use crate::error::Error;
 use crate::expr::BinaryOp;
 use crate::expr::Expr;
+use crate::expr::Folder;
 use crate::expr::UnaryOp;
+use crate::expr::UnitExpr;
+use crate::expr::UnitFolder;
+use crate::expr::UnitPower;
+use crate::int_mode::IntMode;
+use crate::int_mode::Value;
+use crate::parser::Builder;
+use crate::rational::Rational;
+use crate::source::SourceFile;
+use crate::span::Span;
+use crate::trap::Trap;
+use crate::units::Quantity;
 
 pub fn fold(expr: &Expr) -> i64 {
-    match expr {
-        Expr::Binary(expr) => {
-            let left = fold(&expr.left);
-            let right = fold(&expr.right);
-            match expr.op {
-                BinaryOp::Add => left + right,
-                BinaryOp::Sub => left - right,
-                BinaryOp::Mul => left * right,
-                BinaryOp::Div => left / right,
-            }
+    expr.fold(&mut Eval)
+}
+
+pub fn fold_ast(ast: &Ast) -> i64 {
+    ast.fold(&mut Eval)
+}
+
+/// Same as `fold`, but with exact fractions instead of truncating division.
+pub fn fold_rational(expr: &Expr) -> Result<Rational, Trap> {
+    expr.fold(&mut RationalEval)
+}
+
+/// Evaluates with the integer type and overflow behavior of `mode` instead of `i64`.
+///
+/// A minus applied directly to a literal is part of the literal, like in Rust,
+/// so `-128` is in range for `i8` and `-1` isn't for `u8`.
+pub fn fold_with(expr: &Expr, mode: IntMode) -> Result<Value, Trap> {
+    let mut eval = IntEval(mode);
+    let operand = expr.fold(&mut eval)?;
+    eval.value(operand)
+}
+
+/// Same as `fold_with`, but evaluates every statement of `file` while parsing it,
+/// so literals only have to fit the mode's type rather than an `i64`, like `u128::MAX`.
+///
+/// Fails if `file` doesn't parse. Otherwise every statement has its value,
+/// or the trap it raised as an error at the operator or literal raising it.
+pub fn parse_and_fold_with(
+    file: &SourceFile,
+    max_depth: usize,
+    mode: IntMode,
+) -> Result<Vec<Result<Value, Error>>, Error> {
+    let mut eval = IntEval(mode);
+    let stmts = crate::parser::build_program(file, &mut eval, max_depth)?;
+    Ok(stmts.into_iter().map(|stmt| eval.value_at(stmt)).collect())
+}
+
+/// Evaluates quantities with units of measure, checking that dimensions agree.
+///
+/// `spans` are the spans of `expr`'s nodes in post-order, as returned by
+/// `parser::parse_units_program`, or empty if there's no source.
+pub fn fold_units(expr: &UnitExpr, spans: &[Span]) -> Result<Quantity, Error> {
+    expr.fold(&mut UnitEval { spans, next: 0 })
+}
+
+struct Eval;
+
+impl Folder for Eval {
+    type Output = i64;
+
+    fn fold_binary(&mut self, op: BinaryOp, left: i64, right: i64) -> i64 {
+        match op {
+            BinaryOp::Add => left + right,
+            BinaryOp::Sub => left - right,
+            BinaryOp::Mul => left * right,
+            BinaryOp::Div => left / right,
+        }
+    }
+
+    fn fold_unary(&mut self, op: UnaryOp, right: i64) -> i64 {
+        match op {
+            UnaryOp::Plus => right,
+            UnaryOp::Minus => -right,
+        }
+    }
+
+    fn fold_int(&mut self, value: i64) -> i64 {
+        value
+    }
+}
+
+struct RationalEval;
+
+impl Folder for RationalEval {
+    type Output = Result<Rational, Trap>;
+
+    fn fold_binary(
+        &mut self,
+        op: BinaryOp,
+        left: Self::Output,
+        right: Self::Output,
+    ) -> Self::Output {
+        let (left, right) = (left?, right?);
+        match op {
+            BinaryOp::Add => left.checked_add(right),
+            BinaryOp::Sub => left.checked_sub(right),
+            BinaryOp::Mul => left.checked_mul(right),
+            BinaryOp::Div => left.checked_div(right),
+        }
+    }
+
+    fn fold_unary(&mut self, op: UnaryOp, right: Self::Output) -> Self::Output {
+        match op {
+            UnaryOp::Plus => right,
+            UnaryOp::Minus => right?.checked_neg(),
+        }
+    }
+
+    fn fold_int(&mut self, value: i64) -> Self::Output {
+        Ok(Rational::from_int(value))
+    }
+}
+
+struct IntEval(IntMode);
+
+/// Literals are only converted to the mode's type once it's known whether they're negated.
+enum Operand {
+    /// Magnitude, and whether the literal itself is negative.
+    Literal(u128, bool),
+    Value(Value),
+}
+
+impl IntEval {
+    fn value(&self, operand: Operand) -> Result<Value, Trap> {
+        match operand {
+            Operand::Literal(magnitude, negative) => self.0.wide_literal(magnitude, negative),
+            Operand::Value(value) => Ok(value),
         }
-        Expr::Unary(expr) => {
-            let right = fold(&expr.right);
-            match expr.op {
-                UnaryOp::Plus => right,
-                UnaryOp::Minus => -right,
+    }
+
+    fn value_at(&self, (operand, span): (Result<Operand, Error>, Span)) -> Result<Value, Error> {
+        self.value(operand?)
+            .map_err(|trap| Error::new(span, trap.to_string()))
+    }
+}
+
+impl Folder for IntEval {
+    type Output = Result<Operand, Trap>;
+
+    fn fold_binary(
+        &mut self,
+        op: BinaryOp,
+        left: Self::Output,
+        right: Self::Output,
+    ) -> Self::Output {
+        let left = self.value(left?)?;
+        let right = self.value(right?)?;
+        self.0.binary(op, left, right).map(Operand::Value)
+    }
+
+    fn fold_unary(&mut self, op: UnaryOp, right: Self::Output) -> Self::Output {
+        match (op, right?) {
+            (UnaryOp::Plus, right) => Ok(right),
+            (UnaryOp::Minus, Operand::Literal(magnitude, negative)) => self
+                .0
+                .wide_literal(magnitude, !negative)
+                .map(Operand::Value),
+            (op, right) => {
+                let right = self.value(right)?;
+                self.0.unary(op, right).map(Operand::Value)
             }
         }
-        Expr::Int(value) => *value,
+    }
+
+    fn fold_int(&mut self, value: i64) -> Self::Output {
+        // only generated trees have negative literals
+        Ok(Operand::Literal(value.unsigned_abs().into(), value < 0))
+    }
+}
+
+/// Evaluates while parsing, so literals may be as wide as the mode's type.
+/// Nodes keep their span to report traps at, and carry traps up to the statement
+/// instead of failing the parse, so earlier statements still have a value.
+impl Builder for IntEval {
+    type Node = (Result<Operand, Error>, Span);
+
+    fn binary(
+        &mut self,
+        left: Self::Node,
+        op: BinaryOp,
+        right: Self::Node,
+        span: Span,
+    ) -> Result<Self::Node, Error> {
+        let value = self.value_at(left).and_then(|left| {
+            let right = self.value_at(right)?;
+            let value = self.0.binary(op, left, right);
+            value.map_err(|trap| Error::new(span, trap.to_string()))
+        });
+        Ok((value.map(Operand::Value), span))
+    }
+
+    fn unary(
+        &mut self,
+        op: UnaryOp,
+        (right, right_span): Self::Node,
+        span: Span,
+    ) -> Result<Self::Node, Error> {
+        let span = Span::new(span.file, span.start..right_span.end);
+        let value = right.and_then(|right| {
+            let value = self.fold_unary(op, Ok(right));
+            value.map_err(|trap| Error::new(span, trap.to_string()))
+        });
+        Ok((value, span))
+    }
+
+    fn int(&mut self, value: i64, span: Span) -> Result<Self::Node, Error> {
+        let operand = Operand::Literal(value.unsigned_abs().into(), value < 0);
+        Ok((Ok(operand), span))
+    }
+
+    fn literal(&mut self, value: u128, span: Span) -> Result<Self::Node, Error> {
+        Ok((Ok(Operand::Literal(value, false)), span))
+    }
+}
+
+struct UnitEval<'a> {
+    spans: &'a [Span],
+    /// Post-order index of the next node, which is the order `UnitExpr::fold` visits them in.
+    next: usize,
+}
+
+impl UnitEval<'_> {
+    fn span(&mut self) -> Span {
+        let span = self.spans.get(self.next).copied().unwrap_or_default();
+        self.next += 1;
+        span
+    }
+}
+
+impl Folder for UnitEval<'_> {
+    type Output = Result<Quantity, Error>;
+
+    fn fold_binary(
+        &mut self,
+        op: BinaryOp,
+        left: Self::Output,
+        right: Self::Output,
+    ) -> Self::Output {
+        let span = self.span();
+        let (left, right) = (left?, right?);
+        left.binary(op, right).map_err(|e| Error::new(span, e))
+    }
+
+    fn fold_unary(&mut self, op: UnaryOp, right: Self::Output) -> Self::Output {
+        self.span();
+        Ok(right?.unary(op))
+    }
+
+    fn fold_int(&mut self, value: i64) -> Self::Output {
+        self.span();
+        Ok(Quantity::from_int(value))
+    }
+}
+
+impl UnitFolder for UnitEval<'_> {
+    fn fold_unit(&mut self, unit: &UnitPower) -> Self::Output {
+        let span = self.span();
+        Quantity::unit(unit).map_err(|e| Error::new(span, e))
+    }
+
+    fn fold_convert(&mut self, value: Self::Output, target: Self::Output) -> Self::Output {
+        let span = self.span();
+        let (value, target) = (value?, target?);
+        value.convert(target).map_err(|e| Error::new(span, e))
     }
 }
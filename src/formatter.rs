@@ -0,0 +1,184 @@
+use crate::expr::Binary;
+use crate::expr::BinaryOp;
+use crate::expr::Convert;
+use crate::expr::Expr;
+use crate::expr::Unary;
+use crate::expr::UnitExpr;
+use crate::expr::UnitPower;
+
+/// Formats `expr` with the minimal amount of parentheses required for
+/// `parser::parse` to produce the same tree again.
+///
+/// Binary chains which don't fit in `width` columns are broken up after each operator,
+/// with every operand aligned to the first one. Ending the line with the operator
+/// lets `parser::parse_program` read the next line as part of the same statement.
+///
+/// Negative literals can only come from `generator::Generator`, and are printed
+/// the same way as a unary minus applied to a literal.
+pub fn format(expr: &Expr, width: usize) -> String {
+    let mut out = String::new();
+    emit(expr, 0, width, &mut out);
+    out
+}
+
+/// Same as `format`, for `parser::parse_units_program`.
+pub fn format_units(expr: &UnitExpr, width: usize) -> String {
+    let mut out = String::new();
+    emit(expr, 0, width, &mut out);
+    out
+}
+
+/// Lets the formatter handle `Expr` and `UnitExpr` alike.
+trait Tree: Sized {
+    fn node(&self) -> Node<'_, Self>;
+}
+
+enum Node<'a, E> {
+    Binary(&'a Binary<E>),
+    Unary(&'a Unary<E>),
+    Int(i64),
+    Unit(&'a UnitPower),
+    Convert(&'a Convert),
+}
+
+impl Tree for Expr {
+    fn node(&self) -> Node<'_, Self> {
+        match self {
+            Expr::Binary(expr) => Node::Binary(expr),
+            Expr::Unary(expr) => Node::Unary(expr),
+            Expr::Int(value) => Node::Int(*value),
+        }
+    }
+}
+
+impl Tree for UnitExpr {
+    fn node(&self) -> Node<'_, Self> {
+        match self {
+            UnitExpr::Binary(expr) => Node::Binary(expr),
+            UnitExpr::Unary(expr) => Node::Unary(expr),
+            UnitExpr::Int(value) => Node::Int(*value),
+            UnitExpr::Unit(unit) => Node::Unit(unit),
+            UnitExpr::Convert(expr) => Node::Convert(expr),
+        }
+    }
+}
+
+// The parser handles `*` and `/` *before* `+` and `-`,
+// so the multiplicative operators bind the loosest, except for conversions.
+const PREC_CONVERT: u8 = 0;
+const PREC_MUL: u8 = 1;
+const PREC_ADD: u8 = 2;
+const PREC_UNARY: u8 = 3;
+const PREC_ATOM: u8 = 4;
+
+fn precedence<E: Tree>(expr: &E) -> u8 {
+    match expr.node() {
+        Node::Binary(expr) => binary_precedence(expr.op),
+        Node::Unary(_) => PREC_UNARY,
+        Node::Int(value) if value < 0 => PREC_UNARY,
+        Node::Int(_) | Node::Unit(_) => PREC_ATOM,
+        Node::Convert(_) => PREC_CONVERT,
+    }
+}
+
+fn binary_precedence(op: BinaryOp) -> u8 {
+    match op {
+        BinaryOp::Add | BinaryOp::Sub => PREC_ADD,
+        BinaryOp::Mul | BinaryOp::Div => PREC_MUL,
+    }
+}
+
+fn flat<E: Tree>(expr: &E, out: &mut String) {
+    match expr.node() {
+        Node::Binary(expr) => {
+            let prec = binary_precedence(expr.op);
+            operand(&expr.left, prec, out);
+            out.push_str(&format!(" {} ", expr.op));
+            operand(&expr.right, prec + 1, out);
+        }
+        Node::Unary(expr) => {
+            out.push_str(&expr.op.to_string());
+            operand(&expr.right, PREC_UNARY, out);
+        }
+        Node::Int(value) => out.push_str(&value.to_string()),
+        Node::Unit(unit) => out.push_str(&unit.to_string()),
+        Node::Convert(expr) => {
+            operand(&expr.value, PREC_CONVERT, out);
+            out.push_str(" in ");
+            flat(&expr.target, out);
+        }
+    }
+}
+
+fn operand<E: Tree>(expr: &E, min_prec: u8, out: &mut String) {
+    if precedence(expr) < min_prec {
+        out.push('(');
+        flat(expr, out);
+        out.push(')');
+    } else {
+        flat(expr, out);
+    }
+}
+
+/// Same as `flat`, but breaks lines once the current one would exceed `width`.
+/// `col` is the column at which `expr` starts.
+fn emit<E: Tree>(expr: &E, col: usize, width: usize, out: &mut String) {
+    let mut line = String::new();
+    flat(expr, &mut line);
+    if col + line.len() <= width {
+        out.push_str(&line);
+        return;
+    }
+
+    match expr.node() {
+        Node::Binary(binary) => {
+            let prec = binary_precedence(binary.op);
+            let (first, rest) = chain(binary);
+            // leave room for the operator after every operand but the last
+            let before_op = width.saturating_sub(2);
+            emit_operand(first, prec, col, before_op, out);
+            for (i, &(op, right)) in rest.iter().enumerate() {
+                out.push_str(&format!(" {op}\n"));
+                out.push_str(&" ".repeat(col));
+                let width = if i + 1 == rest.len() {
+                    width
+                } else {
+                    before_op
+                };
+                emit_operand(right, prec + 1, col, width, out);
+            }
+        }
+        Node::Unary(expr) => {
+            out.push_str(&expr.op.to_string());
+            emit_operand(&expr.right, PREC_UNARY, col + 1, width, out);
+        }
+        Node::Int(_) | Node::Unit(_) | Node::Convert(_) => out.push_str(&line),
+    }
+}
+
+fn emit_operand<E: Tree>(expr: &E, min_prec: u8, col: usize, width: usize, out: &mut String) {
+    if precedence(expr) < min_prec {
+        out.push('(');
+        emit(expr, col + 1, width, out);
+        out.push(')');
+    } else {
+        emit(expr, col, width, out);
+    }
+}
+
+/// Flattens a left-associative chain of operators with the same precedence,
+/// e.g. `a + b - c` into `a` and `[(+, b), (-, c)]`.
+fn chain<E: Tree>(expr: &Binary<E>) -> (&E, Vec<(BinaryOp, &E)>) {
+    let prec = binary_precedence(expr.op);
+    let mut rest = vec![(expr.op, &expr.right)];
+    let mut first = &expr.left;
+    while let Node::Binary(left) = first.node() {
+        if binary_precedence(left.op) != prec {
+            break;
+        }
+        rest.push((left.op, &left.right));
+        first = &left.left;
+    }
+    rest.reverse();
+    (first, rest)
+}
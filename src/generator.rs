@@ -0,0 +1,224 @@
+use std::ops::RangeInclusive;
+use std::str::FromStr;
+
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::Rng;
+use rand::SeedableRng;
+
+use crate::expr::Binary;
+use crate::expr::BinaryOp;
+use crate::expr::Expr;
+use crate::expr::Unary;
+use crate::expr::UnaryOp;
+
+pub const DEFAULT_DEPTH: usize = 64;
+pub const DEFAULT_NODES: usize = 256;
+
+/// How nodes are split between the two sides of a binary operator.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Shape {
+    #[default]
+    Random,
+    /// Both sides get the same number of nodes.
+    Balanced,
+    /// Chains like `((1 + 2) + 3) + 4`.
+    LeftDeep,
+    /// Chains like `1 + (2 + (3 + 4))`.
+    RightDeep,
+}
+
+/// Shape of the expressions made by a `Generator`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct Config {
+    /// Maximum number of nodes on a path from the root to a literal.
+    pub depth: usize,
+    /// Number of nodes in each expression, unless `depth` doesn't leave room for them.
+    pub nodes: usize,
+    pub shape: Shape,
+    /// Binary operators to pick from.
+    pub ops: Vec<BinaryOp>,
+    /// Use unary `+` and `-` for about a quarter of the operators,
+    /// otherwise only where a binary operator doesn't fit.
+    pub unary: bool,
+    pub literals: RangeInclusive<i64>,
+    /// Avoid division by zero and `i64` overflow, so every backend can evaluate the result.
+    ///
+    /// Operators that would fail are swapped for another one from `ops`,
+    /// or if none of them works the right operand is replaced by `0` or `1`,
+    /// so the result can end up with fewer nodes.
+    pub safe: bool,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Config {
+            depth: DEFAULT_DEPTH,
+            nodes: DEFAULT_NODES,
+            shape: Shape::Random,
+            ops: vec![BinaryOp::Add, BinaryOp::Sub, BinaryOp::Mul, BinaryOp::Div],
+            unary: true,
+            literals: i8::MIN as i64..=i8::MAX as i64,
+            safe: false,
+        }
+    }
+}
+
+/// Makes random expressions, always the same sequence for the same seed and config.
+pub struct Generator {
+    rng: StdRng,
+    config: Config,
+}
+
+impl Generator {
+    pub fn new(seed: u64, config: Config) -> Self {
+        Generator {
+            rng: StdRng::seed_from_u64(seed),
+            config,
+        }
+    }
+
+    pub fn generate(&mut self) -> Expr {
+        enum Task {
+            Build { nodes: usize, depth: usize },
+            Binary(BinaryOp),
+            Unary(UnaryOp),
+        }
+
+        let depth = self.config.depth.max(1);
+        let nodes = self
+            .config
+            .nodes
+            .clamp(1, max_nodes(depth, &self.config.ops));
+        let mut tasks = vec![Task::Build { nodes, depth }];
+        // each finished subtree, along with its value in safe mode
+        let mut values: Vec<(Expr, Option<i64>)> = Vec::new();
+
+        while let Some(task) = tasks.pop() {
+            match task {
+                Task::Build { nodes: 1, .. } => {
+                    let value = self.rng.gen_range(self.config.literals.clone());
+                    values.push((Expr::Int(value), Some(value)));
+                }
+                Task::Build { nodes, depth } => {
+                    let rest = nodes - 1;
+                    let cap = max_nodes(depth - 1, &self.config.ops);
+                    // a binary node needs at least one node on each side
+                    let binary = !self.config.ops.is_empty()
+                        && rest >= 2
+                        && (rest > cap || !self.config.unary || self.rng.gen_ratio(3, 4));
+                    if binary {
+                        let op = *self.config.ops.choose(&mut self.rng).unwrap();
+                        let (min, max) = (rest.saturating_sub(cap).max(1), cap.min(rest - 1));
+                        let left = match self.config.shape {
+                            Shape::Random => self.rng.gen_range(min..=max),
+                            Shape::Balanced => rest / 2,
+                            Shape::LeftDeep => rest - 1,
+                            Shape::RightDeep => 1,
+                        }
+                        .clamp(min, max);
+                        tasks.push(Task::Binary(op));
+                        tasks.push(Task::Build {
+                            nodes: rest - left,
+                            depth: depth - 1,
+                        });
+                        tasks.push(Task::Build {
+                            nodes: left,
+                            depth: depth - 1,
+                        });
+                    } else {
+                        let op = *[UnaryOp::Plus, UnaryOp::Minus]
+                            .choose(&mut self.rng)
+                            .unwrap();
+                        tasks.push(Task::Unary(op));
+                        tasks.push(Task::Build {
+                            nodes: rest,
+                            depth: depth - 1,
+                        });
+                    }
+                }
+                Task::Binary(op) => {
+                    let (mut right, right_value) = values.pop().unwrap();
+                    let (left, left_value) = values.pop().unwrap();
+                    let (op, value) = match (left_value, right_value) {
+                        (Some(left), Some(right_value)) if self.config.safe => {
+                            match self.safe_op(op, left, right_value) {
+                                Some((op, value)) => (op, Some(value)),
+                                None => {
+                                    let identity = match op {
+                                        BinaryOp::Add | BinaryOp::Sub => 0,
+                                        BinaryOp::Mul | BinaryOp::Div => 1,
+                                    };
+                                    right = Expr::Int(identity);
+                                    (op, Some(left))
+                                }
+                            }
+                        }
+                        _ => (op, None),
+                    };
+                    values.push((Expr::Binary(Box::new(Binary { left, op, right })), value));
+                }
+                Task::Unary(op) => {
+                    let (right, right_value) = values.pop().unwrap();
+                    let (op, value) = match (op, right_value) {
+                        (UnaryOp::Minus, Some(value)) if self.config.safe => {
+                            match value.checked_neg() {
+                                Some(value) => (UnaryOp::Minus, Some(value)),
+                                None => (UnaryOp::Plus, Some(value)),
+                            }
+                        }
+                        (UnaryOp::Plus, value) => (op, value),
+                        _ => (op, None),
+                    };
+                    values.push((Expr::Unary(Box::new(Unary { op, right })), value));
+                }
+            }
+        }
+
+        values.pop().unwrap().0
+    }
+
+    /// Returns `op`, or else another operator from the config,
+    /// along with its value if it neither overflows nor divides by zero.
+    fn safe_op(&mut self, op: BinaryOp, left: i64, right: i64) -> Option<(BinaryOp, i64)> {
+        let mut ops = self.config.ops.clone();
+        ops.shuffle(&mut self.rng);
+        std::iter::once(op).chain(ops).find_map(|op| {
+            let value = match op {
+                BinaryOp::Add => left.checked_add(right),
+                BinaryOp::Sub => left.checked_sub(right),
+                BinaryOp::Mul => left.checked_mul(right),
+                BinaryOp::Div => left.checked_div(right),
+            };
+            value.map(|value| (op, value))
+        })
+    }
+}
+
+impl FromStr for Shape {
+    type Err = String;
+
+    fn from_str(shape: &str) -> Result<Self, Self::Err> {
+        match shape {
+            "random" => Ok(Shape::Random),
+            "balanced" => Ok(Shape::Balanced),
+            "left-deep" => Ok(Shape::LeftDeep),
+            "right-deep" => Ok(Shape::RightDeep),
+            _ => Err(format!(
+                "unknown shape `{shape}`, expected `random`, `balanced`, `left-deep` or `right-deep`"
+            )),
+        }
+    }
+}
+
+/// Most nodes a tree of the given depth can have.
+fn max_nodes(depth: usize, ops: &[BinaryOp]) -> usize {
+    if ops.is_empty() {
+        depth
+    } else {
+        u32::try_from(depth)
+            .ok()
+            .and_then(|depth| 1usize.checked_shl(depth))
+            .map_or(usize::MAX, |n| n - 1)
+    }
+}
@@ -0,0 +1,331 @@
+use std::str::FromStr;
+
+use crate::expr::BinaryOp;
+use crate::expr::UnaryOp;
+use crate::number_format::NumberFormat;
+use crate::trap::Trap;
+
+/// Integer type to evaluate with, instead of the default `i64`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum IntType {
+    I8,
+    I16,
+    I32,
+    I64,
+    I128,
+    U8,
+    U16,
+    U32,
+    U64,
+    U128,
+}
+
+impl IntType {
+    pub const ALL: [IntType; 10] = [
+        IntType::I8,
+        IntType::I16,
+        IntType::I32,
+        IntType::I64,
+        IntType::I128,
+        IntType::U8,
+        IntType::U16,
+        IntType::U32,
+        IntType::U64,
+        IntType::U128,
+    ];
+
+    pub fn is_signed(&self) -> bool {
+        matches!(
+            self,
+            IntType::I8 | IntType::I16 | IntType::I32 | IntType::I64 | IntType::I128
+        )
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            IntType::I8 => "i8",
+            IntType::I16 => "i16",
+            IntType::I32 => "i32",
+            IntType::I64 => "i64",
+            IntType::I128 => "i128",
+            IntType::U8 => "u8",
+            IntType::U16 => "u16",
+            IntType::U32 => "u32",
+            IntType::U64 => "u64",
+            IntType::U128 => "u128",
+        }
+    }
+}
+
+/// What happens when a result doesn't fit the type.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub enum Overflow {
+    Wrapping,
+    Saturating,
+    /// Raises a `Trap`.
+    #[default]
+    Checked,
+}
+
+impl Overflow {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Overflow::Wrapping => "wrapping",
+            Overflow::Saturating => "saturating",
+            Overflow::Checked => "checked",
+        }
+    }
+}
+
+/// Integer type and overflow behavior, parsed from a spec like `u16 wrapping`.
+/// Division by zero always traps.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct IntMode {
+    pub ty: IntType,
+    pub overflow: Overflow,
+}
+
+/// Value of one of the `IntType`s, stored sign-extended to 128 bits.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Value {
+    bits: u128,
+    ty: IntType,
+}
+
+impl Value {
+    pub fn ty(&self) -> IntType {
+        self.ty
+    }
+
+    /// The value if the type is signed.
+    pub fn to_i128(&self) -> Option<i128> {
+        self.ty.is_signed().then_some(self.bits as i128)
+    }
+
+    /// The value if the type is unsigned.
+    pub fn to_u128(&self) -> Option<u128> {
+        (!self.ty.is_signed()).then_some(self.bits)
+    }
+
+    pub fn format(&self, format: &NumberFormat) -> String {
+        if self.ty.is_signed() {
+            format.format_i128(self.bits as i128)
+        } else {
+            format.format_u128(self.bits)
+        }
+    }
+}
+
+impl std::fmt::Display for Value {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.ty.is_signed() {
+            write!(f, "{}", self.bits as i128)
+        } else {
+            write!(f, "{}", self.bits)
+        }
+    }
+}
+
+/// Operations shared by the primitive integer types.
+trait Prim: Copy + Sized {
+    const ZERO: Self;
+    const MIN: Self;
+    const MAX: Self;
+
+    fn from_bits(bits: u128) -> Self;
+    fn to_bits(self) -> u128;
+
+    fn wrapping_add(self, rhs: Self) -> Self;
+    fn wrapping_sub(self, rhs: Self) -> Self;
+    fn wrapping_mul(self, rhs: Self) -> Self;
+    fn wrapping_div(self, rhs: Self) -> Self;
+    fn saturating_add(self, rhs: Self) -> Self;
+    fn saturating_sub(self, rhs: Self) -> Self;
+    fn saturating_mul(self, rhs: Self) -> Self;
+    fn saturating_div(self, rhs: Self) -> Self;
+    fn checked_add(self, rhs: Self) -> Option<Self>;
+    fn checked_sub(self, rhs: Self) -> Option<Self>;
+    fn checked_mul(self, rhs: Self) -> Option<Self>;
+    fn checked_div(self, rhs: Self) -> Option<Self>;
+}
+
+macro_rules! impl_prim {
+    ($($ty:ty),*) => {$(
+        impl Prim for $ty {
+            const ZERO: Self = 0;
+            const MIN: Self = <$ty>::MIN;
+            const MAX: Self = <$ty>::MAX;
+
+            fn from_bits(bits: u128) -> Self {
+                bits as Self
+            }
+
+            fn to_bits(self) -> u128 {
+                // sign-extends signed types
+                self as i128 as u128
+            }
+
+            fn wrapping_add(self, rhs: Self) -> Self { <$ty>::wrapping_add(self, rhs) }
+            fn wrapping_sub(self, rhs: Self) -> Self { <$ty>::wrapping_sub(self, rhs) }
+            fn wrapping_mul(self, rhs: Self) -> Self { <$ty>::wrapping_mul(self, rhs) }
+            fn wrapping_div(self, rhs: Self) -> Self { <$ty>::wrapping_div(self, rhs) }
+            fn saturating_add(self, rhs: Self) -> Self { <$ty>::saturating_add(self, rhs) }
+            fn saturating_sub(self, rhs: Self) -> Self { <$ty>::saturating_sub(self, rhs) }
+            fn saturating_mul(self, rhs: Self) -> Self { <$ty>::saturating_mul(self, rhs) }
+            fn saturating_div(self, rhs: Self) -> Self { <$ty>::saturating_div(self, rhs) }
+            fn checked_add(self, rhs: Self) -> Option<Self> { <$ty>::checked_add(self, rhs) }
+            fn checked_sub(self, rhs: Self) -> Option<Self> { <$ty>::checked_sub(self, rhs) }
+            fn checked_mul(self, rhs: Self) -> Option<Self> { <$ty>::checked_mul(self, rhs) }
+            fn checked_div(self, rhs: Self) -> Option<Self> { <$ty>::checked_div(self, rhs) }
+        }
+    )*};
+}
+
+impl_prim!(i8, i16, i32, i64, i128, u8, u16, u32, u64, u128);
+
+/// Calls `$f::<T>` with the primitive type `T` matching `$ty`.
+macro_rules! dispatch {
+    ($ty:expr, $f:ident($($arg:expr),*)) => {
+        match $ty {
+            IntType::I8 => $f::<i8>($($arg),*),
+            IntType::I16 => $f::<i16>($($arg),*),
+            IntType::I32 => $f::<i32>($($arg),*),
+            IntType::I64 => $f::<i64>($($arg),*),
+            IntType::I128 => $f::<i128>($($arg),*),
+            IntType::U8 => $f::<u8>($($arg),*),
+            IntType::U16 => $f::<u16>($($arg),*),
+            IntType::U32 => $f::<u32>($($arg),*),
+            IntType::U64 => $f::<u64>($($arg),*),
+            IntType::U128 => $f::<u128>($($arg),*),
+        }
+    };
+}
+
+impl IntMode {
+    pub fn int(&self, value: i64) -> Result<Value, Trap> {
+        self.literal(value, false)
+    }
+
+    /// Converts a literal along with the minus directly in front of it, if `negated`,
+    /// so `-128` is in range for `i8` like in Rust.
+    pub fn literal(&self, value: i64, negated: bool) -> Result<Value, Trap> {
+        // only generated trees have negative literals
+        self.wide_literal(value.unsigned_abs().into(), negated != (value < 0))
+    }
+
+    /// Same as `literal`, for literals which may not fit in an `i64`,
+    /// like `u128::MAX` or the magnitude of `i128::MIN`.
+    pub fn wide_literal(&self, magnitude: u128, negated: bool) -> Result<Value, Trap> {
+        let bits = dispatch!(self.ty, literal(self.overflow, magnitude, negated))?;
+        Ok(Value { bits, ty: self.ty })
+    }
+
+    pub fn binary(&self, op: BinaryOp, left: Value, right: Value) -> Result<Value, Trap> {
+        let bits = dispatch!(self.ty, binary(self.overflow, op, left.bits, right.bits))?;
+        Ok(Value { bits, ty: self.ty })
+    }
+
+    pub fn unary(&self, op: UnaryOp, right: Value) -> Result<Value, Trap> {
+        match op {
+            UnaryOp::Plus => Ok(right),
+            UnaryOp::Minus => {
+                let zero = Value {
+                    bits: 0,
+                    ty: self.ty,
+                };
+                self.binary(BinaryOp::Sub, zero, right)
+                    .map_err(|trap| match trap {
+                        Trap::SubOverflow => Trap::NegOverflow,
+                        trap => trap,
+                    })
+            }
+        }
+    }
+}
+
+fn literal<T: Prim>(overflow: Overflow, magnitude: u128, negated: bool) -> Result<u128, Trap> {
+    // the two's complement bits of the literal, which are only the value of `T` if it fits
+    let bits = if negated {
+        magnitude.wrapping_neg()
+    } else {
+        magnitude
+    };
+    let fits = if negated {
+        magnitude <= (T::MIN.to_bits() as i128).unsigned_abs()
+    } else {
+        magnitude <= T::MAX.to_bits()
+    };
+    let value = match overflow {
+        _ if fits => T::from_bits(bits),
+        Overflow::Wrapping => T::from_bits(bits),
+        Overflow::Saturating if negated => T::MIN,
+        Overflow::Saturating => T::MAX,
+        Overflow::Checked => return Err(Trap::LiteralOverflow),
+    };
+    Ok(value.to_bits())
+}
+
+fn binary<T: Prim>(
+    overflow: Overflow,
+    op: BinaryOp,
+    left: u128,
+    right: u128,
+) -> Result<u128, Trap> {
+    let (left, right) = (T::from_bits(left), T::from_bits(right));
+    if let BinaryOp::Div = op {
+        if right.to_bits() == T::ZERO.to_bits() {
+            return Err(Trap::DivByZero);
+        }
+    }
+    let value = match overflow {
+        Overflow::Wrapping => match op {
+            BinaryOp::Add => left.wrapping_add(right),
+            BinaryOp::Sub => left.wrapping_sub(right),
+            BinaryOp::Mul => left.wrapping_mul(right),
+            BinaryOp::Div => left.wrapping_div(right),
+        },
+        Overflow::Saturating => match op {
+            BinaryOp::Add => left.saturating_add(right),
+            BinaryOp::Sub => left.saturating_sub(right),
+            BinaryOp::Mul => left.saturating_mul(right),
+            BinaryOp::Div => left.saturating_div(right),
+        },
+        Overflow::Checked => match op {
+            BinaryOp::Add => left.checked_add(right).ok_or(Trap::AddOverflow)?,
+            BinaryOp::Sub => left.checked_sub(right).ok_or(Trap::SubOverflow)?,
+            BinaryOp::Mul => left.checked_mul(right).ok_or(Trap::MulOverflow)?,
+            BinaryOp::Div => left.checked_div(right).ok_or(Trap::DivOverflow)?,
+        },
+    };
+    Ok(value.to_bits())
+}
+
+impl FromStr for IntMode {
+    type Err = String;
+
+    /// Parses a type like `u16`, optionally followed by `wrapping`, `saturating` or `checked`.
+    fn from_str(spec: &str) -> Result<Self, Self::Err> {
+        let mut words = spec.split_whitespace();
+        let ty = words.next().unwrap_or_default();
+        let ty = IntType::ALL
+            .into_iter()
+            .find(|t| t.name() == ty)
+            .ok_or_else(|| format!("unknown integer type `{ty}`"))?;
+        let overflow = match words.next() {
+            None | Some("checked") => Overflow::Checked,
+            Some("wrapping") => Overflow::Wrapping,
+            Some("saturating") => Overflow::Saturating,
+            Some(other) => return Err(format!("unknown overflow behavior `{other}`")),
+        };
+        if let Some(extra) = words.next() {
+            return Err(format!("unexpected `{extra}`"));
+        }
+        Ok(IntMode { ty, overflow })
+    }
+}
+
+impl std::fmt::Display for IntMode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} {}", self.ty.name(), self.overflow.name())
+    }
+}
@@ -0,0 +1,7 @@
+pub use super::register::compiler;
+pub use super::register::op;
+
+#[cfg(all(target_arch = "x86_64", unix))]
+mod x86_64;
+
+pub mod vm;
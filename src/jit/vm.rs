@@ -0,0 +1,52 @@
+use super::compiler::Bytecode;
+use super::compiler::ConstPool;
+use super::compiler::StackSize;
+
+/// Native code, or the bytecode if it couldn't be lowered.
+pub struct Program(Repr);
+
+enum Repr {
+    #[cfg(all(target_arch = "x86_64", unix))]
+    Native(super::x86_64::Code, StackSize),
+    Interpreted(Bytecode, ConstPool, StackSize),
+}
+
+impl Program {
+    /// Whether `eval` runs native code instead of the interpreter.
+    pub fn is_native(&self) -> bool {
+        match self.0 {
+            #[cfg(all(target_arch = "x86_64", unix))]
+            Repr::Native(..) => true,
+            Repr::Interpreted(..) => false,
+        }
+    }
+}
+
+/// Lowers register bytecode to native code.
+///
+/// Falls back to the `register` interpreter on hosts other than x86-64 unix,
+/// or if executable memory could not be mapped.
+pub fn jit(ops: Bytecode, pool: ConstPool, stack_size: StackSize) -> Program {
+    #[cfg(all(target_arch = "x86_64", unix))]
+    if let Some(code) = super::x86_64::lower(&ops, &pool) {
+        return Program(Repr::Native(code, stack_size));
+    }
+
+    Program(Repr::Interpreted(ops, pool, stack_size))
+}
+
+pub fn eval(program: &Program) -> i64 {
+    match &program.0 {
+        #[cfg(all(target_arch = "x86_64", unix))]
+        Repr::Native(code, stack_size) => {
+            let mut stack = vec![0i64; (*stack_size).max(code.slots())];
+            if let Err(trap) = code.call(&mut stack) {
+                panic!("{trap}");
+            }
+            stack[0]
+        }
+        Repr::Interpreted(ops, pool, stack_size) => {
+            crate::register::vm::eval(ops, pool, *stack_size)
+        }
+    }
+}
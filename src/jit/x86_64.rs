@@ -0,0 +1,239 @@
+use super::compiler::Bytecode;
+use super::compiler::ConstPool;
+use super::op::Op;
+use crate::trap::Trap;
+
+/// Executable buffer holding a function with the signature
+/// `extern "sysv64" fn(stack: *mut i64) -> u32`, which returns `0` or a `Trap`.
+pub struct Code {
+    ptr: *mut u8,
+    len: usize,
+    /// Register slots the code reads or writes, which `call` checks the stack has.
+    slots: usize,
+}
+
+impl Code {
+    fn new(bytes: &[u8], slots: usize) -> Option<Self> {
+        unsafe {
+            let ptr = libc::mmap(
+                std::ptr::null_mut(),
+                bytes.len(),
+                libc::PROT_READ | libc::PROT_WRITE,
+                libc::MAP_PRIVATE | libc::MAP_ANONYMOUS,
+                -1,
+                0,
+            );
+            if ptr == libc::MAP_FAILED {
+                return None;
+            }
+            std::ptr::copy_nonoverlapping(bytes.as_ptr(), ptr as *mut u8, bytes.len());
+            if libc::mprotect(ptr, bytes.len(), libc::PROT_READ | libc::PROT_EXEC) != 0 {
+                libc::munmap(ptr, bytes.len());
+                return None;
+            }
+            Some(Code {
+                ptr: ptr as *mut u8,
+                len: bytes.len(),
+                slots,
+            })
+        }
+    }
+
+    /// Number of register slots `call` needs.
+    pub fn slots(&self) -> usize {
+        self.slots
+    }
+
+    /// Runs the code on `stack`, which must have at least `slots` registers.
+    pub fn call(&self, stack: &mut [i64]) -> Result<(), Trap> {
+        assert!(
+            stack.len() >= self.slots,
+            "the code needs {} registers, but the stack has {}",
+            self.slots,
+            stack.len()
+        );
+        let f: extern "sysv64" fn(*mut i64) -> u32 = unsafe { std::mem::transmute(self.ptr) };
+        match Trap::from_code(f(stack.as_mut_ptr())) {
+            None => Ok(()),
+            Some(trap) => Err(trap),
+        }
+    }
+}
+
+impl Drop for Code {
+    fn drop(&mut self) {
+        unsafe {
+            libc::munmap(self.ptr as *mut libc::c_void, self.len);
+        }
+    }
+}
+
+/// Every op loads its left operand into `rax` and its right operand into `rcx`,
+/// then writes `rax` back to the register slot. Register slots live in
+/// the buffer passed in `rdi`.
+pub fn lower(ops: &Bytecode, pool: &ConstPool) -> Option<Code> {
+    let mut asm = Assembler::default();
+
+    for op in ops {
+        match op {
+            Op::LInt(n) => {
+                asm.mov_rax_imm(n.val as i64);
+                asm.store_rax(n.dst);
+            }
+            Op::LConst(n) => {
+                asm.mov_rax_imm(pool[n.idx as usize]);
+                asm.store_rax(n.dst);
+            }
+            Op::BAdd(n) => asm.binary(BinOp::Add, n.dst, n.lhs, Rhs::Reg(n.rhs)),
+            Op::BSub(n) => asm.binary(BinOp::Sub, n.dst, n.lhs, Rhs::Reg(n.rhs)),
+            Op::BMul(n) => asm.binary(BinOp::Mul, n.dst, n.lhs, Rhs::Reg(n.rhs)),
+            Op::BDiv(n) => asm.binary(BinOp::Div, n.dst, n.lhs, Rhs::Reg(n.rhs)),
+            Op::UMinus(n) => {
+                asm.load_rax(n.rhs);
+                asm.emit(&[0x48, 0xF7, 0xD8]); // neg rax
+                asm.check_overflow(Trap::NegOverflow);
+                asm.store_rax(n.dst);
+            }
+            Op::BAddI(n) => asm.binary(BinOp::Add, n.dst, n.lhs, Rhs::Imm(n.imm as i64)),
+            Op::BSubI(n) => asm.binary(BinOp::Sub, n.dst, n.lhs, Rhs::Imm(n.imm as i64)),
+            Op::BMulI(n) => asm.binary(BinOp::Mul, n.dst, n.lhs, Rhs::Imm(n.imm as i64)),
+            Op::BDivI(n) => asm.binary(BinOp::Div, n.dst, n.lhs, Rhs::Imm(n.imm as i64)),
+            Op::BAddK(n) => asm.binary(BinOp::Add, n.dst, n.lhs, Rhs::Imm(pool[n.kidx as usize])),
+            Op::BSubK(n) => asm.binary(BinOp::Sub, n.dst, n.lhs, Rhs::Imm(pool[n.kidx as usize])),
+            Op::BMulK(n) => asm.binary(BinOp::Mul, n.dst, n.lhs, Rhs::Imm(pool[n.kidx as usize])),
+            Op::BDivK(n) => asm.binary(BinOp::Div, n.dst, n.lhs, Rhs::Imm(pool[n.kidx as usize])),
+        }
+    }
+
+    let slots = asm.slots;
+    Code::new(&asm.finish(), slots)
+}
+
+#[derive(Clone, Copy)]
+enum BinOp {
+    Add,
+    Sub,
+    Mul,
+    Div,
+}
+
+enum Rhs {
+    Reg(u8),
+    Imm(i64),
+}
+
+#[derive(Default)]
+struct Assembler {
+    buf: Vec<u8>,
+    /// Offsets of `rel32` jump operands which should point at a trap handler.
+    fixups: Vec<(usize, Trap)>,
+    /// One past the highest register slot accessed.
+    slots: usize,
+}
+
+impl Assembler {
+    fn emit(&mut self, bytes: &[u8]) {
+        self.buf.extend_from_slice(bytes);
+    }
+
+    fn disp(&mut self, reg: u8) -> [u8; 4] {
+        self.slots = self.slots.max(reg as usize + 1);
+        (reg as i32 * 8).to_le_bytes()
+    }
+
+    fn load_rax(&mut self, reg: u8) {
+        self.emit(&[0x48, 0x8B, 0x87]); // mov rax, [rdi + disp32]
+        let disp = self.disp(reg);
+        self.emit(&disp);
+    }
+
+    fn load_rcx(&mut self, reg: u8) {
+        self.emit(&[0x48, 0x8B, 0x8F]); // mov rcx, [rdi + disp32]
+        let disp = self.disp(reg);
+        self.emit(&disp);
+    }
+
+    fn store_rax(&mut self, reg: u8) {
+        self.emit(&[0x48, 0x89, 0x87]); // mov [rdi + disp32], rax
+        let disp = self.disp(reg);
+        self.emit(&disp);
+    }
+
+    fn mov_rax_imm(&mut self, value: i64) {
+        self.emit(&[0x48, 0xB8]); // mov rax, imm64
+        self.emit(&value.to_le_bytes());
+    }
+
+    fn mov_rcx_imm(&mut self, value: i64) {
+        self.emit(&[0x48, 0xB9]); // mov rcx, imm64
+        self.emit(&value.to_le_bytes());
+    }
+
+    fn jcc(&mut self, cc: u8, trap: Trap) {
+        self.emit(&[0x0F, cc]);
+        self.fixups.push((self.buf.len(), trap));
+        self.emit(&[0; 4]);
+    }
+
+    fn check_overflow(&mut self, trap: Trap) {
+        if cfg!(debug_assertions) {
+            self.jcc(0x80, trap); // jo
+        }
+    }
+
+    fn binary(&mut self, op: BinOp, dst: u8, lhs: u8, rhs: Rhs) {
+        self.load_rax(lhs);
+        match rhs {
+            Rhs::Reg(rhs) => self.load_rcx(rhs),
+            Rhs::Imm(value) => self.mov_rcx_imm(value),
+        }
+        match op {
+            BinOp::Add => {
+                self.emit(&[0x48, 0x01, 0xC8]); // add rax, rcx
+                self.check_overflow(Trap::AddOverflow);
+            }
+            BinOp::Sub => {
+                self.emit(&[0x48, 0x29, 0xC8]); // sub rax, rcx
+                self.check_overflow(Trap::SubOverflow);
+            }
+            BinOp::Mul => {
+                self.emit(&[0x48, 0x0F, 0xAF, 0xC1]); // imul rax, rcx
+                self.check_overflow(Trap::MulOverflow);
+            }
+            BinOp::Div => {
+                self.emit(&[0x48, 0x85, 0xC9]); // test rcx, rcx
+                self.jcc(0x84, Trap::DivByZero); // jz
+                self.emit(&[0x48, 0x83, 0xF9, 0xFF]); // cmp rcx, -1
+                self.emit(&[0x75, 19]); // jne over the i64::MIN check
+                self.emit(&[0x48, 0xBA]); // mov rdx, imm64
+                self.emit(&i64::MIN.to_le_bytes());
+                self.emit(&[0x48, 0x39, 0xD0]); // cmp rax, rdx
+                self.jcc(0x84, Trap::DivOverflow); // je
+                self.emit(&[0x48, 0x99]); // cqo
+                self.emit(&[0x48, 0xF7, 0xF9]); // idiv rcx
+            }
+        }
+        self.store_rax(dst);
+    }
+
+    fn finish(mut self) -> Vec<u8> {
+        self.emit(&[0x31, 0xC0]); // xor eax, eax
+        self.emit(&[0xC3]); // ret
+
+        let mut handlers = [0usize; Trap::ALL.len()];
+        for (i, trap) in Trap::ALL.iter().enumerate() {
+            handlers[i] = self.buf.len();
+            self.emit(&[0xB8]); // mov eax, imm32
+            self.emit(&(*trap as u32).to_le_bytes());
+            self.emit(&[0xC3]); // ret
+        }
+
+        for (at, trap) in std::mem::take(&mut self.fixups) {
+            let target = handlers[trap as usize - 1];
+            let rel = (target as i64 - (at as i64 + 4)) as i32;
+            self.buf[at..at + 4].copy_from_slice(&rel.to_le_bytes());
+        }
+
+        self.buf
+    }
+}
@@ -1,23 +1,31 @@
 use crate::error::Error;
 use crate::error::Result;
+use crate::source::FileId;
+use crate::source::SourceFile;
+use crate::span::Span;
 use crate::token::Token;
 use crate::token::TokenKind;
-use std::sync::Arc;
 
+/// Produces tokens on demand, borrowing the text from its `SourceFile`.
 pub struct Lexer<'src> {
-    src: Arc<str>,
+    src: &'src str,
+    file: FileId,
+    /// Whether line breaks are produced as `Newline` tokens or skipped like other whitespace.
+    newlines: bool,
     inner: logos::Lexer<'src, TokenKind>,
     previous: Token,
     current: Token,
 }
 
 impl<'src> Lexer<'src> {
-    pub fn new(src: &'src str) -> Result<Self> {
+    pub fn new(file: &'src SourceFile, newlines: bool) -> Result<Self> {
         let mut lex = Self {
-            src: src.into(),
-            inner: logos::Logos::lexer(src),
-            previous: Token::eof(0),
-            current: Token::eof(0),
+            src: file.text(),
+            file: file.id(),
+            newlines,
+            inner: logos::Logos::lexer(file.text()),
+            previous: Token::eof(file.id(), 0),
+            current: Token::eof(file.id(), 0),
         };
         lex.bump()?;
         Ok(lex)
@@ -25,14 +33,16 @@ impl<'src> Lexer<'src> {
 
     pub fn bump(&mut self) -> Result<()> {
         std::mem::swap(&mut self.previous, &mut self.current);
-        let token = self.inner.next();
-        let span = self.inner.span().into();
+        let mut token = self.inner.next();
+        while !self.newlines && token == Some(Ok(TokenKind::Newline)) {
+            token = self.inner.next();
+        }
+        let span = Span::new(self.file, self.inner.span());
         self.current = match token {
             Some(Ok(kind)) => Token::new(kind, span),
-            None => Token::eof(self.previous.span.end),
+            None => Token::eof(self.file, self.previous.span.end),
             Some(Err(())) => {
                 return Err(Error::new(
-                    self.src.clone(),
                     span,
                     format!("unexpected token `{}`", &self.src[span]),
                 ))
@@ -49,7 +59,7 @@ impl<'src> Lexer<'src> {
         &self.current
     }
 
-    pub fn src(&self) -> &Arc<str> {
-        &self.src
+    pub fn src(&self) -> &'src str {
+        self.src
     }
 }
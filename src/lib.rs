@@ -1,16 +1,35 @@
+pub mod arena;
+pub mod benchmark;
+pub mod closure;
+pub mod codegen;
+#[cfg(feature = "cranelift")]
+pub mod cranelift;
+pub mod debugger;
+pub mod differential;
 pub mod error;
 pub mod expr;
 pub mod folder;
+pub mod formatter;
+pub mod generator;
+pub mod int_mode;
 pub mod lexer;
+pub mod number_format;
 pub mod parser;
+pub mod rational;
+pub mod source;
 pub mod span;
+pub mod stats;
 pub mod token;
+pub mod trap;
+pub mod units;
 
 pub mod alloc_exact_stack;
+pub mod jit;
 pub mod register;
 pub mod rpn;
 pub mod stack;
 pub mod stack_pointer;
+pub mod threaded_register;
 pub mod unsafe_register;
 pub mod unsafe_stack;
 
@@ -1,54 +1,702 @@
+use std::io::Write;
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::time::Duration;
+
+use calc::debugger::Debugger;
+use calc::expr::BinaryOp;
+use calc::generator::Shape;
+use calc::int_mode::IntMode;
+use calc::number_format::NumberFormat;
+use calc::parser::Precedence;
+use calc::rational::Rational;
+use calc::source::FileId;
+use calc::source::SourceDb;
+use calc::span::Span;
+use calc::stats::Stats;
+use calc::token::TokenKind;
 use clap::Parser;
 use clap::Subcommand;
-use rustyline::{error::ReadlineError, DefaultEditor};
+use clap::ValueEnum;
+use logos::Logos;
+use rustyline::completion::Completer;
+use rustyline::highlight::Highlighter;
+use rustyline::hint::Hinter;
+use rustyline::history::DefaultHistory;
+use rustyline::validate::ValidationContext;
+use rustyline::validate::ValidationResult;
+use rustyline::validate::Validator;
+use rustyline::{error::ReadlineError, Editor, Helper};
 
 #[derive(Parser)]
 #[command(author, version, about, long_about = None)]
 struct Cli {
     #[clap(subcommand)]
     cmd: Option<Cmd>,
+    /// Reject expressions nested deeper than this
+    #[arg(long, global = true, default_value_t = calc::parser::DEFAULT_MAX_DEPTH)]
+    max_depth: usize,
+    /// How to print results, e.g. `hex`, `dec group` or `bin u8`
+    #[arg(long, global = true, default_value = "dec")]
+    format: NumberFormat,
+    /// How to evaluate `run` and REPL input: `int`, `rational`, `units`,
+    /// or a fixed-width type like `u16 wrapping` or `i32 checked`
+    #[arg(long, global = true, default_value = "int")]
+    mode: Mode,
+}
+
+/// Settings for evaluating programs.
+#[derive(Clone, Copy)]
+struct Options {
+    max_depth: usize,
+    format: NumberFormat,
+    mode: Mode,
+    /// Show VM instruction counts after integer results
+    stats: bool,
+}
+
+#[derive(Clone, Copy)]
+enum Mode {
+    /// 64-bit integers, with truncating division
+    Int,
+    /// Exact fractions, e.g. `1/3 + 1/6` is `1/2`.
+    /// Unlike the other modes, `*` and `/` bind tighter than `+` and `-`
+    Rational,
+    /// Floating point quantities with units of measure, e.g. `3 km + 200 m in mi`
+    Units,
+    /// Integers of the given width, wrapping, saturating or trapping on overflow
+    Fixed(IntMode),
+}
+
+impl FromStr for Mode {
+    type Err = String;
+
+    fn from_str(spec: &str) -> Result<Self, Self::Err> {
+        match spec.trim() {
+            "int" => Ok(Mode::Int),
+            "rational" => Ok(Mode::Rational),
+            "units" => Ok(Mode::Units),
+            spec => spec.parse().map(Mode::Fixed).map_err(|e| {
+                format!("{e}, expected `int`, `rational`, `units` or a type like `u16 wrapping`")
+            }),
+        }
+    }
+}
+
+impl std::fmt::Display for Mode {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Mode::Int => f.write_str("int"),
+            Mode::Rational => f.write_str("rational"),
+            Mode::Units => f.write_str("units"),
+            Mode::Fixed(mode) => write!(f, "{mode}"),
+        }
+    }
 }
 
 #[derive(Subcommand)]
 enum Cmd {
     Repl,
-    Gen,
+    /// Print random expressions, one per line
+    Gen {
+        /// Seed for reproducible output, random if omitted
+        #[arg(long)]
+        seed: Option<u64>,
+        /// Maximum nesting depth
+        #[arg(long, default_value_t = calc::generator::DEFAULT_DEPTH)]
+        depth: usize,
+        /// Number of nodes in each expression
+        #[arg(long, default_value_t = calc::generator::DEFAULT_NODES)]
+        nodes: usize,
+        /// How to split nodes between operands: `random`, `balanced`, `left-deep` or `right-deep`
+        #[arg(long, default_value = "random")]
+        shape: Shape,
+        /// Binary operators to use
+        #[arg(
+            long,
+            value_delimiter = ',',
+            allow_hyphen_values = true,
+            default_value = "+,-,*,/"
+        )]
+        ops: Vec<BinaryOp>,
+        /// Only use unary operators where a binary one doesn't fit
+        #[arg(long)]
+        no_unary: bool,
+        /// Number of expressions
+        #[arg(long, default_value_t = 1)]
+        count: usize,
+        /// Avoid division by zero and overflow
+        #[arg(long)]
+        safe: bool,
+    },
+    Compile {
+        #[arg(long, value_enum)]
+        target: Target,
+        /// Write the output to this file instead of stdout
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+        /// Expression to compile, read from stdin if omitted
+        #[arg(allow_hyphen_values = true)]
+        expr: Option<String>,
+    },
+    /// Time parsing, compiling and evaluating generated workloads with every backend
+    Bench {
+        /// Seed for the workloads
+        #[arg(long, default_value_t = 0)]
+        seed: u64,
+        /// Number of nodes in each workload
+        #[arg(long, default_value_t = 1023)]
+        nodes: usize,
+        /// Only run these workloads
+        #[arg(long, value_delimiter = ',')]
+        workloads: Vec<String>,
+        /// How long to repeat each measurement for, in milliseconds
+        #[arg(long, default_value_t = 100)]
+        time: u64,
+        #[arg(long, value_enum, default_value = "markdown")]
+        report: Report,
+        /// Write the report to this file instead of stdout
+        #[arg(short, long)]
+        output: Option<PathBuf>,
+    },
+    /// Evaluate an expression with one of the VMs
+    Eval {
+        #[arg(long, value_enum, default_value = "stack")]
+        backend: Backend,
+        /// Also print executed instructions per opcode, max stack depth and constant pool loads
+        #[arg(long)]
+        stats: bool,
+        /// Expression to evaluate, read from stdin if omitted
+        #[arg(allow_hyphen_values = true)]
+        expr: Option<String>,
+    },
+    /// Step through the bytecode of an expression one instruction at a time
+    Debug {
+        #[arg(long, value_enum, default_value = "stack")]
+        backend: Backend,
+        #[arg(allow_hyphen_values = true)]
+        expr: String,
+    },
+    /// Run a file, printing the value of every statement
+    Run {
+        file: PathBuf,
+    },
+    Fmt {
+        /// Maximum line width
+        #[arg(long, default_value_t = 80)]
+        width: usize,
+        /// Expression to format, read from stdin if omitted
+        #[arg(allow_hyphen_values = true)]
+        expr: Option<String>,
+    },
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum Backend {
+    Stack,
+    Register,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum Report {
+    Markdown,
+    Csv,
+}
+
+#[derive(Clone, Copy, ValueEnum)]
+enum Target {
+    Wasm,
+    Wat,
+    C,
+    Rust,
+    #[value(name = "asm-x86_64")]
+    AsmX86_64,
+    #[value(name = "asm-aarch64")]
+    AsmAarch64,
 }
 
 fn main() {
-    match Cli::parse().cmd {
-        Some(Cmd::Gen) => gen(),
-        Some(Cmd::Repl) => repl(),
-        _ => repl(),
+    let cli = Cli::parse();
+    let max_depth = cli.max_depth;
+    let options = Options {
+        max_depth,
+        format: cli.format,
+        mode: cli.mode,
+        stats: false,
+    };
+    match cli.cmd {
+        Some(Cmd::Gen {
+            seed,
+            depth,
+            nodes,
+            shape,
+            ops,
+            no_unary,
+            count,
+            safe,
+        }) => {
+            let config = calc::generator::Config {
+                depth,
+                nodes,
+                shape,
+                ops,
+                unary: !no_unary,
+                safe,
+                ..Default::default()
+            };
+            gen(seed.unwrap_or_else(rand::random), config, count)
+        }
+        Some(Cmd::Repl) => repl(options),
+        Some(Cmd::Compile {
+            target,
+            output,
+            expr,
+        }) => compile(target, output, expr, max_depth),
+        Some(Cmd::Fmt { width, expr }) => fmt(width, expr, max_depth),
+        Some(Cmd::Run { file }) => run(file, options),
+        Some(Cmd::Eval {
+            backend,
+            stats,
+            expr,
+        }) => eval_expr(backend, stats, expr, options),
+        Some(Cmd::Debug { backend, expr }) => debug(backend, expr, options),
+        Some(Cmd::Bench {
+            seed,
+            nodes,
+            workloads,
+            time,
+            report,
+            output,
+        }) => bench(seed, nodes, workloads, time, report, output),
+        _ => repl(options),
+    }
+}
+
+fn gen(seed: u64, config: calc::generator::Config, count: usize) {
+    let mut generator = calc::generator::Generator::new(seed, config);
+    for _ in 0..count {
+        let expr = generator.generate();
+        println!("{}", calc::formatter::format(&expr, usize::MAX));
     }
 }
 
-fn gen() {
-    let expr = calc::expr::Expr::generate();
-    println!("{expr}");
+fn read_source(src: Option<String>) -> (SourceDb, FileId) {
+    let mut db = SourceDb::default();
+    let file = match src {
+        Some(src) => db.add("<expr>", src),
+        None => db.add(
+            "<stdin>",
+            std::io::read_to_string(std::io::stdin()).unwrap(),
+        ),
+    };
+    (db, file)
 }
 
-fn repl() {
-    fn run_and_print(src: &str) {
-        if src.is_empty() {
-            return;
+fn read_expr(src: Option<String>, max_depth: usize) -> calc::expr::Expr {
+    let (db, file) = read_source(src);
+    match calc::parser::parse_with_max_depth(db.get(file), max_depth) {
+        Ok(expr) => expr,
+        Err(e) => exit_with(&e, &db),
+    }
+}
+
+fn exit_with(error: &calc::error::Error, db: &SourceDb) -> ! {
+    eprintln!("{}", error.report(db));
+    std::process::exit(1);
+}
+
+fn fmt(width: usize, src: Option<String>, max_depth: usize) {
+    let expr = read_expr(src, max_depth);
+    println!("{}", calc::formatter::format(&expr, width));
+}
+
+fn compile(target: Target, output: Option<PathBuf>, src: Option<String>, max_depth: usize) {
+    let expr = read_expr(src, max_depth);
+
+    let code = match target {
+        Target::Wasm => calc::codegen::wasm::binary(&expr),
+        Target::Wat => calc::codegen::wasm::text(&expr).into_bytes(),
+        Target::C => calc::codegen::c::source(&expr).into_bytes(),
+        Target::Rust => calc::codegen::rust::source(&expr).into_bytes(),
+        Target::AsmX86_64 | Target::AsmAarch64 => {
+            let source = match target {
+                Target::AsmX86_64 => calc::codegen::asm::x86_64::source(&expr),
+                _ => calc::codegen::asm::aarch64::source(&expr),
+            };
+            match source {
+                Ok(source) => source.into_bytes(),
+                Err(e) => {
+                    eprintln!("{}", e.message());
+                    std::process::exit(1);
+                }
+            }
         }
-        let expr = match calc::parser::parse(src) {
-            Ok(expr) => expr,
+    };
+
+    let result = match output {
+        Some(path) => std::fs::write(path, code),
+        None => std::io::stdout().write_all(&code),
+    };
+    if let Err(e) = result {
+        eprintln!("{e}");
+        std::process::exit(1);
+    }
+}
+
+fn bench(
+    seed: u64,
+    nodes: usize,
+    names: Vec<String>,
+    time: u64,
+    report: Report,
+    output: Option<PathBuf>,
+) {
+    let mut workloads = calc::benchmark::workloads(nodes);
+    if !names.is_empty() {
+        if let Some(name) = names
+            .iter()
+            .find(|name| !workloads.iter().any(|w| w.name == name.as_str()))
+        {
+            let known: Vec<_> = workloads.iter().map(|w| w.name).collect();
+            eprintln!(
+                "unknown workload `{name}`, expected one of {}",
+                known.join(", ")
+            );
+            std::process::exit(1);
+        }
+        workloads.retain(|w| names.iter().any(|name| name == w.name));
+    }
+
+    let results = calc::benchmark::run(&workloads, seed, Duration::from_millis(time));
+    let text = match report {
+        Report::Markdown => results.markdown(),
+        Report::Csv => results.csv(),
+    };
+    let result = match output {
+        Some(path) => std::fs::write(path, text),
+        None => std::io::stdout().write_all(text.as_bytes()),
+    };
+    if let Err(e) = result {
+        eprintln!("{e}");
+        std::process::exit(1);
+    }
+}
+
+fn eval_expr(backend: Backend, stats: bool, src: Option<String>, options: Options) {
+    let (db, file) = read_source(src);
+    let (expr, spans) = match calc::parser::parse_spanned(db.get(file), options.max_depth) {
+        Ok(parsed) => parsed,
+        Err(e) => exit_with(&e, &db),
+    };
+    let (value, observed) = match eval_observed(&expr, &spans, backend) {
+        Ok(observed) => observed,
+        Err(e) => exit_with(&e, &db),
+    };
+    println!("{}", options.format.format(value));
+    if stats {
+        println!("{observed}");
+    }
+}
+
+/// `spans` are used to point compile errors at the source, see `parser::parse_spanned`.
+fn eval_observed(
+    expr: &calc::expr::Expr,
+    spans: &[Span],
+    backend: Backend,
+) -> calc::error::Result<(i64, Stats)> {
+    let mut stats = Stats::default();
+    let value = match backend {
+        Backend::Stack => {
+            let (ops, pool) = calc::stack::compiler::compile(expr);
+            calc::stack::vm::eval_observed(&ops, &pool, &mut stats)
+        }
+        Backend::Register => {
+            let (ops, pool, stack_size) = calc::register::compiler::compile_spanned(expr, spans)?;
+            calc::register::vm::eval_observed(&ops, &pool, stack_size, &mut stats)
+        }
+    };
+    Ok((value, stats))
+}
+
+const DEBUG_HELP: &str = "\
+step [n]    run the next n instructions, 1 by default
+continue    run until the next breakpoint or the end
+break <i>   set or clear a breakpoint at instruction i
+list        show every instruction
+print       show the operand stack or registers
+quit
+An empty line repeats the previous command.";
+
+fn debug(backend: Backend, src: String, options: Options) {
+    let mut db = SourceDb::default();
+    let file = db.add("<expr>", src);
+    let (expr, spans) = match calc::parser::parse_spanned(db.get(file), options.max_depth) {
+        Ok(parsed) => parsed,
+        Err(e) => exit_with(&e, &db),
+    };
+    let mut debugger = match backend {
+        Backend::Stack => Debugger::stack(&expr, &spans),
+        Backend::Register => match Debugger::register(&expr, &spans) {
+            Ok(debugger) => debugger,
+            Err(e) => exit_with(&e, &db),
+        },
+    };
+
+    // the state, then what runs next or the result
+    let show = |debugger: &Debugger| {
+        println!("{}", debugger.state());
+        match debugger.result() {
+            Some(value) => println!("result: {}", options.format.format(value)),
+            None => {
+                let pc = debugger.pc();
+                let span = debugger.span(pc);
+                print!("{pc}: {}\n{}", debugger.disassemble(pc), db.snippet(span));
+            }
+        }
+    };
+    let trapped = |debugger: &Debugger, trap| {
+        eprint!("{trap}:\n{}", db.snippet(debugger.span(debugger.pc())));
+    };
+
+    print!("{}", debugger.listing());
+    show(&debugger);
+    let mut ed = Editor::<(), DefaultHistory>::new().unwrap();
+    let mut last = String::new();
+    loop {
+        let line = match ed.readline("(debug) ") {
+            Ok(line) if line.trim().is_empty() => last.clone(),
+            Ok(line) => {
+                let _ = ed.add_history_entry(&line);
+                line
+            }
+            Err(ReadlineError::Interrupted | ReadlineError::Eof) => break,
             Err(e) => {
-                eprintln!("\n{}", e.report());
-                return;
+                eprintln!("{e}");
+                std::process::exit(1);
             }
         };
-        let ops = calc::rpn::compiler::compile(&expr);
-        let value = calc::rpn::vm::eval(&ops);
-        println!("{value}");
+        let mut words = line.split_whitespace();
+        let arg = |word: Option<&str>| word.map(str::parse::<usize>).transpose();
+        match (words.next(), arg(words.next())) {
+            (None, _) => continue,
+            (Some(_), Err(e)) => eprintln!("{e}"),
+            (Some("s" | "step"), Ok(count)) => {
+                if debugger.finished() {
+                    eprintln!("the program has finished");
+                    continue;
+                }
+                // stepping past the end does nothing
+                match (0..count.unwrap_or(1)).try_for_each(|_| debugger.step()) {
+                    Ok(()) => show(&debugger),
+                    Err(trap) => trapped(&debugger, trap),
+                }
+            }
+            (Some("c" | "continue"), Ok(None)) => {
+                if debugger.finished() {
+                    eprintln!("the program has finished");
+                    continue;
+                }
+                match debugger.run() {
+                    Ok(()) => show(&debugger),
+                    Err(trap) => trapped(&debugger, trap),
+                }
+            }
+            (Some("b" | "break"), Ok(Some(index))) => match debugger.toggle_breakpoint(index) {
+                Ok(true) => println!("breakpoint at {index}"),
+                Ok(false) => println!("cleared breakpoint at {index}"),
+                Err(e) => eprintln!("{e}"),
+            },
+            (Some("l" | "list"), Ok(None)) => print!("{}", debugger.listing()),
+            (Some("p" | "print"), Ok(None)) => println!("{}", debugger.state()),
+            (Some("q" | "quit"), Ok(None)) => break,
+            _ => eprintln!("{DEBUG_HELP}"),
+        }
+        last = line;
     }
+}
+
+/// Stats of both VMs side by side, for comparing them in the REPL.
+fn compare_stats(expr: &calc::expr::Expr) -> String {
+    let mut out = String::new();
+    for (name, backend) in [("stack", Backend::Stack), ("register", Backend::Register)] {
+        out.push_str(&format!("\n{name}:"));
+        match eval_observed(expr, &[], backend) {
+            Ok((_, stats)) => {
+                for line in stats.to_string().lines() {
+                    out.push_str(&format!("\n  {line}"));
+                }
+            }
+            Err(e) => out.push_str(&format!("\n  {}", e.message())),
+        }
+    }
+    out
+}
+
+fn eval(expr: &calc::expr::Expr) -> i64 {
+    let ops = calc::rpn::compiler::compile(expr);
+    calc::rpn::vm::eval(&ops)
+}
+
+fn run(path: PathBuf, options: Options) {
+    let text = match std::fs::read_to_string(&path) {
+        Ok(text) => text,
+        Err(e) => {
+            eprintln!("{}: {e}", path.display());
+            std::process::exit(1);
+        }
+    };
+    let mut db = SourceDb::default();
+    let file = db.add(path.display().to_string(), text);
+    if let Err(e) = eval_program(&db, file, &options, &mut |value| println!("{value}")) {
+        eprintln!("{e}");
+        std::process::exit(1);
+    }
+}
+
+/// Keeps reading lines while parentheses are left open.
+struct InputValidator;
+
+impl Validator for InputValidator {
+    fn validate(&self, ctx: &mut ValidationContext) -> rustyline::Result<ValidationResult> {
+        let mut depth = 0i32;
+        for token in TokenKind::lexer(ctx.input()).flatten() {
+            match token {
+                TokenKind::ParenL => depth += 1,
+                TokenKind::ParenR => depth -= 1,
+                _ => {}
+            }
+        }
+        if depth > 0 {
+            Ok(ValidationResult::Incomplete)
+        } else {
+            Ok(ValidationResult::Valid(None))
+        }
+    }
+}
+
+impl Completer for InputValidator {
+    type Candidate = String;
+}
+
+impl Hinter for InputValidator {
+    type Hint = String;
+}
+
+impl Highlighter for InputValidator {}
+
+impl Helper for InputValidator {}
+
+/// Evaluates every statement in order, passing each rendered result to `emit` as soon as
+/// it's known. Stops at the first error, which is rendered too.
+fn eval_program(
+    db: &SourceDb,
+    file: FileId,
+    options: &Options,
+    emit: &mut dyn FnMut(String),
+) -> Result<(), String> {
+    let file = db.get(file);
+    if let Mode::Units = options.mode {
+        let stmts =
+            calc::parser::parse_units_program(file, options.max_depth).map_err(|e| e.report(db))?;
+        for (stmt, spans) in &stmts {
+            let value = calc::folder::fold_units(stmt, spans).map_err(|e| e.report(db))?;
+            emit(value.format(&options.format));
+        }
+        return Ok(());
+    }
+    if let Mode::Fixed(mode) = options.mode {
+        let values = calc::folder::parse_and_fold_with(file, options.max_depth, mode)
+            .map_err(|e| e.report(db))?;
+        for value in values {
+            let value = value.map_err(|e| e.report(db))?;
+            emit(value.format(&options.format));
+        }
+        return Ok(());
+    }
+
+    let precedence = match options.mode {
+        Mode::Rational => Precedence::MulFirst,
+        _ => Precedence::AddFirst,
+    };
+    let program = calc::parser::parse_program_with_precedence(file, options.max_depth, precedence)
+        .map_err(|e| e.report(db))?;
+    for stmt in &program.stmts {
+        let value = match options.mode {
+            Mode::Rational => {
+                let (ops, pool) = calc::stack::compiler::compile(stmt);
+                let value =
+                    calc::stack::vm::eval_rational(&ops, &pool).map_err(|e| e.to_string())?;
+                format_rational(value, &options.format)
+            }
+            _ if options.stats => {
+                let value = options.format.format(eval(stmt));
+                value + &compare_stats(stmt)
+            }
+            _ => options.format.format(eval(stmt)),
+        };
+        emit(value);
+    }
+    Ok(())
+}
+
+/// Shows fractions along with their decimal expansion, e.g. `1/3 ≈ 0.333333333333…`.
+fn format_rational(value: Rational, format: &NumberFormat) -> String {
+    if value.is_integer() {
+        return format.format(value.numer());
+    }
+    let decimal = value.to_decimal(12);
+    let approx = if decimal.ends_with('…') { "≈" } else { "=" };
+    format!("{value} {approx} {decimal}")
+}
+
+fn repl(mut options: Options) {
+    let mut run_and_print = |src: String| {
+        if let Some(spec) = src.trim().strip_prefix(":format") {
+            match spec.trim() {
+                "" => println!("{}", options.format),
+                spec => match spec.parse() {
+                    Ok(format) => options.format = format,
+                    Err(e) => eprintln!("{e}"),
+                },
+            }
+            return;
+        }
+        if src.trim() == ":stats" {
+            options.stats = !options.stats;
+            let state = if options.stats { "on" } else { "off" };
+            println!("stats {state}");
+            return;
+        }
+        if let Some(spec) = src.trim().strip_prefix(":mode") {
+            match spec.trim() {
+                "" => println!("{}", options.mode),
+                spec => match spec.parse() {
+                    Ok(mode) => options.mode = mode,
+                    Err(e) => eprintln!("{e}"),
+                },
+            }
+            return;
+        }
+
+        let mut db = SourceDb::default();
+        let file = db.add("<repl>", src);
+        // the value of the program is the value of its last statement
+        let mut last = None;
+        match eval_program(&db, file, &options, &mut |value| last = Some(value)) {
+            Ok(()) => {
+                if let Some(value) = last {
+                    println!("{value}");
+                }
+            }
+            Err(e) => eprintln!("\n{e}"),
+        }
+    };
 
-    let mut ed = DefaultEditor::new().unwrap();
+    let mut ed = Editor::<InputValidator, DefaultHistory>::new().unwrap();
+    ed.set_helper(Some(InputValidator));
     loop {
         match ed.readline("> ") {
-            Ok(line) => run_and_print(&line),
+            Ok(line) => run_and_print(line),
             Err(ReadlineError::Interrupted | ReadlineError::Eof) => break,
             Err(e) => {
                 eprintln!("{e}");
@@ -0,0 +1,167 @@
+use std::str::FromStr;
+
+/// How a value is rendered, e.g. as `0xff` or `1.5e3`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Notation {
+    #[default]
+    Dec,
+    Hex,
+    Oct,
+    Bin,
+    /// Decimal mantissa and exponent, e.g. `1.234e6`.
+    Sci,
+}
+
+/// Reinterprets values as a two's complement integer of the given width before rendering,
+/// so `-1` shows as `255` at `u8`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct Width {
+    /// Between 1 and 64.
+    pub bits: u32,
+    pub signed: bool,
+}
+
+/// Options for rendering results, parsed from a spec like `hex group u16`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct NumberFormat {
+    pub notation: Notation,
+    /// Separate groups of digits with `_`, e.g. `1_000_000` or `0xffff_ffff`.
+    pub group: bool,
+    pub width: Option<Width>,
+}
+
+impl NumberFormat {
+    pub fn format(&self, value: i64) -> String {
+        self.format_i128(value as i128)
+    }
+
+    pub fn format_i128(&self, value: i128) -> String {
+        match self.width {
+            Some(width) => self.render(truncate(value as u128, width)),
+            None => self.render((value < 0, value.unsigned_abs())),
+        }
+    }
+
+    pub fn format_u128(&self, value: u128) -> String {
+        match self.width {
+            Some(width) => self.render(truncate(value, width)),
+            None => self.render((false, value)),
+        }
+    }
+
+    /// Integral values are rendered like integers, others in decimal unless `sci` is set.
+    pub fn format_f64(&self, value: f64) -> String {
+        match self.notation {
+            Notation::Sci => format!("{value:e}"),
+            _ if value.fract() == 0.0 && value.abs() < i64::MAX as f64 => self.format(value as i64),
+            _ => value.to_string(),
+        }
+    }
+
+    fn render(&self, (negative, magnitude): (bool, u128)) -> String {
+        let (prefix, digits, group_len) = match self.notation {
+            Notation::Dec => ("", magnitude.to_string(), 3),
+            Notation::Hex => ("0x", format!("{magnitude:x}"), 4),
+            Notation::Oct => ("0o", format!("{magnitude:o}"), 3),
+            Notation::Bin => ("0b", format!("{magnitude:b}"), 4),
+            Notation::Sci => ("", scientific(magnitude), 0),
+        };
+        let digits = if self.group && group_len > 0 {
+            group(&digits, group_len)
+        } else {
+            digits
+        };
+
+        let sign = if negative { "-" } else { "" };
+        format!("{sign}{prefix}{digits}")
+    }
+}
+
+/// Returns the sign and magnitude of `value` truncated to `width`.
+fn truncate(value: u128, width: Width) -> (bool, u128) {
+    let modulus = 1u128 << width.bits;
+    let bits = value & (modulus - 1);
+    if width.signed && bits >= modulus / 2 {
+        (true, modulus - bits)
+    } else {
+        (false, bits)
+    }
+}
+
+fn scientific(magnitude: u128) -> String {
+    let digits = magnitude.to_string();
+    let exponent = digits.len() - 1;
+    let mantissa = digits[1..].trim_end_matches('0');
+    if mantissa.is_empty() {
+        format!("{}e{exponent}", &digits[..1])
+    } else {
+        format!("{}.{mantissa}e{exponent}", &digits[..1])
+    }
+}
+
+/// Inserts `_` between groups of `len` digits, counting from the right.
+fn group(digits: &str, len: usize) -> String {
+    let mut out = String::with_capacity(digits.len() + digits.len() / len);
+    for (i, digit) in digits.chars().enumerate() {
+        if i > 0 && (digits.len() - i).is_multiple_of(len) {
+            out.push('_');
+        }
+        out.push(digit);
+    }
+    out
+}
+
+impl FromStr for NumberFormat {
+    type Err = String;
+
+    /// Parses space or comma separated options: one of `dec`, `hex`, `oct`, `bin` or `sci`,
+    /// `group`, and a width like `i32` or `u8`. Options left out take their default.
+    fn from_str(spec: &str) -> Result<Self, Self::Err> {
+        let mut format = NumberFormat::default();
+        for option in spec.split([' ', ',']).filter(|option| !option.is_empty()) {
+            match option {
+                "dec" => format.notation = Notation::Dec,
+                "hex" => format.notation = Notation::Hex,
+                "oct" => format.notation = Notation::Oct,
+                "bin" => format.notation = Notation::Bin,
+                "sci" => format.notation = Notation::Sci,
+                "group" => format.group = true,
+                _ => format.width = Some(parse_width(option)?),
+            }
+        }
+        Ok(format)
+    }
+}
+
+fn parse_width(option: &str) -> Result<Width, String> {
+    let signed = match option.get(..1) {
+        Some("i") => true,
+        Some("u") => false,
+        _ => return Err(format!("unknown format option `{option}`")),
+    };
+    match option[1..].parse::<u32>() {
+        Ok(bits @ 1..=64) => Ok(Width { bits, signed }),
+        _ => Err(format!(
+            "invalid width `{option}`, expected `i1` to `i64` or `u1` to `u64`"
+        )),
+    }
+}
+
+impl std::fmt::Display for NumberFormat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self.notation {
+            Notation::Dec => "dec",
+            Notation::Hex => "hex",
+            Notation::Oct => "oct",
+            Notation::Bin => "bin",
+            Notation::Sci => "sci",
+        })?;
+        if self.group {
+            f.write_str(" group")?;
+        }
+        if let Some(Width { bits, signed }) = self.width {
+            write!(f, " {}{bits}", if signed { 'i' } else { 'u' })?;
+        }
+        Ok(())
+    }
+}
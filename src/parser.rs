@@ -1,13 +1,20 @@
-use std::sync::Arc;
-
+use crate::arena::Ast;
 use crate::error::Error;
 use crate::error::Result;
 use crate::expr::Binary;
 use crate::expr::BinaryOp;
+use crate::expr::Convert;
 use crate::expr::Expr;
+use crate::expr::Folder;
+use crate::expr::Program;
 use crate::expr::Unary;
 use crate::expr::UnaryOp;
+use crate::expr::UnitExpr;
+use crate::expr::UnitFolder;
+use crate::expr::UnitPower;
 use crate::lexer::Lexer;
+use crate::source::SourceFile;
+use crate::span::Span;
 use crate::token::Token;
 use crate::token::TokenKind;
 
@@ -16,16 +23,12 @@ pub struct Parser<'src> {
 }
 
 impl<'src> Parser<'src> {
-    fn new(src: &'src str) -> Result<Self> {
+    fn new(file: &'src SourceFile, newlines: bool) -> Result<Self> {
         Ok(Self {
-            lex: Lexer::new(src)?,
+            lex: Lexer::new(file, newlines)?,
         })
     }
 
-    fn src(&self) -> &Arc<str> {
-        self.lex.src()
-    }
-
     fn at(&self, kind: TokenKind) -> bool {
         self.lex.current().is(kind)
     }
@@ -46,7 +49,6 @@ impl<'src> Parser<'src> {
     fn must(&mut self, kind: TokenKind) -> Result<()> {
         if !self.at(kind) {
             return Err(Error::new(
-                self.src().clone(),
                 self.lex.current().span,
                 format!(
                     "expected `{}` got `{}` instead",
@@ -70,96 +72,628 @@ impl<'src> Parser<'src> {
         self.lex.current()
     }
 
-    fn lexeme(&self, token: &Token) -> &str {
+    fn lexeme(&self, token: &Token) -> &'src str {
         &self.lex.src()[token.span]
     }
+
+    fn unexpected(&self) -> Error {
+        Error::new(
+            self.current().span,
+            format!("unexpected token `{}`", self.lexeme(self.current())),
+        )
+    }
+}
+
+/// Constructs the tree as the parser recognizes each node,
+/// so the same parser can produce different representations or evaluate the input directly.
+/// `span` points at the operator or literal, for errors.
+pub trait Builder {
+    type Node;
+
+    fn binary(
+        &mut self,
+        left: Self::Node,
+        op: BinaryOp,
+        right: Self::Node,
+        span: Span,
+    ) -> Result<Self::Node>;
+
+    fn unary(&mut self, op: UnaryOp, right: Self::Node, span: Span) -> Result<Self::Node>;
+
+    fn int(&mut self, value: i64, span: Span) -> Result<Self::Node>;
+
+    /// A literal as written, without any minus in front of it.
+    /// Only builders which can hold values beyond `i64::MAX` need to override this.
+    fn literal(&mut self, value: u128, span: Span) -> Result<Self::Node> {
+        let value = i64::try_from(value)
+            .map_err(|_| Error::new(span, "number too large to fit in target type".to_string()))?;
+        self.int(value, span)
+    }
+
+    /// A unit name like `km` or `s^2`, standing for one of that unit.
+    /// A unit after an operand, like `3 km`, is built as a multiplication.
+    fn unit(&mut self, name: &str, exponent: i8, span: Span) -> Result<Self::Node> {
+        let _ = exponent;
+        Err(Error::new(span, format!("unexpected unit `{name}`")))
+    }
+
+    /// `value in target`, where `target` only consists of units.
+    fn convert(&mut self, value: Self::Node, target: Self::Node, span: Span) -> Result<Self::Node> {
+        let _ = (value, target);
+        Err(Error::new(span, "unexpected unit conversion".to_string()))
+    }
+}
+
+struct Boxed;
+
+impl Builder for Boxed {
+    type Node = Expr;
+
+    fn binary(&mut self, left: Expr, op: BinaryOp, right: Expr, _: Span) -> Result<Expr> {
+        Ok(Expr::Binary(Box::new(Binary { left, op, right })))
+    }
+
+    fn unary(&mut self, op: UnaryOp, right: Expr, _: Span) -> Result<Expr> {
+        Ok(Expr::Unary(Box::new(Unary { op, right })))
+    }
+
+    fn int(&mut self, value: i64, _: Span) -> Result<Expr> {
+        Ok(Expr::Int(value))
+    }
+}
+
+/// Deepest tree `parse` accepts, counting every node on the way from the root to a leaf.
+/// Most consumers of `Expr` walk it recursively, so this bounds their stack usage.
+pub const DEFAULT_MAX_DEPTH: usize = 4096;
+
+pub fn parse(file: &SourceFile) -> Result<Expr> {
+    parse_with_max_depth(file, DEFAULT_MAX_DEPTH)
+}
+
+pub fn parse_with_max_depth(file: &SourceFile, max_depth: usize) -> Result<Expr> {
+    build(file, &mut Boxed, max_depth)
+}
+
+pub fn parse_ast(file: &SourceFile) -> Result<Ast> {
+    parse_ast_with_max_depth(file, DEFAULT_MAX_DEPTH)
+}
+
+pub fn parse_ast_with_max_depth(file: &SourceFile, max_depth: usize) -> Result<Ast> {
+    let mut ast = Ast::default();
+    build(file, &mut ast, max_depth)?;
+    Ok(ast)
+}
+
+/// Parses an expression along with the span of every node, in post-order.
+///
+/// A node's span covers the whole subexpression, except for enclosing parentheses.
+pub fn parse_spanned(file: &SourceFile, max_depth: usize) -> Result<(Expr, Vec<Span>)> {
+    let mut spanned = Spanned::new(file);
+    let (expr, _) = build(file, &mut spanned, max_depth)?;
+    Ok((expr, spanned.spans))
+}
+
+/// Records spans as the parser builds nodes, which it does in post-order.
+struct Spanned {
+    /// Offset of every parenthesis token, and whether it opens one.
+    parens: Vec<(usize, bool)>,
+    spans: Vec<Span>,
+}
+
+impl Spanned {
+    fn new(file: &SourceFile) -> Self {
+        // lexed rather than scanned, so parentheses in comments don't count
+        let parens = logos::Logos::lexer(file.text())
+            .spanned()
+            .filter_map(|(kind, range)| match kind {
+                Ok(TokenKind::ParenL) => Some((range.start, true)),
+                Ok(TokenKind::ParenR) => Some((range.start, false)),
+                _ => None,
+            })
+            .collect();
+        Spanned {
+            parens,
+            spans: Vec::new(),
+        }
+    }
+
+    fn node<E>(&mut self, expr: E, span: Span) -> Result<(E, Span)> {
+        let span = self.balance(span);
+        self.spans.push(span);
+        Ok((expr, span))
+    }
+
+    /// Widens `span` over the other half of any parentheses it only contains one of,
+    /// since operand spans don't include their parentheses, like `1 + 2)` in `(1 + 2) * 3`.
+    fn balance(&self, span: Span) -> Span {
+        let inside = self.parens.partition_point(|&(at, _)| at < span.start)
+            ..self.parens.partition_point(|&(at, _)| at < span.end);
+        let (mut depth, mut unclosed) = (0i32, 0i32);
+        for &(_, open) in &self.parens[inside.clone()] {
+            match open {
+                true => depth += 1,
+                false if depth == 0 => unclosed += 1,
+                false => depth -= 1,
+            }
+        }
+        let (mut start, mut end) = (span.start, span.end);
+        for &(at, open) in self.parens[..inside.start].iter().rev() {
+            match open {
+                _ if unclosed == 0 => break,
+                true => {
+                    unclosed -= 1;
+                    start = at;
+                }
+                false => {}
+            }
+        }
+        for &(at, open) in &self.parens[inside.end..] {
+            match open {
+                _ if depth == 0 => break,
+                false => {
+                    depth -= 1;
+                    end = at + 1;
+                }
+                true => {}
+            }
+        }
+        Span::new(span.file, start..end)
+    }
+}
+
+impl Builder for Spanned {
+    type Node = (Expr, Span);
+
+    fn binary(
+        &mut self,
+        (left, left_span): (Expr, Span),
+        op: BinaryOp,
+        (right, right_span): (Expr, Span),
+        span: Span,
+    ) -> Result<(Expr, Span)> {
+        let expr = Expr::Binary(Box::new(Binary { left, op, right }));
+        self.node(expr, Span::new(span.file, left_span.start..right_span.end))
+    }
+
+    fn unary(
+        &mut self,
+        op: UnaryOp,
+        (right, right_span): (Expr, Span),
+        span: Span,
+    ) -> Result<(Expr, Span)> {
+        let expr = Expr::Unary(Box::new(Unary { op, right }));
+        self.node(expr, Span::new(span.file, span.start..right_span.end))
+    }
+
+    fn int(&mut self, value: i64, span: Span) -> Result<(Expr, Span)> {
+        self.node(Expr::Int(value), span)
+    }
 }
 
-pub fn parse(src: &str) -> Result<Expr> {
-    let mut p = Parser::new(src)?;
-    let expr = parse_expr(&mut p)?;
+/// Parses statements which may contain units, along with the spans of each one's nodes
+/// like `parse_spanned`. Units are looked up when evaluating, see `folder::fold_units`.
+///
+/// A unit binds tighter than any operator, so it only applies to the operand right
+/// before it: `1/3 km` is one third of a reciprocal kilometer, and `10 m / 2 s^2`
+/// is an acceleration. Parenthesize the value to apply a unit to all of it, like `(1/3) km`.
+pub fn parse_units_program(
+    file: &SourceFile,
+    max_depth: usize,
+) -> Result<Vec<(UnitExpr, Vec<Span>)>> {
+    let mut b = WithUnits(Spanned::new(file));
+    let stmts = build_program(file, &mut b, max_depth)?;
+    let mut spans = b.0.spans.into_iter();
+    Ok(stmts
+        .into_iter()
+        .map(|(expr, _)| {
+            let spans = spans.by_ref().take(expr.fold(&mut Size)).collect();
+            (expr, spans)
+        })
+        .collect())
+}
+
+/// Same as `Spanned`, but builds `UnitExpr`s, with units and conversions.
+struct WithUnits(Spanned);
+
+impl Builder for WithUnits {
+    type Node = (UnitExpr, Span);
+
+    fn binary(
+        &mut self,
+        (left, left_span): (UnitExpr, Span),
+        op: BinaryOp,
+        (right, right_span): (UnitExpr, Span),
+        span: Span,
+    ) -> Result<(UnitExpr, Span)> {
+        let expr = UnitExpr::Binary(Box::new(Binary { left, op, right }));
+        self.0
+            .node(expr, Span::new(span.file, left_span.start..right_span.end))
+    }
+
+    fn unary(
+        &mut self,
+        op: UnaryOp,
+        (right, right_span): (UnitExpr, Span),
+        span: Span,
+    ) -> Result<(UnitExpr, Span)> {
+        let expr = UnitExpr::Unary(Box::new(Unary { op, right }));
+        self.0
+            .node(expr, Span::new(span.file, span.start..right_span.end))
+    }
+
+    fn int(&mut self, value: i64, span: Span) -> Result<(UnitExpr, Span)> {
+        self.0.node(UnitExpr::Int(value), span)
+    }
+
+    fn unit(&mut self, name: &str, exponent: i8, span: Span) -> Result<(UnitExpr, Span)> {
+        let name = name.to_string();
+        self.0
+            .node(UnitExpr::Unit(Box::new(UnitPower { name, exponent })), span)
+    }
+
+    fn convert(
+        &mut self,
+        (value, value_span): (UnitExpr, Span),
+        (target, _): (UnitExpr, Span),
+        span: Span,
+    ) -> Result<(UnitExpr, Span)> {
+        let expr = UnitExpr::Convert(Box::new(Convert { value, target }));
+        self.0
+            .node(expr, Span::new(span.file, value_span.start..span.end))
+    }
+}
+
+/// Number of nodes in the tree.
+struct Size;
+
+impl Folder for Size {
+    type Output = usize;
+
+    fn fold_binary(&mut self, _: BinaryOp, left: usize, right: usize) -> usize {
+        left + right + 1
+    }
+
+    fn fold_unary(&mut self, _: UnaryOp, right: usize) -> usize {
+        right + 1
+    }
+
+    fn fold_int(&mut self, _: i64) -> usize {
+        1
+    }
+}
+
+impl UnitFolder for Size {
+    fn fold_unit(&mut self, _: &UnitPower) -> usize {
+        1
+    }
+
+    fn fold_convert(&mut self, value: usize, target: usize) -> usize {
+        value + target + 1
+    }
+}
+
+/// Parses a single expression with a custom `Builder`.
+pub fn build<B: Builder>(file: &SourceFile, b: &mut B, max_depth: usize) -> Result<B::Node> {
+    let mut p = Parser::new(file, false)?;
+    let expr = parse_expr(&mut p, b, max_depth, Precedence::default())?;
     if !p.end() {
-        return Err(Error::new(
-            p.src().clone(),
-            p.current().span,
-            format!("unexpected token `{}`", p.lexeme(p.current())),
-        ));
+        return Err(p.unexpected());
     }
     Ok(expr)
 }
 
-fn parse_expr(p: &mut Parser) -> Result<Expr> {
-    parse_mul_or_div(p)
+/// Parses a sequence of statements. Unlike in a single expression,
+/// a line break ends a statement, unless it's inside parentheses or right after an operator.
+pub fn parse_program(file: &SourceFile) -> Result<Program> {
+    parse_program_with_max_depth(file, DEFAULT_MAX_DEPTH)
 }
 
-fn parse_mul_or_div(p: &mut Parser) -> Result<Expr> {
-    let mut left = parse_add_or_sub(p)?;
+pub fn parse_program_with_max_depth(file: &SourceFile, max_depth: usize) -> Result<Program> {
+    parse_program_with_precedence(file, max_depth, Precedence::default())
+}
 
-    while !p.end() {
-        let op = match p.current().kind {
-            TokenKind::Star => BinaryOp::Mul,
-            TokenKind::Slash => BinaryOp::Div,
-            _ => break,
-        };
-        p.bump()?; // bump op
-        let right = parse_add_or_sub(p)?;
+/// Same as `parse_program_with_max_depth`, with the given operator precedence.
+pub fn parse_program_with_precedence(
+    file: &SourceFile,
+    max_depth: usize,
+    precedence: Precedence,
+) -> Result<Program> {
+    let stmts = build_program_with_precedence(file, &mut Boxed, max_depth, precedence)?;
+    Ok(Program { stmts })
+}
 
-        left = Expr::Binary(Box::new(Binary { left, op, right }));
+/// Parses a sequence of statements with a custom `Builder`, see `parse_program`.
+pub fn build_program<B: Builder>(
+    file: &SourceFile,
+    b: &mut B,
+    max_depth: usize,
+) -> Result<Vec<B::Node>> {
+    build_program_with_precedence(file, b, max_depth, Precedence::default())
+}
+
+fn build_program_with_precedence<B: Builder>(
+    file: &SourceFile,
+    b: &mut B,
+    max_depth: usize,
+    precedence: Precedence,
+) -> Result<Vec<B::Node>> {
+    let mut p = Parser::new(file, true)?;
+    let mut stmts = Vec::new();
+    loop {
+        while p.eat(TokenKind::Semi)? || p.eat(TokenKind::Newline)? {}
+        if p.end() {
+            break;
+        }
+        stmts.push(parse_expr(&mut p, b, max_depth, precedence)?);
+        if !p.end() && !p.at(TokenKind::Semi) && !p.at(TokenKind::Newline) {
+            return Err(p.unexpected());
+        }
     }
+    Ok(stmts)
+}
 
-    Ok(left)
+/// Which binary operators bind tighter.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum Precedence {
+    /// `+` and `-` bind tighter than `*` and `/`, so `1 + 2 * 3` is 9.
+    #[default]
+    AddFirst,
+    /// `*` and `/` bind tighter than `+` and `-`, so `1 + 2 * 3` is 7.
+    MulFirst,
 }
 
-fn parse_add_or_sub(p: &mut Parser) -> Result<Expr> {
-    let mut left = parse_unary(p)?;
+// Precedences, higher binds tighter.
+const PREC_LOOSE: u8 = 1;
+const PREC_TIGHT: u8 = 2;
 
-    while !p.end() {
-        let op = match p.current().kind {
-            TokenKind::Plus => BinaryOp::Add,
-            TokenKind::Minus => BinaryOp::Sub,
-            _ => break,
-        };
-        p.bump()?; // bump op
-        let right = parse_unary(p)?;
+impl Precedence {
+    fn of(self, op: BinaryOp) -> u8 {
+        match (self, op) {
+            (Precedence::AddFirst, BinaryOp::Add | BinaryOp::Sub)
+            | (Precedence::MulFirst, BinaryOp::Mul | BinaryOp::Div) => PREC_TIGHT,
+            _ => PREC_LOOSE,
+        }
+    }
+}
 
-        left = Expr::Binary(Box::new(Binary { left, op, right }));
+/// Operator or parenthesis waiting for its operands.
+enum Pending {
+    Binary(BinaryOp, Span),
+    Unary(UnaryOp, Span),
+    Paren,
+}
+
+impl Pending {
+    fn precedence(&self, order: Precedence) -> u8 {
+        match self {
+            Pending::Binary(op, _) => order.of(*op),
+            Pending::Unary(..) => u8::MAX,
+            Pending::Paren => 0,
+        }
+    }
+}
+
+/// Operator precedence parser which keeps its state in explicit stacks
+/// instead of recursing, so arbitrarily nested input can't overflow the call stack.
+struct Stacks<'b, B: Builder> {
+    b: &'b mut B,
+    max_depth: usize,
+    order: Precedence,
+    /// Finished subtrees along with their depth.
+    operands: Vec<(B::Node, usize)>,
+    pending: Vec<Pending>,
+    parens: usize,
+}
+
+impl<B: Builder> Stacks<'_, B> {
+    fn push_operand(&mut self, node: B::Node, depth: usize, span: Span) -> Result<()> {
+        if depth > self.max_depth {
+            return Err(Error::new(
+                span,
+                format!(
+                    "expression nested too deeply, the limit is {}",
+                    self.max_depth
+                ),
+            ));
+        }
+        self.operands.push((node, depth));
+        Ok(())
     }
 
-    Ok(left)
+    /// Applies pending operators binding at least as tight as `precedence`.
+    fn reduce(&mut self, precedence: u8) -> Result<()> {
+        while self
+            .pending
+            .last()
+            .is_some_and(|top| top.precedence(self.order) >= precedence)
+        {
+            match self.pending.pop().unwrap() {
+                Pending::Binary(op, span) => {
+                    let (right, right_depth) = self.operands.pop().unwrap();
+                    let (left, left_depth) = self.operands.pop().unwrap();
+                    let node = self.b.binary(left, op, right, span)?;
+                    let depth = left_depth.max(right_depth) + 1;
+                    self.push_operand(node, depth, span)?;
+                }
+                Pending::Unary(op, span) => {
+                    let (right, depth) = self.operands.pop().unwrap();
+                    let node = self.b.unary(op, right, span)?;
+                    self.push_operand(node, depth + 1, span)?;
+                }
+                Pending::Paren => unreachable!(),
+            }
+        }
+        Ok(())
+    }
 }
 
-fn parse_unary(p: &mut Parser) -> Result<Expr> {
-    let op = match p.current().kind {
-        TokenKind::Plus => UnaryOp::Plus,
-        TokenKind::Minus => UnaryOp::Minus,
-        _ => return parse_primary(p),
+fn parse_expr<B: Builder>(
+    p: &mut Parser,
+    b: &mut B,
+    max_depth: usize,
+    order: Precedence,
+) -> Result<B::Node> {
+    let mut s = Stacks {
+        b,
+        max_depth,
+        order,
+        operands: Vec::new(),
+        pending: Vec::new(),
+        parens: 0,
     };
-    p.bump()?;
-    let right = parse_unary(p)?;
 
-    Ok(Expr::Unary(Box::new(Unary { op, right })))
+    loop {
+        // operand, with any number of unary operators and parentheses in front
+        loop {
+            let span = p.current().span;
+            match p.current().kind {
+                TokenKind::Plus => s.pending.push(Pending::Unary(UnaryOp::Plus, span)),
+                TokenKind::Minus => s.pending.push(Pending::Unary(UnaryOp::Minus, span)),
+                TokenKind::ParenL => {
+                    s.parens += 1;
+                    s.pending.push(Pending::Paren);
+                }
+                // the expression can't end here, so the line continues
+                TokenKind::Newline => {}
+                _ => break,
+            }
+            p.bump()?;
+        }
+        let node = parse_primary(p, s.b)?;
+        let span = p.previous().span;
+        s.push_operand(node, 1, span)?;
+
+        // binary operator, with any number of closing parentheses in front
+        loop {
+            let op = match p.current().kind {
+                TokenKind::Star => BinaryOp::Mul,
+                TokenKind::Slash => BinaryOp::Div,
+                TokenKind::Plus => BinaryOp::Add,
+                TokenKind::Minus => BinaryOp::Sub,
+                TokenKind::Newline if s.parens > 0 => {
+                    p.bump()?;
+                    continue;
+                }
+                // unit after an operand, binding tighter than any operator
+                TokenKind::Ident => {
+                    let span = p.current().span;
+                    let unit = parse_primary(p, s.b)?;
+                    let (value, depth) = s.operands.pop().unwrap();
+                    let node = s.b.binary(value, BinaryOp::Mul, unit, span)?;
+                    s.push_operand(node, depth + 1, span)?;
+                    continue;
+                }
+                // conversion, applying to everything since the innermost open parenthesis
+                TokenKind::In | TokenKind::To => {
+                    let start = p.current().span;
+                    p.bump()?;
+                    s.reduce(PREC_LOOSE)?;
+                    let target = parse_unit(p, s.b)?;
+                    let span = Span::new(start.file, start.start..p.previous().span.end);
+                    let (value, depth) = s.operands.pop().unwrap();
+                    let node = s.b.convert(value, target, span)?;
+                    s.push_operand(node, depth + 1, span)?;
+                    continue;
+                }
+                _ if s.parens > 0 => {
+                    s.reduce(PREC_LOOSE)?;
+                    p.must(TokenKind::ParenR)?;
+                    s.pending.pop(); // pop paren
+                    s.parens -= 1;
+                    continue;
+                }
+                _ => {
+                    s.reduce(PREC_LOOSE)?;
+                    return Ok(s.operands.pop().unwrap().0);
+                }
+            };
+            let op = Pending::Binary(op, p.current().span);
+            s.reduce(op.precedence(s.order))?;
+            s.pending.push(op);
+            p.bump()?; // bump op
+            break;
+        }
+    }
 }
 
-fn parse_primary(p: &mut Parser) -> Result<Expr> {
+fn parse_primary<B: Builder>(p: &mut Parser, b: &mut B) -> Result<B::Node> {
     if p.eat(TokenKind::Int)? {
         let token = p.previous();
-        let value = p
-            .lexeme(token)
-            .parse::<i64>()
-            .map_err(|e| Error::new(p.src().clone(), token.span, e.to_string()))
-            .map(Expr::Int)?;
-        return Ok(value);
+        let value = parse_int(p.lexeme(token)).map_err(|e| Error::new(token.span, e))?;
+        return b.literal(value, token.span);
     }
 
-    if p.eat(TokenKind::ParenL)? {
-        let value = parse_expr(p)?;
-        p.must(TokenKind::ParenR)?;
-        return Ok(value);
+    if p.at(TokenKind::Ident) {
+        return parse_unit_power(p, b);
     }
 
-    Err(Error::new(
-        p.src().clone(),
-        p.current().span,
-        "unexpected eof".to_string(),
-    ))
+    Err(Error::new(p.current().span, "unexpected eof".to_string()))
+}
+
+/// Parses a conversion target, units multiplied or divided like `kg m/s^2`.
+fn parse_unit<B: Builder>(p: &mut Parser, b: &mut B) -> Result<B::Node> {
+    let mut unit = parse_unit_power(p, b)?;
+    loop {
+        let span = p.current().span;
+        let op = match p.current().kind {
+            TokenKind::Star => BinaryOp::Mul,
+            TokenKind::Slash => BinaryOp::Div,
+            TokenKind::Ident => {
+                let right = parse_unit_power(p, b)?;
+                unit = b.binary(unit, BinaryOp::Mul, right, span)?;
+                continue;
+            }
+            _ => break,
+        };
+        p.bump()?; // bump op
+        let right = parse_unit_power(p, b)?;
+        unit = b.binary(unit, op, right, span)?;
+    }
+    Ok(unit)
+}
+
+/// Parses a unit name with an optional exponent, like `km` or `s^-1`.
+fn parse_unit_power<B: Builder>(p: &mut Parser, b: &mut B) -> Result<B::Node> {
+    p.must(TokenKind::Ident)?;
+    let name = *p.previous();
+    if !p.eat(TokenKind::Caret)? {
+        return b.unit(p.lexeme(&name), 1, name.span);
+    }
+    let start = p.current().span;
+    let negative = p.eat(TokenKind::Minus)?;
+    p.must(TokenKind::Int)?;
+    let token = p.previous();
+    let digits = parse_int(p.lexeme(token)).map_err(|e| Error::new(token.span, e))?;
+    let value = i128::try_from(digits).map(|digits| if negative { -digits } else { digits });
+    let exponent = value
+        .ok()
+        .and_then(|value| i8::try_from(value).ok())
+        .ok_or_else(|| {
+            let span = Span::new(start.file, start.start..token.span.end);
+            Error::new(
+                span,
+                format!("exponent must be between {} and {}", i8::MIN, i8::MAX),
+            )
+        })?;
+    let span = Span::new(name.span.file, name.span.start..token.span.end);
+    b.unit(p.lexeme(&name), exponent, span)
+}
+
+/// Parses a decimal, `0x` hexadecimal, `0o` octal or `0b` binary literal,
+/// with any number of `_` separators after the first digit.
+fn parse_int(lexeme: &str) -> Result<u128, String> {
+    let (prefix, base) = match lexeme.get(..2) {
+        Some("0x" | "0X") => (&lexeme[..2], 16),
+        Some("0o" | "0O") => (&lexeme[..2], 8),
+        Some("0b" | "0B") => (&lexeme[..2], 2),
+        _ => ("", 10),
+    };
+    let digits = lexeme[prefix.len()..].replace('_', "");
+    if digits.is_empty() {
+        return Err(format!("missing digits after `{prefix}`"));
+    }
+    if let Some(digit) = digits.chars().find(|c| !c.is_digit(base)) {
+        return Err(format!("invalid digit `{digit}` in base {base} literal"));
+    }
+    u128::from_str_radix(&digits, base).map_err(|e| e.to_string())
 }
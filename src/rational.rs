@@ -0,0 +1,133 @@
+use crate::trap::Trap;
+
+/// Exact fraction, kept normalized: the denominator is positive and shares no factor with the numerator.
+///
+/// Operations are always checked, and report overflow of the normalized result
+/// with the same traps as integer arithmetic.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct Rational {
+    numer: i64,
+    denom: i64,
+}
+
+impl Rational {
+    pub fn new(numer: i64, denom: i64) -> Result<Self, Trap> {
+        Self::reduce(numer as i128, denom as i128, Trap::DivOverflow)
+    }
+
+    pub fn from_int(value: i64) -> Self {
+        Rational {
+            numer: value,
+            denom: 1,
+        }
+    }
+
+    pub fn numer(&self) -> i64 {
+        self.numer
+    }
+
+    pub fn denom(&self) -> i64 {
+        self.denom
+    }
+
+    pub fn is_integer(&self) -> bool {
+        self.denom == 1
+    }
+
+    pub fn checked_add(self, other: Self) -> Result<Self, Trap> {
+        let numer =
+            self.numer as i128 * other.denom as i128 + other.numer as i128 * self.denom as i128;
+        let denom = self.denom as i128 * other.denom as i128;
+        Self::reduce(numer, denom, Trap::AddOverflow)
+    }
+
+    pub fn checked_sub(self, other: Self) -> Result<Self, Trap> {
+        let numer =
+            self.numer as i128 * other.denom as i128 - other.numer as i128 * self.denom as i128;
+        let denom = self.denom as i128 * other.denom as i128;
+        Self::reduce(numer, denom, Trap::SubOverflow)
+    }
+
+    pub fn checked_mul(self, other: Self) -> Result<Self, Trap> {
+        let numer = self.numer as i128 * other.numer as i128;
+        let denom = self.denom as i128 * other.denom as i128;
+        Self::reduce(numer, denom, Trap::MulOverflow)
+    }
+
+    pub fn checked_div(self, other: Self) -> Result<Self, Trap> {
+        let numer = self.numer as i128 * other.denom as i128;
+        let denom = self.denom as i128 * other.numer as i128;
+        Self::reduce(numer, denom, Trap::DivOverflow)
+    }
+
+    pub fn checked_neg(self) -> Result<Self, Trap> {
+        let numer = self.numer.checked_neg().ok_or(Trap::NegOverflow)?;
+        Ok(Rational {
+            numer,
+            denom: self.denom,
+        })
+    }
+
+    fn reduce(numer: i128, denom: i128, overflow: Trap) -> Result<Self, Trap> {
+        if denom == 0 {
+            return Err(Trap::DivByZero);
+        }
+        let gcd = gcd(numer.unsigned_abs(), denom.unsigned_abs()) as i128;
+        let (numer, denom) = (numer / gcd, denom / gcd);
+        let (numer, denom) = if denom < 0 {
+            (-numer, -denom)
+        } else {
+            (numer, denom)
+        };
+        Ok(Rational {
+            numer: i64::try_from(numer).map_err(|_| overflow)?,
+            denom: i64::try_from(denom).map_err(|_| overflow)?,
+        })
+    }
+
+    /// Decimal expansion truncated to at most `places` fractional digits,
+    /// ending in `…` if digits were cut off, e.g. `0.333…` or `-2.5`.
+    pub fn to_decimal(&self, places: usize) -> String {
+        let denom = self.denom.unsigned_abs();
+        let numer = self.numer.unsigned_abs();
+        let mut out = String::new();
+        if self.numer < 0 {
+            out.push('-');
+        }
+        out.push_str(&(numer / denom).to_string());
+
+        let mut remainder = (numer % denom) as u128;
+        if remainder != 0 && places > 0 {
+            out.push('.');
+        }
+        for _ in 0..places {
+            if remainder == 0 {
+                break;
+            }
+            remainder *= 10;
+            out.push(char::from(b'0' + (remainder / denom as u128) as u8));
+            remainder %= denom as u128;
+        }
+        if remainder != 0 {
+            out.push('…');
+        }
+        out
+    }
+}
+
+fn gcd(mut a: u128, mut b: u128) -> u128 {
+    while b != 0 {
+        (a, b) = (b, a % b);
+    }
+    a
+}
+
+impl std::fmt::Display for Rational {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.is_integer() {
+            write!(f, "{}", self.numer)
+        } else {
+            write!(f, "{}/{}", self.numer, self.denom)
+        }
+    }
+}
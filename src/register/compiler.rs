@@ -1,6 +1,11 @@
+use std::collections::HashMap;
+
+use crate::error::Error;
+use crate::error::Result;
 use crate::expr::BinaryOp;
 use crate::expr::Expr;
 use crate::expr::UnaryOp;
+use crate::span::Span;
 
 use super::op;
 use super::op::Op;
@@ -8,70 +13,222 @@ use super::op::Op;
 pub type Bytecode = Vec<Op>;
 pub type ConstPool = Vec<i64>;
 pub type StackSize = usize;
+/// Node each instruction was compiled from, by its position in post-order,
+/// the order `Expr::fold` visits nodes in.
+pub type Origins = Vec<usize>;
 
-pub fn compile(expr: &Expr) -> (Bytecode, ConstPool, StackSize) {
-    fn emit(expr: &Expr, ops: &mut Bytecode, pool: &mut ConstPool, reg: &mut RegAlloc, dst: u8) {
-        match expr {
-            Expr::Binary(expr) => {
-                let lhs = dst;
-                emit(&expr.left, ops, pool, reg, lhs);
-                let rhs = reg.alloc();
-                emit(&expr.right, ops, pool, reg, rhs);
-                match expr.op {
-                    BinaryOp::Add => ops.push(op::BAdd(lhs, lhs, rhs)),
-                    BinaryOp::Sub => ops.push(op::BSub(lhs, lhs, rhs)),
-                    BinaryOp::Mul => ops.push(op::BMul(lhs, lhs, rhs)),
-                    BinaryOp::Div => ops.push(op::BDiv(lhs, lhs, rhs)),
+/// Fails if `expr` needs more registers than instructions can address,
+/// which takes right operands nested about 256 levels deep.
+pub fn compile(expr: &Expr) -> Result<(Bytecode, ConstPool, StackSize)> {
+    compile_spanned(expr, &[])
+}
+
+/// Same as `compile`, pointing errors at the offending node.
+///
+/// `spans` are the spans of `expr`'s nodes in post-order, as returned by
+/// `parser::parse_spanned`, or empty if there's no source.
+pub fn compile_spanned(expr: &Expr, spans: &[Span]) -> Result<(Bytecode, ConstPool, StackSize)> {
+    let emitter = emit(expr, spans)?;
+    Ok((emitter.ops, emitter.pool, emitter.reg.stack_size()))
+}
+
+/// Same as `compile_spanned`, also returning where each instruction came from.
+pub fn compile_with_origins(
+    expr: &Expr,
+    spans: &[Span],
+) -> Result<(Bytecode, ConstPool, StackSize, Origins)> {
+    let emitter = emit(expr, spans)?;
+    let nodes = post_order(expr);
+    let origins = emitter
+        .origins
+        .iter()
+        .map(|&node| nodes[&(node as *const Expr)])
+        .collect();
+    Ok((emitter.ops, emitter.pool, emitter.reg.stack_size(), origins))
+}
+
+fn emit<'a>(expr: &'a Expr, spans: &[Span]) -> Result<Emitter<'a>> {
+    let mut reg = RegAlloc::default();
+    let dst = reg.alloc().expect("the first register is free");
+    let mut emitter = Emitter {
+        ops: Vec::new(),
+        pool: Vec::new(),
+        origins: Vec::new(),
+        reg,
+        tasks: vec![Task::Emit(expr, dst)],
+    };
+    match emitter.run() {
+        Ok(()) => Ok(emitter),
+        Err((node, message)) => {
+            let span = spans
+                .get(post_order(expr)[&(node as *const Expr)])
+                .copied()
+                .unwrap_or_default();
+            Err(Error::new(span, message))
+        }
+    }
+}
+
+/// Work left to do, kept on an explicit stack so deep trees can't overflow the call stack.
+/// Tasks finishing a node also hold the node itself, as the origin of the instructions.
+enum Task<'a> {
+    /// Emit code which writes the value of the expression into the register.
+    Emit(&'a Expr, u8),
+    /// Combine `lhs` with the right operand, once the left one is done.
+    Right(BinaryOp, u8, &'a Expr, &'a Expr),
+    /// Combine `lhs` with `rhs`, once both operands are done.
+    Binary(BinaryOp, u8, u8, &'a Expr),
+    Unary(UnaryOp, u8, &'a Expr),
+}
+
+struct Emitter<'a> {
+    ops: Bytecode,
+    pool: ConstPool,
+    origins: Vec<&'a Expr>,
+    reg: RegAlloc,
+    tasks: Vec<Task<'a>>,
+}
+
+impl<'a> Emitter<'a> {
+    /// Fails with the node which can't be compiled and why,
+    /// like an operand there's no register left for.
+    fn run(&mut self) -> Result<(), (&'a Expr, String)> {
+        while let Some(task) = self.tasks.pop() {
+            match task {
+                Task::Emit(node @ Expr::Binary(expr), dst) => {
+                    self.tasks
+                        .push(Task::Right(expr.op, dst, &expr.right, node));
+                    self.tasks.push(Task::Emit(&expr.left, dst));
                 }
-                reg.free(rhs);
-            }
-            Expr::Unary(expr) => {
-                let rhs = dst;
-                emit(&expr.right, ops, pool, reg, rhs);
-                match expr.op {
+                Task::Emit(node @ Expr::Unary(expr), dst) => {
+                    self.tasks.push(Task::Unary(expr.op, dst, node));
+                    self.tasks.push(Task::Emit(&expr.right, dst));
+                }
+                Task::Emit(node @ Expr::Int(value), dst) => self.int(dst, *value, node),
+                Task::Right(op, lhs, right, node) => self.right(op, lhs, right, node)?,
+                Task::Binary(op, lhs, rhs, node) => {
+                    match op {
+                        BinaryOp::Add => self.push(op::BAdd(lhs, lhs, rhs), node),
+                        BinaryOp::Sub => self.push(op::BSub(lhs, lhs, rhs), node),
+                        BinaryOp::Mul => self.push(op::BMul(lhs, lhs, rhs), node),
+                        BinaryOp::Div => self.push(op::BDiv(lhs, lhs, rhs), node),
+                    }
+                    self.reg.free(rhs);
+                }
+                Task::Unary(op, rhs, node) => match op {
                     UnaryOp::Plus => {}
-                    UnaryOp::Minus => ops.push(op::UMinus(rhs, rhs)),
+                    UnaryOp::Minus => self.push(op::UMinus(rhs, rhs), node),
+                },
+            }
+        }
+        Ok(())
+    }
+
+    fn push(&mut self, op: Op, origin: &'a Expr) {
+        self.ops.push(op);
+        self.origins.push(origin);
+    }
+
+    fn right(
+        &mut self,
+        op: BinaryOp,
+        lhs: u8,
+        right: &'a Expr,
+        node: &'a Expr,
+    ) -> Result<(), (&'a Expr, String)> {
+        match *right {
+            Expr::Int(value @ MIN_IMM_INT..=MAX_IMM_INT) => {
+                let imm = value as i8;
+                match op {
+                    BinaryOp::Add => self.push(op::BAddI(lhs, lhs, imm), node),
+                    BinaryOp::Sub => self.push(op::BSubI(lhs, lhs, imm), node),
+                    BinaryOp::Mul => self.push(op::BMulI(lhs, lhs, imm), node),
+                    BinaryOp::Div => self.push(op::BDivI(lhs, lhs, imm), node),
                 }
             }
-            Expr::Int(value) => match value {
-                MIN_INLINE_INT..=MAX_INLINE_INT => ops.push(op::LInt(dst, *value as i16)),
-                _ => {
-                    let i = pool.len() as u16;
-                    pool.push(*value);
-                    ops.push(op::LConst(dst, i));
+            Expr::Int(value) if self.pool.len() <= MAX_KIDX => {
+                let kidx = self.pool.len() as u8;
+                self.pool.push(value);
+                match op {
+                    BinaryOp::Add => self.push(op::BAddK(lhs, lhs, kidx), node),
+                    BinaryOp::Sub => self.push(op::BSubK(lhs, lhs, kidx), node),
+                    BinaryOp::Mul => self.push(op::BMulK(lhs, lhs, kidx), node),
+                    BinaryOp::Div => self.push(op::BDivK(lhs, lhs, kidx), node),
                 }
-            },
+            }
+            _ => {
+                let rhs = self.reg.alloc().ok_or_else(|| {
+                    let message = format!("expression needs more than {MAX_REGS} registers");
+                    (right, message)
+                })?;
+                self.tasks.push(Task::Binary(op, lhs, rhs, node));
+                self.tasks.push(Task::Emit(right, rhs));
+            }
         }
+        Ok(())
     }
 
-    let (mut ops, mut pool, mut reg) = (Vec::new(), Vec::new(), RegAlloc::default());
-    let dst = reg.alloc();
-    emit(expr, &mut ops, &mut pool, &mut reg, dst);
-    (ops, pool, reg.stack_size())
+    fn int(&mut self, dst: u8, value: i64, node: &'a Expr) {
+        match value {
+            MIN_INLINE_INT..=MAX_INLINE_INT => self.push(op::LInt(dst, value as i16), node),
+            _ => {
+                let i = self.pool.len() as u16;
+                self.pool.push(value);
+                self.push(op::LConst(dst, i), node);
+            }
+        }
+    }
 }
 
 const MIN_INLINE_INT: i64 = i16::MIN as i64;
 const MAX_INLINE_INT: i64 = i16::MAX as i64;
+const MIN_IMM_INT: i64 = i8::MIN as i64;
+const MAX_IMM_INT: i64 = i8::MAX as i64;
+const MAX_KIDX: usize = u8::MAX as usize;
+const MAX_REGS: usize = u8::MAX as usize + 1;
 
+/// Registers are numbered with a `u8`, so expressions needing more of them can't be compiled.
 #[derive(Default)]
 struct RegAlloc {
-    current: u8,
-    max: u8,
+    current: usize,
+    max: usize,
 }
 
 impl RegAlloc {
-    fn alloc(&mut self) -> u8 {
-        let reg = self.current;
+    fn alloc(&mut self) -> Option<u8> {
+        let reg = u8::try_from(self.current).ok()?;
         self.current += 1;
         self.max = std::cmp::max(self.max, self.current);
-        reg
+        Some(reg)
     }
 
     fn free(&mut self, to: u8) {
-        self.current = to;
+        self.current = to as usize;
     }
 
     fn stack_size(&self) -> usize {
-        self.max as usize
+        self.max
+    }
+}
+
+/// Position of every node of `expr` in post-order, keyed by address.
+fn post_order(expr: &Expr) -> HashMap<*const Expr, usize> {
+    let mut nodes = HashMap::new();
+    let mut stack = vec![(expr, false)];
+    while let Some((expr, visited)) = stack.pop() {
+        if visited {
+            nodes.insert(expr as *const Expr, nodes.len());
+            continue;
+        }
+        stack.push((expr, true));
+        match expr {
+            Expr::Binary(expr) => {
+                stack.push((&expr.right, false));
+                stack.push((&expr.left, false));
+            }
+            Expr::Unary(expr) => stack.push((&expr.right, false)),
+            Expr::Int(_) => {}
+        }
     }
+    nodes
 }
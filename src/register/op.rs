@@ -11,8 +11,37 @@ macro_rules! newtype_enum {
       $($variant($variant)),*
     }
 
+    impl $name {
+      $vis fn name(&self) -> &'static str {
+        match self {
+          $($name::$variant(_) => stringify!($variant)),*
+        }
+      }
+
+      /// Register written by the instruction.
+      $vis fn dst(&self) -> u8 {
+        match self {
+          $($name::$variant(n) => n.dst),*
+        }
+      }
+    }
+
+    /// Disassembles the instruction, like `BAddI dst=0 lhs=0 imm=1`.
+    impl std::fmt::Display for $name {
+      fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+          $($name::$variant(n) => {
+            f.write_str(stringify!($variant))?;
+            // braces copy the field, packed fields can't be borrowed
+            $(write!(f, " {}={}", stringify!($field), { n.$field })?;)*
+            Ok(())
+          }),*
+        }
+      }
+    }
+
     $(
-      #[repr(packed)]
+      #[repr(C, packed)]
       $vis struct $variant {
         $(pub $field : $ty),*
       }
@@ -36,6 +65,14 @@ newtype_enum! {
     BMul { dst: u8, lhs: u8, rhs: u8 },
     BDiv { dst: u8, lhs: u8, rhs: u8 },
     UMinus { dst: u8, rhs: u8 },
+    BAddI { dst: u8, lhs: u8, imm: i8 },
+    BSubI { dst: u8, lhs: u8, imm: i8 },
+    BMulI { dst: u8, lhs: u8, imm: i8 },
+    BDivI { dst: u8, lhs: u8, imm: i8 },
+    BAddK { dst: u8, lhs: u8, kidx: u8 },
+    BSubK { dst: u8, lhs: u8, kidx: u8 },
+    BMulK { dst: u8, lhs: u8, kidx: u8 },
+    BDivK { dst: u8, lhs: u8, kidx: u8 },
   }
 }
 
@@ -1,14 +1,40 @@
+use std::iter::Peekable;
+use std::slice::Iter;
+
 use super::compiler::Bytecode;
 use super::compiler::ConstPool;
 use super::compiler::StackSize;
+use super::op::Op;
+use crate::expr::BinaryOp;
+use crate::expr::UnaryOp;
+use crate::int_mode::IntMode;
+use crate::int_mode::Value;
+use crate::stats::Observer;
+use crate::trap::Trap;
 
 pub fn eval(ops: &Bytecode, pool: &ConstPool, stack_size: StackSize) -> i64 {
+    eval_observed(ops, pool, stack_size, &mut ())
+}
+
+/// Same as `eval`, reporting every step to `observer`.
+///
+/// The depth is the highest register written so far, since registers are allocated like a stack.
+pub fn eval_observed<O: Observer>(
+    ops: &Bytecode,
+    pool: &ConstPool,
+    stack_size: StackSize,
+    observer: &mut O,
+) -> i64 {
     let mut stack = vec![0i64; stack_size];
 
     for op in ops {
+        observer.op(op.name());
         match op {
             super::op::Op::LInt(n) => stack[n.dst as usize] = n.val as i64,
-            super::op::Op::LConst(n) => stack[n.dst as usize] = pool[n.idx as usize],
+            super::op::Op::LConst(n) => {
+                observer.const_load();
+                stack[n.dst as usize] = pool[n.idx as usize]
+            }
             super::op::Op::BAdd(n) => {
                 stack[n.dst as usize] = stack[n.lhs as usize] + stack[n.rhs as usize]
             }
@@ -22,8 +48,102 @@ pub fn eval(ops: &Bytecode, pool: &ConstPool, stack_size: StackSize) -> i64 {
                 stack[n.dst as usize] = stack[n.lhs as usize] / stack[n.rhs as usize]
             }
             super::op::Op::UMinus(n) => stack[n.dst as usize] = -stack[n.rhs as usize],
+            super::op::Op::BAddI(n) => stack[n.dst as usize] = stack[n.lhs as usize] + n.imm as i64,
+            super::op::Op::BSubI(n) => stack[n.dst as usize] = stack[n.lhs as usize] - n.imm as i64,
+            super::op::Op::BMulI(n) => stack[n.dst as usize] = stack[n.lhs as usize] * n.imm as i64,
+            super::op::Op::BDivI(n) => stack[n.dst as usize] = stack[n.lhs as usize] / n.imm as i64,
+            super::op::Op::BAddK(n) => {
+                observer.const_load();
+                stack[n.dst as usize] = stack[n.lhs as usize] + pool[n.kidx as usize]
+            }
+            super::op::Op::BSubK(n) => {
+                observer.const_load();
+                stack[n.dst as usize] = stack[n.lhs as usize] - pool[n.kidx as usize]
+            }
+            super::op::Op::BMulK(n) => {
+                observer.const_load();
+                stack[n.dst as usize] = stack[n.lhs as usize] * pool[n.kidx as usize]
+            }
+            super::op::Op::BDivK(n) => {
+                observer.const_load();
+                stack[n.dst as usize] = stack[n.lhs as usize] / pool[n.kidx as usize]
+            }
         }
+        observer.depth(op.dst() as usize + 1);
     }
 
     stack[0]
 }
+
+/// Same as `eval`, but with the integer type and overflow behavior of `mode`.
+pub fn eval_with(
+    ops: &Bytecode,
+    pool: &ConstPool,
+    stack_size: StackSize,
+    mode: IntMode,
+) -> Result<Value, Trap> {
+    let mut stack = vec![mode.int(0)?; stack_size];
+
+    let mut ops = ops.iter().peekable();
+    while let Some(op) = ops.next() {
+        let binary = |op, lhs: u8, right| mode.binary(op, stack[lhs as usize], right);
+        let (dst, value) = match op {
+            Op::LInt(n) => literal(mode, n.dst, n.val as i64, &mut ops)?,
+            Op::LConst(n) => literal(mode, n.dst, pool[n.idx as usize], &mut ops)?,
+            Op::BAdd(n) => (n.dst, binary(BinaryOp::Add, n.lhs, stack[n.rhs as usize])?),
+            Op::BSub(n) => (n.dst, binary(BinaryOp::Sub, n.lhs, stack[n.rhs as usize])?),
+            Op::BMul(n) => (n.dst, binary(BinaryOp::Mul, n.lhs, stack[n.rhs as usize])?),
+            Op::BDiv(n) => (n.dst, binary(BinaryOp::Div, n.lhs, stack[n.rhs as usize])?),
+            Op::UMinus(n) => (n.dst, mode.unary(UnaryOp::Minus, stack[n.rhs as usize])?),
+            Op::BAddI(n) => (
+                n.dst,
+                binary(BinaryOp::Add, n.lhs, mode.int(n.imm as i64)?)?,
+            ),
+            Op::BSubI(n) => (
+                n.dst,
+                binary(BinaryOp::Sub, n.lhs, mode.int(n.imm as i64)?)?,
+            ),
+            Op::BMulI(n) => (
+                n.dst,
+                binary(BinaryOp::Mul, n.lhs, mode.int(n.imm as i64)?)?,
+            ),
+            Op::BDivI(n) => (
+                n.dst,
+                binary(BinaryOp::Div, n.lhs, mode.int(n.imm as i64)?)?,
+            ),
+            Op::BAddK(n) => (
+                n.dst,
+                binary(BinaryOp::Add, n.lhs, mode.int(pool[n.kidx as usize])?)?,
+            ),
+            Op::BSubK(n) => (
+                n.dst,
+                binary(BinaryOp::Sub, n.lhs, mode.int(pool[n.kidx as usize])?)?,
+            ),
+            Op::BMulK(n) => (
+                n.dst,
+                binary(BinaryOp::Mul, n.lhs, mode.int(pool[n.kidx as usize])?)?,
+            ),
+            Op::BDivK(n) => (
+                n.dst,
+                binary(BinaryOp::Div, n.lhs, mode.int(pool[n.kidx as usize])?)?,
+            ),
+        };
+        stack[dst as usize] = value;
+    }
+
+    Ok(stack[0])
+}
+
+/// Converts a literal, along with a negation of it right after it, see `IntMode::literal`.
+/// Also returns the register to write it to.
+fn literal(
+    mode: IntMode,
+    dst: u8,
+    value: i64,
+    ops: &mut Peekable<Iter<Op>>,
+) -> Result<(u8, Value), Trap> {
+    match ops.next_if(|op| matches!(op, Op::UMinus(n) if n.rhs == dst)) {
+        Some(Op::UMinus(n)) => Ok((n.dst, mode.literal(value, true)?)),
+        _ => Ok((dst, mode.literal(value, false)?)),
+    }
+}
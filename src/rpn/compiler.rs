@@ -1,37 +1,50 @@
 use super::op::Op;
+use crate::arena::Ast;
 use crate::expr::BinaryOp;
 use crate::expr::Expr;
+use crate::expr::Folder;
 use crate::expr::UnaryOp;
 
 pub type Bytecode = Vec<Op>;
 
 pub fn compile(expr: &Expr) -> Bytecode {
-    fn emit(expr: &Expr, ops: &mut Bytecode) {
-        match expr {
-            Expr::Binary(expr) => {
-                emit(&expr.left, ops);
-                emit(&expr.right, ops);
-                let op = match expr.op {
-                    BinaryOp::Add => Op::BAdd,
-                    BinaryOp::Sub => Op::BSub,
-                    BinaryOp::Mul => Op::BMul,
-                    BinaryOp::Div => Op::BDiv,
-                };
-                ops.push(op);
-            }
-            Expr::Unary(expr) => {
-                emit(&expr.right, ops);
-                let op = match expr.op {
-                    UnaryOp::Plus => return,
-                    UnaryOp::Minus => Op::UMinus,
-                };
-                ops.push(op);
-            }
-            Expr::Int(value) => ops.push(Op::LInt(*value)),
-        }
+    let mut emitter = Emitter { ops: Vec::new() };
+    expr.fold(&mut emitter);
+    emitter.ops
+}
+
+pub fn compile_ast(ast: &Ast) -> Bytecode {
+    let mut emitter = Emitter { ops: Vec::new() };
+    ast.fold(&mut emitter);
+    emitter.ops
+}
+
+struct Emitter {
+    ops: Bytecode,
+}
+
+impl Folder for Emitter {
+    type Output = ();
+
+    fn fold_binary(&mut self, op: BinaryOp, _: (), _: ()) {
+        let op = match op {
+            BinaryOp::Add => Op::BAdd,
+            BinaryOp::Sub => Op::BSub,
+            BinaryOp::Mul => Op::BMul,
+            BinaryOp::Div => Op::BDiv,
+        };
+        self.ops.push(op);
     }
 
-    let mut ops = Vec::new();
-    emit(expr, &mut ops);
-    ops
+    fn fold_unary(&mut self, op: UnaryOp, _: ()) {
+        let op = match op {
+            UnaryOp::Plus => return,
+            UnaryOp::Minus => Op::UMinus,
+        };
+        self.ops.push(op);
+    }
+
+    fn fold_int(&mut self, value: i64) {
+        self.ops.push(Op::LInt(value));
+    }
 }
@@ -1,4 +1,12 @@
+use std::iter::Peekable;
+use std::slice::Iter;
+
 use super::op::Op;
+use crate::expr::BinaryOp;
+use crate::expr::UnaryOp;
+use crate::int_mode::IntMode;
+use crate::int_mode::Value;
+use crate::trap::Trap;
 
 pub fn eval(ops: &[Op]) -> i64 {
     let mut stack = Vec::with_capacity(128);
@@ -35,3 +43,36 @@ pub fn eval(ops: &[Op]) -> i64 {
 
     stack.pop().unwrap()
 }
+
+/// Same as `eval`, but with the integer type and overflow behavior of `mode`.
+pub fn eval_with(ops: &[Op], mode: IntMode) -> Result<Value, Trap> {
+    let mut stack: Vec<Value> = Vec::with_capacity(128);
+
+    let mut ops = ops.iter().peekable();
+    while let Some(op) = ops.next() {
+        let value = match *op {
+            Op::LInt(value) => literal(mode, value, &mut ops)?,
+            Op::BAdd | Op::BSub | Op::BMul | Op::BDiv => {
+                let right = stack.pop().unwrap();
+                let left = stack.pop().unwrap();
+                let op = match op {
+                    Op::BAdd => BinaryOp::Add,
+                    Op::BSub => BinaryOp::Sub,
+                    Op::BMul => BinaryOp::Mul,
+                    _ => BinaryOp::Div,
+                };
+                mode.binary(op, left, right)?
+            }
+            Op::UMinus => mode.unary(UnaryOp::Minus, stack.pop().unwrap())?,
+        };
+        stack.push(value);
+    }
+
+    Ok(stack.pop().unwrap())
+}
+
+/// Converts a literal, along with a negation right after it, see `IntMode::literal`.
+fn literal(mode: IntMode, value: i64, ops: &mut Peekable<Iter<Op>>) -> Result<Value, Trap> {
+    let negated = ops.next_if(|op| matches!(op, Op::UMinus)).is_some();
+    mode.literal(value, negated)
+}
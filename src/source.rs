@@ -0,0 +1,109 @@
+use crate::span::Span;
+
+/// Index of a file in its `SourceDb`.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub struct FileId(u32);
+
+pub struct SourceFile {
+    id: FileId,
+    name: String,
+    text: String,
+    /// Byte offset of the start of every line.
+    line_starts: Vec<usize>,
+}
+
+impl SourceFile {
+    fn new(id: FileId, name: String, text: String) -> Self {
+        let line_starts = std::iter::once(0)
+            .chain(text.match_indices('\n').map(|(i, _)| i + 1))
+            .collect();
+        Self {
+            id,
+            name,
+            text,
+            line_starts,
+        }
+    }
+
+    pub fn id(&self) -> FileId {
+        self.id
+    }
+
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    pub fn text(&self) -> &str {
+        &self.text
+    }
+
+    /// Zero-based index of the line containing `offset`.
+    pub fn line_index(&self, offset: usize) -> usize {
+        match self.line_starts.binary_search(&offset) {
+            Ok(line) => line,
+            Err(next) => next - 1,
+        }
+    }
+
+    /// Byte range of the `line`th line, without the line terminator.
+    pub fn line_range(&self, line: usize) -> std::ops::Range<usize> {
+        let start = self.line_starts[line];
+        let end = match self.line_starts.get(line + 1) {
+            Some(next) => next - 1,
+            None => self.text.len(),
+        };
+        start..end
+    }
+}
+
+/// Owns the text of every file, so that spans and errors
+/// can refer to it by `FileId` instead of holding a copy.
+#[derive(Default)]
+pub struct SourceDb {
+    files: Vec<SourceFile>,
+}
+
+impl SourceDb {
+    pub fn add(&mut self, name: impl Into<String>, text: impl Into<String>) -> FileId {
+        let id = FileId(self.files.len() as u32);
+        self.files
+            .push(SourceFile::new(id, name.into(), text.into()));
+        id
+    }
+
+    pub fn get(&self, id: FileId) -> &SourceFile {
+        &self.files[id.0 as usize]
+    }
+
+    /// The line containing the start of `span`, with its location and the span underlined.
+    pub fn snippet(&self, span: Span) -> String {
+        use core::fmt::Write;
+
+        let file = self.get(span.file);
+        let line = file.line_index(span.start);
+        let line_range = file.line_range(line);
+        let cursor_pos = span.start - line_range.start;
+        let cursor_len = span.end.min(line_range.end).saturating_sub(span.start);
+
+        let mut out = String::new();
+        writeln!(
+            &mut out,
+            "  --> {}:{}:{}",
+            file.name(),
+            line + 1,
+            cursor_pos + 1
+        )
+        .unwrap();
+        writeln!(&mut out, "  {}", &file.text()[line_range]).unwrap();
+        // not `{:w$}`, which panics once the width passes `u16::MAX`
+        writeln!(
+            &mut out,
+            "  {}{}",
+            " ".repeat(cursor_pos),
+            "^".repeat(cursor_len.max(1))
+        )
+        .unwrap();
+
+        out
+    }
+}
@@ -1,17 +1,22 @@
 use std::ops::Index;
 use std::ops::Range;
 
-#[derive(Debug, Clone, Copy)]
+use crate::source::FileId;
+
+/// `Span::default()` is empty, for errors without a location in the source.
+#[derive(Debug, Clone, Copy, Default)]
 pub struct Span {
+    pub file: FileId,
     pub start: usize,
     pub end: usize,
 }
 
-impl From<Range<usize>> for Span {
-    fn from(value: Range<usize>) -> Self {
+impl Span {
+    pub fn new(file: FileId, range: Range<usize>) -> Self {
         Span {
-            start: value.start,
-            end: value.end,
+            file,
+            start: range.start,
+            end: range.end,
         }
     }
 }
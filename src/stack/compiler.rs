@@ -1,46 +1,84 @@
 use super::op::Op;
+use crate::arena::Ast;
 use crate::expr::BinaryOp;
 use crate::expr::Expr;
+use crate::expr::Folder;
 use crate::expr::UnaryOp;
 
 pub type ConstPool = Vec<i64>;
 pub type Bytecode = Vec<Op>;
+/// Node each instruction was compiled from, by its position in post-order,
+/// the order `Expr::fold` visits nodes in.
+pub type Origins = Vec<usize>;
 
 const MIN_INLINE_INT: i64 = i16::MIN as i64;
 const MAX_INLINE_INT: i64 = i16::MAX as i64;
 
 pub fn compile(expr: &Expr) -> (Bytecode, ConstPool) {
-    fn emit(expr: &Expr, ops: &mut Bytecode, pool: &mut ConstPool) {
-        match expr {
-            Expr::Binary(expr) => {
-                emit(&expr.left, ops, pool);
-                emit(&expr.right, ops, pool);
-                match expr.op {
-                    BinaryOp::Add => ops.push(Op::BAdd),
-                    BinaryOp::Sub => ops.push(Op::BSub),
-                    BinaryOp::Mul => ops.push(Op::BMul),
-                    BinaryOp::Div => ops.push(Op::BDiv),
-                }
-            }
-            Expr::Unary(expr) => {
-                emit(&expr.right, ops, pool);
-                match expr.op {
-                    UnaryOp::Plus => {}
-                    UnaryOp::Minus => ops.push(Op::UMinus),
-                }
-            }
-            Expr::Int(value) => match value {
-                MIN_INLINE_INT..=MAX_INLINE_INT => ops.push(Op::LInt(*value as i16)),
-                _ => {
-                    let i = pool.len() as u16;
-                    pool.push(*value);
-                    ops.push(Op::LConst(i));
-                }
-            },
+    let mut emitter = Emitter::default();
+    expr.fold(&mut emitter);
+    (emitter.ops, emitter.pool)
+}
+
+/// Same as `compile`, also returning where each instruction came from.
+pub fn compile_with_origins(expr: &Expr) -> (Bytecode, ConstPool, Origins) {
+    let mut emitter = Emitter::default();
+    expr.fold(&mut emitter);
+    (emitter.ops, emitter.pool, emitter.origins)
+}
+
+pub fn compile_ast(ast: &Ast) -> (Bytecode, ConstPool) {
+    let mut emitter = Emitter::default();
+    ast.fold(&mut emitter);
+    (emitter.ops, emitter.pool)
+}
+
+#[derive(Default)]
+struct Emitter {
+    ops: Bytecode,
+    pool: ConstPool,
+    origins: Origins,
+    /// Nodes folded so far, including the current one.
+    nodes: usize,
+}
+
+impl Emitter {
+    fn push(&mut self, op: Op) {
+        self.ops.push(op);
+        self.origins.push(self.nodes - 1);
+    }
+}
+
+impl Folder for Emitter {
+    type Output = ();
+
+    fn fold_binary(&mut self, op: BinaryOp, _: (), _: ()) {
+        self.nodes += 1;
+        match op {
+            BinaryOp::Add => self.push(Op::BAdd),
+            BinaryOp::Sub => self.push(Op::BSub),
+            BinaryOp::Mul => self.push(Op::BMul),
+            BinaryOp::Div => self.push(Op::BDiv),
+        }
+    }
+
+    fn fold_unary(&mut self, op: UnaryOp, _: ()) {
+        self.nodes += 1;
+        match op {
+            UnaryOp::Plus => {}
+            UnaryOp::Minus => self.push(Op::UMinus),
         }
     }
 
-    let (mut ops, mut pool) = (Vec::new(), Vec::new());
-    emit(expr, &mut ops, &mut pool);
-    (ops, pool)
+    fn fold_int(&mut self, value: i64) {
+        self.nodes += 1;
+        match value {
+            MIN_INLINE_INT..=MAX_INLINE_INT => self.push(Op::LInt(value as i16)),
+            _ => {
+                let i = self.pool.len() as u16;
+                self.pool.push(value);
+                self.push(Op::LConst(i));
+            }
+        }
+    }
 }
@@ -1,3 +1,4 @@
 pub mod compiler;
 pub mod op;
+pub mod peephole;
 pub mod vm;
@@ -1,4 +1,4 @@
-#[derive(Debug)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 #[repr(u8)]
 pub enum Op {
     LInt(i16),
@@ -8,6 +8,44 @@ pub enum Op {
     BMul,
     BDiv,
     UMinus,
+
+    // superinstructions, only emitted by `peephole::optimize`
+    AddImm(i16),
+    SubImm(i16),
+    MulImm(i16),
+    DivImm(i16),
+}
+
+impl Op {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Op::LInt(_) => "LInt",
+            Op::LConst(_) => "LConst",
+            Op::BAdd => "BAdd",
+            Op::BSub => "BSub",
+            Op::BMul => "BMul",
+            Op::BDiv => "BDiv",
+            Op::UMinus => "UMinus",
+            Op::AddImm(_) => "AddImm",
+            Op::SubImm(_) => "SubImm",
+            Op::MulImm(_) => "MulImm",
+            Op::DivImm(_) => "DivImm",
+        }
+    }
+}
+
+impl std::fmt::Display for Op {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Op::LConst(index) => write!(f, "{} #{index}", self.name()),
+            Op::LInt(value)
+            | Op::AddImm(value)
+            | Op::SubImm(value)
+            | Op::MulImm(value)
+            | Op::DivImm(value) => write!(f, "{} {value}", self.name()),
+            _ => f.write_str(self.name()),
+        }
+    }
 }
 
 const _: () = {
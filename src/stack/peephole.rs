@@ -0,0 +1,43 @@
+use super::compiler::Bytecode;
+use super::op::Op;
+
+/// Fuses common instruction sequences emitted by `compiler::compile`.
+///
+/// Rewrites are applied against the already optimized output, so they cascade,
+/// e.g. `LInt(1); UMinus; UMinus` becomes `LInt(-1); UMinus` and then `LInt(1)`.
+pub fn optimize(ops: &Bytecode) -> Bytecode {
+    rewrite(ops, true)
+}
+
+/// Same as `optimize`, but only fuses literal operands, for `vm::eval_rational` and
+/// `vm::eval_with`. Removing negations would skip their overflow checks,
+/// e.g. `--x` traps when `x` is the minimum of a signed type.
+pub fn optimize_checked(ops: &Bytecode) -> Bytecode {
+    rewrite(ops, false)
+}
+
+fn rewrite(ops: &Bytecode, negations: bool) -> Bytecode {
+    let mut out = Vec::with_capacity(ops.len());
+
+    for op in ops {
+        match (out.last().copied(), *op) {
+            (Some(Op::LInt(value)), Op::BAdd) => replace_last(&mut out, Op::AddImm(value)),
+            (Some(Op::LInt(value)), Op::BSub) => replace_last(&mut out, Op::SubImm(value)),
+            (Some(Op::LInt(value)), Op::BMul) => replace_last(&mut out, Op::MulImm(value)),
+            (Some(Op::LInt(value)), Op::BDiv) => replace_last(&mut out, Op::DivImm(value)),
+            (Some(Op::LInt(value)), Op::UMinus) if negations && value != i16::MIN => {
+                replace_last(&mut out, Op::LInt(-value))
+            }
+            (Some(Op::UMinus), Op::UMinus) if negations => {
+                out.pop();
+            }
+            (_, op) => out.push(op),
+        }
+    }
+
+    out
+}
+
+fn replace_last(ops: &mut Bytecode, op: Op) {
+    *ops.last_mut().unwrap() = op;
+}
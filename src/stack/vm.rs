@@ -1,14 +1,33 @@
+use std::iter::Peekable;
+use std::slice::Iter;
+
 use super::compiler::Bytecode;
 use super::compiler::ConstPool;
 use super::op::Op;
+use crate::expr::BinaryOp;
+use crate::expr::UnaryOp;
+use crate::int_mode::IntMode;
+use crate::int_mode::Value;
+use crate::rational::Rational;
+use crate::stats::Observer;
+use crate::trap::Trap;
 
 pub fn eval(ops: &Bytecode, pool: &ConstPool) -> i64 {
+    eval_observed(ops, pool, &mut ())
+}
+
+/// Same as `eval`, reporting every step to `observer`.
+pub fn eval_observed<O: Observer>(ops: &Bytecode, pool: &ConstPool, observer: &mut O) -> i64 {
     let mut stack = Vec::with_capacity(128);
 
     for op in ops {
+        observer.op(op.name());
         match op {
             Op::LInt(value) => stack.push(*value as i64),
-            Op::LConst(index) => stack.push(pool[*index as usize]),
+            Op::LConst(index) => {
+                observer.const_load();
+                stack.push(pool[*index as usize]);
+            }
             Op::BAdd => {
                 let right = stack.pop().unwrap();
                 let left = stack.pop().unwrap();
@@ -33,8 +52,103 @@ pub fn eval(ops: &Bytecode, pool: &ConstPool) -> i64 {
                 let right = stack.pop().unwrap();
                 stack.push(-right);
             }
+            Op::AddImm(value) => *stack.last_mut().unwrap() += *value as i64,
+            Op::SubImm(value) => *stack.last_mut().unwrap() -= *value as i64,
+            Op::MulImm(value) => *stack.last_mut().unwrap() *= *value as i64,
+            Op::DivImm(value) => *stack.last_mut().unwrap() /= *value as i64,
         }
+        observer.depth(stack.len());
     }
 
     stack.pop().unwrap()
 }
+
+/// Same as `eval`, but with exact fractions instead of truncating division.
+///
+/// Takes bytecode straight from the compiler or from `peephole::optimize_checked`.
+pub fn eval_rational(ops: &Bytecode, pool: &ConstPool) -> Result<Rational, Trap> {
+    let mut stack: Vec<Rational> = Vec::with_capacity(128);
+
+    for op in ops {
+        let value = match *op {
+            Op::LInt(value) => Rational::from_int(value as i64),
+            Op::LConst(index) => Rational::from_int(pool[index as usize]),
+            Op::BAdd | Op::BSub | Op::BMul | Op::BDiv => {
+                let right = stack.pop().unwrap();
+                let left = stack.pop().unwrap();
+                match op {
+                    Op::BAdd => left.checked_add(right)?,
+                    Op::BSub => left.checked_sub(right)?,
+                    Op::BMul => left.checked_mul(right)?,
+                    _ => left.checked_div(right)?,
+                }
+            }
+            Op::UMinus => stack.pop().unwrap().checked_neg()?,
+            Op::AddImm(value) => {
+                let left = stack.pop().unwrap();
+                left.checked_add(Rational::from_int(value as i64))?
+            }
+            Op::SubImm(value) => {
+                let left = stack.pop().unwrap();
+                left.checked_sub(Rational::from_int(value as i64))?
+            }
+            Op::MulImm(value) => {
+                let left = stack.pop().unwrap();
+                left.checked_mul(Rational::from_int(value as i64))?
+            }
+            Op::DivImm(value) => {
+                let left = stack.pop().unwrap();
+                left.checked_div(Rational::from_int(value as i64))?
+            }
+        };
+        stack.push(value);
+    }
+
+    Ok(stack.pop().unwrap())
+}
+
+/// Same as `eval`, but with the integer type and overflow behavior of `mode`.
+///
+/// Takes bytecode straight from the compiler or from `peephole::optimize_checked`.
+pub fn eval_with(ops: &Bytecode, pool: &ConstPool, mode: IntMode) -> Result<Value, Trap> {
+    let mut stack: Vec<Value> = Vec::with_capacity(128);
+
+    let mut ops = ops.iter().peekable();
+    while let Some(op) = ops.next() {
+        let value = match *op {
+            Op::LInt(value) => literal(mode, value as i64, &mut ops)?,
+            Op::LConst(index) => literal(mode, pool[index as usize], &mut ops)?,
+            Op::BAdd | Op::BSub | Op::BMul | Op::BDiv => {
+                let right = stack.pop().unwrap();
+                let left = stack.pop().unwrap();
+                let op = match op {
+                    Op::BAdd => BinaryOp::Add,
+                    Op::BSub => BinaryOp::Sub,
+                    Op::BMul => BinaryOp::Mul,
+                    _ => BinaryOp::Div,
+                };
+                mode.binary(op, left, right)?
+            }
+            Op::UMinus => mode.unary(UnaryOp::Minus, stack.pop().unwrap())?,
+            Op::AddImm(value) | Op::SubImm(value) | Op::MulImm(value) | Op::DivImm(value) => {
+                let left = stack.pop().unwrap();
+                let op = match op {
+                    Op::AddImm(_) => BinaryOp::Add,
+                    Op::SubImm(_) => BinaryOp::Sub,
+                    Op::MulImm(_) => BinaryOp::Mul,
+                    _ => BinaryOp::Div,
+                };
+                mode.binary(op, left, mode.int(value as i64)?)?
+            }
+        };
+        stack.push(value);
+    }
+
+    Ok(stack.pop().unwrap())
+}
+
+/// Converts a literal, along with a negation right after it, see `IntMode::literal`.
+fn literal(mode: IntMode, value: i64, ops: &mut Peekable<Iter<Op>>) -> Result<Value, Trap> {
+    let negated = ops.next_if(|op| matches!(op, Op::UMinus)).is_some();
+    mode.literal(value, negated)
+}
@@ -0,0 +1,66 @@
+use std::collections::BTreeMap;
+
+/// Receives events from an instrumented VM, see `stack::vm::eval_observed`.
+///
+/// `()` ignores them, so VMs instantiated with it run as fast as without instrumentation.
+pub trait Observer {
+    /// An instruction is about to run.
+    fn op(&mut self, name: &'static str);
+    /// Number of stack slots or registers in use after an instruction.
+    fn depth(&mut self, depth: usize);
+    /// A value was read from the constant pool.
+    fn const_load(&mut self);
+}
+
+impl Observer for () {
+    #[inline(always)]
+    fn op(&mut self, _: &'static str) {}
+
+    #[inline(always)]
+    fn depth(&mut self, _: usize) {}
+
+    #[inline(always)]
+    fn const_load(&mut self) {}
+}
+
+/// Execution statistics of a single VM run.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct Stats {
+    /// Executed instructions by opcode.
+    pub ops: BTreeMap<&'static str, u64>,
+    pub max_depth: usize,
+    pub const_loads: u64,
+}
+
+impl Stats {
+    pub fn instructions(&self) -> u64 {
+        self.ops.values().sum()
+    }
+}
+
+impl Observer for Stats {
+    fn op(&mut self, name: &'static str) {
+        *self.ops.entry(name).or_default() += 1;
+    }
+
+    fn depth(&mut self, depth: usize) {
+        self.max_depth = self.max_depth.max(depth);
+    }
+
+    fn const_load(&mut self) {
+        self.const_loads += 1;
+    }
+}
+
+impl std::fmt::Display for Stats {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "instructions: {}", self.instructions())?;
+        let mut ops: Vec<_> = self.ops.iter().collect();
+        ops.sort_by(|a, b| b.1.cmp(a.1).then(a.0.cmp(b.0)));
+        for (name, count) in ops {
+            writeln!(f, "  {name:<8} {count}")?;
+        }
+        writeln!(f, "max stack depth: {}", self.max_depth)?;
+        write!(f, "constant pool loads: {}", self.const_loads)
+    }
+}
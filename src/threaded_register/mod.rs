@@ -0,0 +1,4 @@
+pub use super::register::compiler;
+pub use super::register::op;
+
+pub mod vm;
@@ -0,0 +1,256 @@
+use super::compiler::Bytecode;
+use super::compiler::ConstPool;
+use super::compiler::StackSize;
+use super::op::Op;
+use crate::expr::BinaryOp;
+use crate::expr::UnaryOp;
+use crate::int_mode::IntMode;
+use crate::int_mode::Value;
+use crate::trap::Trap;
+
+pub type Code = Vec<Instr>;
+
+type Handler = fn(&mut [i64], &ConstPool, &Instr);
+
+/// A decoded instruction paired with the handler which executes it.
+///
+/// `arg` holds whatever the second operand of the original op was:
+/// a register, an immediate, or a constant pool index.
+pub struct Instr {
+    handler: Handler,
+    dst: u8,
+    lhs: u8,
+    arg: u16,
+}
+
+pub fn thread(ops: &Bytecode) -> Code {
+    fn instr(handler: Handler, dst: u8, lhs: u8, arg: u16) -> Instr {
+        Instr {
+            handler,
+            dst,
+            lhs,
+            arg,
+        }
+    }
+
+    ops.iter()
+        .map(|op| match op {
+            Op::LInt(n) => instr(l_int, n.dst, 0, n.val as u16),
+            Op::LConst(n) => instr(l_const, n.dst, 0, n.idx),
+            Op::BAdd(n) => instr(b_add, n.dst, n.lhs, n.rhs as u16),
+            Op::BSub(n) => instr(b_sub, n.dst, n.lhs, n.rhs as u16),
+            Op::BMul(n) => instr(b_mul, n.dst, n.lhs, n.rhs as u16),
+            Op::BDiv(n) => instr(b_div, n.dst, n.lhs, n.rhs as u16),
+            Op::UMinus(n) => instr(u_minus, n.dst, n.rhs, 0),
+            Op::BAddI(n) => instr(b_add_i, n.dst, n.lhs, n.imm as u16),
+            Op::BSubI(n) => instr(b_sub_i, n.dst, n.lhs, n.imm as u16),
+            Op::BMulI(n) => instr(b_mul_i, n.dst, n.lhs, n.imm as u16),
+            Op::BDivI(n) => instr(b_div_i, n.dst, n.lhs, n.imm as u16),
+            Op::BAddK(n) => instr(b_add_k, n.dst, n.lhs, n.kidx as u16),
+            Op::BSubK(n) => instr(b_sub_k, n.dst, n.lhs, n.kidx as u16),
+            Op::BMulK(n) => instr(b_mul_k, n.dst, n.lhs, n.kidx as u16),
+            Op::BDivK(n) => instr(b_div_k, n.dst, n.lhs, n.kidx as u16),
+        })
+        .collect()
+}
+
+pub fn eval(code: &Code, pool: &ConstPool, stack_size: StackSize) -> i64 {
+    let mut stack = vec![0i64; stack_size];
+
+    for instr in code {
+        (instr.handler)(&mut stack, pool, instr);
+    }
+
+    stack[0]
+}
+
+fn l_int(stack: &mut [i64], _: &ConstPool, i: &Instr) {
+    stack[i.dst as usize] = i.arg as i16 as i64;
+}
+
+fn l_const(stack: &mut [i64], pool: &ConstPool, i: &Instr) {
+    stack[i.dst as usize] = pool[i.arg as usize];
+}
+
+fn u_minus(stack: &mut [i64], _: &ConstPool, i: &Instr) {
+    stack[i.dst as usize] = -stack[i.lhs as usize];
+}
+
+macro_rules! binary_handlers {
+    ($($op:tt => $reg:ident, $imm:ident, $konst:ident;)*) => {
+        $(
+            fn $reg(stack: &mut [i64], _: &ConstPool, i: &Instr) {
+                stack[i.dst as usize] = stack[i.lhs as usize] $op stack[i.arg as usize];
+            }
+
+            fn $imm(stack: &mut [i64], _: &ConstPool, i: &Instr) {
+                stack[i.dst as usize] = stack[i.lhs as usize] $op (i.arg as i8 as i64);
+            }
+
+            fn $konst(stack: &mut [i64], pool: &ConstPool, i: &Instr) {
+                stack[i.dst as usize] = stack[i.lhs as usize] $op pool[i.arg as usize];
+            }
+        )*
+    };
+}
+
+binary_handlers! {
+    + => b_add, b_add_i, b_add_k;
+    - => b_sub, b_sub_i, b_sub_k;
+    * => b_mul, b_mul_i, b_mul_k;
+    / => b_div, b_div_i, b_div_k;
+}
+
+pub type CodeWith = Vec<InstrWith>;
+
+type HandlerWith = fn(&mut [Value], &ConstPool, IntMode, &InstrWith) -> Result<(), Trap>;
+
+/// Same as `Instr`, for `eval_with`.
+pub struct InstrWith {
+    handler: HandlerWith,
+    dst: u8,
+    lhs: u8,
+    arg: u16,
+}
+
+/// Same as `thread`, for `eval_with`.
+///
+/// A literal and a negation of it right after it become one instruction,
+/// since the minus is part of the literal, see `IntMode::literal`.
+pub fn thread_with(ops: &Bytecode) -> CodeWith {
+    fn instr(handler: HandlerWith, dst: u8, lhs: u8, arg: u16) -> InstrWith {
+        InstrWith {
+            handler,
+            dst,
+            lhs,
+            arg,
+        }
+    }
+
+    let mut code = Vec::with_capacity(ops.len());
+    let mut ops = ops.iter().peekable();
+    while let Some(op) = ops.next() {
+        let negation = |dst: u8| move |op: &&Op| matches!(op, Op::UMinus(n) if n.rhs == dst);
+        code.push(match op {
+            Op::LInt(n) => match ops.next_if(negation(n.dst)) {
+                Some(neg) => instr(l_neg_int_with, neg.dst(), 0, n.val as u16),
+                None => instr(l_int_with, n.dst, 0, n.val as u16),
+            },
+            Op::LConst(n) => match ops.next_if(negation(n.dst)) {
+                Some(neg) => instr(l_neg_const_with, neg.dst(), 0, n.idx),
+                None => instr(l_const_with, n.dst, 0, n.idx),
+            },
+            Op::BAdd(n) => instr(b_add_with, n.dst, n.lhs, n.rhs as u16),
+            Op::BSub(n) => instr(b_sub_with, n.dst, n.lhs, n.rhs as u16),
+            Op::BMul(n) => instr(b_mul_with, n.dst, n.lhs, n.rhs as u16),
+            Op::BDiv(n) => instr(b_div_with, n.dst, n.lhs, n.rhs as u16),
+            Op::UMinus(n) => instr(u_minus_with, n.dst, n.rhs, 0),
+            Op::BAddI(n) => instr(b_add_i_with, n.dst, n.lhs, n.imm as u16),
+            Op::BSubI(n) => instr(b_sub_i_with, n.dst, n.lhs, n.imm as u16),
+            Op::BMulI(n) => instr(b_mul_i_with, n.dst, n.lhs, n.imm as u16),
+            Op::BDivI(n) => instr(b_div_i_with, n.dst, n.lhs, n.imm as u16),
+            Op::BAddK(n) => instr(b_add_k_with, n.dst, n.lhs, n.kidx as u16),
+            Op::BSubK(n) => instr(b_sub_k_with, n.dst, n.lhs, n.kidx as u16),
+            Op::BMulK(n) => instr(b_mul_k_with, n.dst, n.lhs, n.kidx as u16),
+            Op::BDivK(n) => instr(b_div_k_with, n.dst, n.lhs, n.kidx as u16),
+        });
+    }
+    code
+}
+
+/// Same as `eval`, but with the integer type and overflow behavior of `mode`.
+pub fn eval_with(
+    code: &CodeWith,
+    pool: &ConstPool,
+    stack_size: StackSize,
+    mode: IntMode,
+) -> Result<Value, Trap> {
+    let mut stack = vec![mode.int(0)?; stack_size];
+
+    for instr in code {
+        (instr.handler)(&mut stack, pool, mode, instr)?;
+    }
+
+    Ok(stack[0])
+}
+
+fn l_int_with(
+    stack: &mut [Value],
+    _: &ConstPool,
+    mode: IntMode,
+    i: &InstrWith,
+) -> Result<(), Trap> {
+    stack[i.dst as usize] = mode.literal(i.arg as i16 as i64, false)?;
+    Ok(())
+}
+
+fn l_neg_int_with(
+    stack: &mut [Value],
+    _: &ConstPool,
+    mode: IntMode,
+    i: &InstrWith,
+) -> Result<(), Trap> {
+    stack[i.dst as usize] = mode.literal(i.arg as i16 as i64, true)?;
+    Ok(())
+}
+
+fn l_const_with(
+    stack: &mut [Value],
+    pool: &ConstPool,
+    mode: IntMode,
+    i: &InstrWith,
+) -> Result<(), Trap> {
+    stack[i.dst as usize] = mode.literal(pool[i.arg as usize], false)?;
+    Ok(())
+}
+
+fn l_neg_const_with(
+    stack: &mut [Value],
+    pool: &ConstPool,
+    mode: IntMode,
+    i: &InstrWith,
+) -> Result<(), Trap> {
+    stack[i.dst as usize] = mode.literal(pool[i.arg as usize], true)?;
+    Ok(())
+}
+
+fn u_minus_with(
+    stack: &mut [Value],
+    _: &ConstPool,
+    mode: IntMode,
+    i: &InstrWith,
+) -> Result<(), Trap> {
+    stack[i.dst as usize] = mode.unary(UnaryOp::Minus, stack[i.lhs as usize])?;
+    Ok(())
+}
+
+macro_rules! binary_handlers_with {
+    ($($op:ident => $reg:ident, $imm:ident, $konst:ident;)*) => {
+        $(
+            fn $reg(stack: &mut [Value], _: &ConstPool, mode: IntMode, i: &InstrWith) -> Result<(), Trap> {
+                let right = stack[i.arg as usize];
+                stack[i.dst as usize] = mode.binary(BinaryOp::$op, stack[i.lhs as usize], right)?;
+                Ok(())
+            }
+
+            fn $imm(stack: &mut [Value], _: &ConstPool, mode: IntMode, i: &InstrWith) -> Result<(), Trap> {
+                let right = mode.int(i.arg as i8 as i64)?;
+                stack[i.dst as usize] = mode.binary(BinaryOp::$op, stack[i.lhs as usize], right)?;
+                Ok(())
+            }
+
+            fn $konst(stack: &mut [Value], pool: &ConstPool, mode: IntMode, i: &InstrWith) -> Result<(), Trap> {
+                let right = mode.int(pool[i.arg as usize])?;
+                stack[i.dst as usize] = mode.binary(BinaryOp::$op, stack[i.lhs as usize], right)?;
+                Ok(())
+            }
+        )*
+    };
+}
+
+binary_handlers_with! {
+    Add => b_add_with, b_add_i_with, b_add_k_with;
+    Sub => b_sub_with, b_sub_i_with, b_sub_k_with;
+    Mul => b_mul_with, b_mul_i_with, b_mul_k_with;
+    Div => b_div_with, b_div_i_with, b_div_k_with;
+}
@@ -1,3 +1,4 @@
+use crate::source::FileId;
 use crate::span::Span;
 
 #[derive(Clone, Copy)]
@@ -11,13 +12,10 @@ impl Token {
         Self { kind, span }
     }
 
-    pub fn eof(at: usize) -> Self {
+    pub fn eof(file: FileId, at: usize) -> Self {
         Self {
             kind: TokenKind::Eof,
-            span: Span {
-                start: at,
-                end: at + 1,
-            },
+            span: Span::new(file, at..at + 1),
         }
     }
 
@@ -27,7 +25,8 @@ impl Token {
 }
 
 #[derive(Clone, Copy, logos::Logos, PartialEq, Eq)]
-#[logos(skip r"[\s\n]+")]
+#[logos(skip r"[^\S\n]+")]
+#[logos(skip r"(#|//)[^\n]*")]
 pub enum TokenKind {
     #[token("+")]
     Plus,
@@ -37,12 +36,26 @@ pub enum TokenKind {
     Star,
     #[token("/")]
     Slash,
+    #[token("^")]
+    Caret,
     #[token("(")]
     ParenL,
     #[token(")")]
     ParenR,
-    #[regex(r"\d+")]
+    #[token(";")]
+    Semi,
+    #[token("\n")]
+    Newline,
+    /// Also matches malformed literals like `0x` or `0b12`,
+    /// which are rejected by the parser with a precise error.
+    #[regex(r"[0-9][0-9_]*|0[xXoObB][0-9a-zA-Z_]*")]
     Int,
+    #[regex(r"[a-zA-Z_][a-zA-Z0-9_]*")]
+    Ident,
+    #[token("in")]
+    In,
+    #[token("to")]
+    To,
 
     Eof,
 }
@@ -55,9 +68,15 @@ impl TokenKind {
             Minus => "-",
             Star => "*",
             Slash => "/",
+            Caret => "^",
             ParenL => "(",
             ParenR => ")",
+            Semi => ";",
+            Newline => "newline",
             Int => "int",
+            Ident => "identifier",
+            In => "in",
+            To => "to",
             Eof => "eof",
         }
     }
@@ -0,0 +1,46 @@
+/// Arithmetic errors detected by generated code.
+///
+/// Rust always checks division, but only checks overflow with debug assertions
+/// enabled, so native backends do the same to stay in sync with the interpreters.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+#[repr(u32)]
+pub enum Trap {
+    AddOverflow = 1,
+    SubOverflow,
+    MulOverflow,
+    DivOverflow,
+    NegOverflow,
+    DivByZero,
+    /// An integer literal doesn't fit the type selected by `int_mode`.
+    LiteralOverflow,
+}
+
+impl Trap {
+    /// Every trap native code can raise, `LiteralOverflow` only comes from interpreters.
+    pub const ALL: [Trap; 6] = [
+        Trap::AddOverflow,
+        Trap::SubOverflow,
+        Trap::MulOverflow,
+        Trap::DivOverflow,
+        Trap::NegOverflow,
+        Trap::DivByZero,
+    ];
+
+    pub fn from_code(code: u32) -> Option<Trap> {
+        Trap::ALL.get((code as usize).checked_sub(1)?).copied()
+    }
+}
+
+impl std::fmt::Display for Trap {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Trap::AddOverflow => f.write_str("attempt to add with overflow"),
+            Trap::SubOverflow => f.write_str("attempt to subtract with overflow"),
+            Trap::MulOverflow => f.write_str("attempt to multiply with overflow"),
+            Trap::DivOverflow => f.write_str("attempt to divide with overflow"),
+            Trap::NegOverflow => f.write_str("attempt to negate with overflow"),
+            Trap::DivByZero => f.write_str("attempt to divide by zero"),
+            Trap::LiteralOverflow => f.write_str("literal out of range for integer type"),
+        }
+    }
+}
@@ -0,0 +1,364 @@
+use crate::expr::BinaryOp;
+use crate::expr::UnaryOp;
+use crate::expr::UnitPower;
+use crate::number_format::NumberFormat;
+
+// Quantities are floating point, since most conversions aren't integral, e.g. `1 mi = 1.609344 km`.
+
+const LENGTH: usize = 0;
+const MASS: usize = 1;
+const TIME: usize = 2;
+const CURRENT: usize = 3;
+const TEMPERATURE: usize = 4;
+const AMOUNT: usize = 5;
+const LUMINOSITY: usize = 6;
+const INFORMATION: usize = 7;
+
+/// Exponents of the base dimensions, e.g. `length^1 time^-1` for a speed.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+pub struct Dimension([i8; 8]);
+
+impl Dimension {
+    const fn of(exponents: &[(usize, i8)]) -> Self {
+        let mut dimension = [0; 8];
+        let mut i = 0;
+        while i < exponents.len() {
+            dimension[exponents[i].0] = exponents[i].1;
+            i += 1;
+        }
+        Dimension(dimension)
+    }
+
+    pub fn is_dimensionless(&self) -> bool {
+        self.0.iter().all(|&exponent| exponent == 0)
+    }
+
+    /// `None` if an exponent leaves the range of `i8`, same as `mul`.
+    fn pow(self, n: i8) -> Option<Self> {
+        let mut dimension = self.0;
+        for exponent in &mut dimension {
+            *exponent = exponent.checked_mul(n)?;
+        }
+        Some(Dimension(dimension))
+    }
+
+    fn mul(self, other: Self) -> Option<Self> {
+        let mut dimension = self.0;
+        for (exponent, other) in dimension.iter_mut().zip(other.0) {
+            *exponent = exponent.checked_add(other)?;
+        }
+        Some(Dimension(dimension))
+    }
+}
+
+/// A named unit, `factor` times the SI base unit of its dimension.
+#[derive(Debug, PartialEq)]
+pub struct UnitDef {
+    pub name: &'static str,
+    pub factor: f64,
+    pub dimension: Dimension,
+}
+
+const fn def(name: &'static str, factor: f64, dimension: &[(usize, i8)]) -> UnitDef {
+    UnitDef {
+        name,
+        factor,
+        dimension: Dimension::of(dimension),
+    }
+}
+
+#[rustfmt::skip]
+static UNITS: &[UnitDef] = &[
+    def("m", 1.0, &[(LENGTH, 1)]),
+    def("km", 1e3, &[(LENGTH, 1)]),
+    def("cm", 1e-2, &[(LENGTH, 1)]),
+    def("mm", 1e-3, &[(LENGTH, 1)]),
+    def("um", 1e-6, &[(LENGTH, 1)]),
+    def("nm", 1e-9, &[(LENGTH, 1)]),
+    def("mi", 1609.344, &[(LENGTH, 1)]),
+    def("yd", 0.9144, &[(LENGTH, 1)]),
+    def("ft", 0.3048, &[(LENGTH, 1)]),
+    def("inch", 0.0254, &[(LENGTH, 1)]),
+    def("g", 1e-3, &[(MASS, 1)]),
+    def("kg", 1.0, &[(MASS, 1)]),
+    def("t", 1e3, &[(MASS, 1)]),
+    def("lb", 0.45359237, &[(MASS, 1)]),
+    def("s", 1.0, &[(TIME, 1)]),
+    def("ms", 1e-3, &[(TIME, 1)]),
+    def("us", 1e-6, &[(TIME, 1)]),
+    def("ns", 1e-9, &[(TIME, 1)]),
+    def("min", 60.0, &[(TIME, 1)]),
+    def("h", 3600.0, &[(TIME, 1)]),
+    def("day", 86400.0, &[(TIME, 1)]),
+    def("A", 1.0, &[(CURRENT, 1)]),
+    def("K", 1.0, &[(TEMPERATURE, 1)]),
+    def("mol", 1.0, &[(AMOUNT, 1)]),
+    def("cd", 1.0, &[(LUMINOSITY, 1)]),
+    def("bit", 0.125, &[(INFORMATION, 1)]),
+    def("B", 1.0, &[(INFORMATION, 1)]),
+    def("kB", 1e3, &[(INFORMATION, 1)]),
+    def("MB", 1e6, &[(INFORMATION, 1)]),
+    def("GB", 1e9, &[(INFORMATION, 1)]),
+    def("TB", 1e12, &[(INFORMATION, 1)]),
+    def("KiB", 1024.0, &[(INFORMATION, 1)]),
+    def("MiB", 1048576.0, &[(INFORMATION, 1)]),
+    def("GiB", 1073741824.0, &[(INFORMATION, 1)]),
+    def("Hz", 1.0, &[(TIME, -1)]),
+    def("N", 1.0, &[(MASS, 1), (LENGTH, 1), (TIME, -2)]),
+    def("Pa", 1.0, &[(MASS, 1), (LENGTH, -1), (TIME, -2)]),
+    def("J", 1.0, &[(MASS, 1), (LENGTH, 2), (TIME, -2)]),
+    def("W", 1.0, &[(MASS, 1), (LENGTH, 2), (TIME, -3)]),
+    def("V", 1.0, &[(MASS, 1), (LENGTH, 2), (TIME, -3), (CURRENT, -1)]),
+];
+
+pub fn lookup(name: &str) -> Option<&'static UnitDef> {
+    UNITS.iter().find(|unit| unit.name == name)
+}
+
+/// Product of named units with exponents, e.g. `kg m/s^2`.
+/// Units are kept as written rather than reduced to SI, so results read like the input.
+///
+/// Operations return `None` when an exponent of the unit or of its dimension leaves the range of `i8`.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Unit {
+    factors: Vec<(&'static UnitDef, i8)>,
+}
+
+impl Unit {
+    pub fn power(def: &'static UnitDef, exponent: i8) -> Option<Self> {
+        Unit {
+            factors: vec![(def, exponent)],
+        }
+        .checked()
+    }
+
+    /// Size of the unit in SI base units.
+    pub fn factor(&self) -> f64 {
+        self.factors
+            .iter()
+            .map(|(def, exponent)| def.factor.powi(*exponent as i32))
+            .product()
+    }
+
+    pub fn dimension(&self) -> Dimension {
+        self.checked_dimension()
+            .expect("units are only built with dimensions in range")
+    }
+
+    fn checked_dimension(&self) -> Option<Dimension> {
+        self.factors
+            .iter()
+            .try_fold(Dimension::default(), |dimension, (def, exponent)| {
+                dimension.mul(def.dimension.pow(*exponent)?)
+            })
+    }
+
+    /// Drops factors which cancelled out, and checks the dimension.
+    fn checked(mut self) -> Option<Self> {
+        self.factors.retain(|(_, exponent)| *exponent != 0);
+        self.checked_dimension()?;
+        Some(self)
+    }
+
+    pub fn mul(&self, other: &Unit) -> Option<Unit> {
+        let mut factors = self.factors.clone();
+        for &(def, exponent) in &other.factors {
+            match factors.iter_mut().find(|(d, _)| *d == def) {
+                Some((_, e)) => *e = e.checked_add(exponent)?,
+                None => factors.push((def, exponent)),
+            }
+        }
+        Unit { factors }.checked()
+    }
+
+    pub fn recip(&self) -> Option<Unit> {
+        let factors = self
+            .factors
+            .iter()
+            .map(|&(def, e)| Some((def, e.checked_neg()?)))
+            .collect::<Option<_>>()?;
+        Unit { factors }.checked()
+    }
+}
+
+impl std::fmt::Display for Unit {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        fn write_factors<'a>(
+            f: &mut std::fmt::Formatter<'_>,
+            factors: impl Iterator<Item = (&'a UnitDef, i8)>,
+        ) -> std::fmt::Result {
+            for (i, (def, exponent)) in factors.enumerate() {
+                if i > 0 {
+                    f.write_str(" ")?;
+                }
+                f.write_str(def.name)?;
+                if exponent != 1 {
+                    write!(f, "^{exponent}")?;
+                }
+            }
+            Ok(())
+        }
+
+        let numerator = self.factors.iter().filter(|(_, e)| *e > 0).copied();
+        let denominator: Vec<_> = self
+            .factors
+            .iter()
+            .filter(|(_, e)| *e < 0)
+            .map(|&(def, e)| (def, -e))
+            .collect();
+
+        if numerator.clone().next().is_some() {
+            write_factors(f, numerator)?;
+        } else {
+            f.write_str("1")?;
+        }
+        match denominator.len() {
+            0 => Ok(()),
+            1 => {
+                f.write_str("/")?;
+                write_factors(f, denominator.into_iter())
+            }
+            _ => {
+                f.write_str("/(")?;
+                write_factors(f, denominator.into_iter())?;
+                f.write_str(")")
+            }
+        }
+    }
+}
+
+/// A value in the given unit, e.g. `3.2 km`.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Quantity {
+    pub value: f64,
+    pub unit: Unit,
+}
+
+impl Quantity {
+    pub fn from_int(value: i64) -> Self {
+        Quantity {
+            value: value as f64,
+            unit: Unit::default(),
+        }
+    }
+
+    /// One of `unit`, failing if it's unknown.
+    pub fn unit(unit: &UnitPower) -> Result<Self, String> {
+        let def = lookup(&unit.name).ok_or_else(|| format!("unknown unit `{}`", unit.name))?;
+        Ok(Quantity {
+            value: 1.0,
+            unit: Unit::power(def, unit.exponent).ok_or_else(out_of_range)?,
+        })
+    }
+
+    /// Fails if the dimensions of a sum or difference don't agree,
+    /// or if the resulting unit can't be represented.
+    pub fn binary(self, op: BinaryOp, right: Quantity) -> Result<Self, String> {
+        let quantity = match op {
+            BinaryOp::Add | BinaryOp::Sub => {
+                if self.unit.dimension() != right.unit.dimension() {
+                    let verb = if op == BinaryOp::Add {
+                        "add"
+                    } else {
+                        "subtract"
+                    };
+                    return Err(format!(
+                        "cannot {verb} `{}` and `{}`",
+                        self.unit, right.unit
+                    ));
+                }
+                let right = right.value_in(&self.unit);
+                let value = match op {
+                    BinaryOp::Add => self.value + right,
+                    _ => self.value - right,
+                };
+                Quantity {
+                    value,
+                    unit: self.unit,
+                }
+            }
+            BinaryOp::Mul => Quantity {
+                value: self.value * right.value,
+                unit: self.unit.mul(&right.unit).ok_or_else(out_of_range)?,
+            },
+            BinaryOp::Div => {
+                if right.value == 0.0 {
+                    return Err("attempt to divide by zero".to_string());
+                }
+                let recip = right.unit.recip().ok_or_else(out_of_range)?;
+                Quantity {
+                    value: self.value / right.value,
+                    unit: self.unit.mul(&recip).ok_or_else(out_of_range)?,
+                }
+            }
+        };
+        Ok(quantity.simplify())
+    }
+
+    pub fn unary(self, op: UnaryOp) -> Self {
+        match op {
+            UnaryOp::Plus => self,
+            UnaryOp::Minus => Quantity {
+                value: -self.value,
+                ..self
+            },
+        }
+    }
+
+    /// `self` expressed in the unit of `target`, which must have the same dimension.
+    pub fn convert(self, target: Quantity) -> Result<Self, String> {
+        if self.unit.dimension() != target.unit.dimension() {
+            return Err(format!(
+                "cannot convert `{}` to `{}`",
+                self.unit, target.unit
+            ));
+        }
+        // `target` is usually one of its unit, unless its dimensions cancel out, e.g. `km/m`
+        Ok(Quantity {
+            value: self.value_in(&target.unit) / target.value,
+            unit: target.unit,
+        })
+    }
+
+    /// Folds the unit into the value once all dimensions cancel out, so `1 km / 1 m` is `1000`.
+    fn simplify(self) -> Self {
+        if !self.unit.factors.is_empty() && self.unit.dimension().is_dimensionless() {
+            Quantity {
+                value: self.value * self.unit.factor(),
+                unit: Unit::default(),
+            }
+        } else {
+            self
+        }
+    }
+
+    /// Value of `self` expressed in `unit`, which must have the same dimension.
+    fn value_in(&self, unit: &Unit) -> f64 {
+        self.value * self.unit.factor() / unit.factor()
+    }
+
+    /// Renders the value with `format`, followed by the unit.
+    /// Units with nothing but a denominator, like `1/s`, are written as `5/s` rather than `5 1/s`
+    /// so the output parses again.
+    pub fn format(&self, format: &NumberFormat) -> String {
+        let value = format.format_f64(self.value);
+        if self.unit.factors.is_empty() {
+            return value;
+        }
+        let unit = self.unit.to_string();
+        match unit.strip_prefix('1') {
+            Some(denominator) => format!("{value}{denominator}"),
+            None => format!("{value} {unit}"),
+        }
+    }
+}
+
+impl std::fmt::Display for Quantity {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.format(&NumberFormat::default()))
+    }
+}
+
+fn out_of_range() -> String {
+    format!("unit exponents must be between {} and {}", i8::MIN, i8::MAX)
+}
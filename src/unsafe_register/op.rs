@@ -12,7 +12,7 @@ macro_rules! newtype_enum {
     }
 
     $(
-      #[repr(packed)]
+      #[repr(C, packed)]
       $vis struct $variant {
         $(pub $field : $ty),*
       }
@@ -36,6 +36,14 @@ newtype_enum! {
     BMul { dst: u8, lhs: u8, rhs: u8 },
     BDiv { dst: u8, lhs: u8, rhs: u8 },
     UMinus { dst: u8, rhs: u8 },
+    BAddI { dst: u8, lhs: u8, imm: i8 },
+    BSubI { dst: u8, lhs: u8, imm: i8 },
+    BMulI { dst: u8, lhs: u8, imm: i8 },
+    BDivI { dst: u8, lhs: u8, imm: i8 },
+    BAddK { dst: u8, lhs: u8, kidx: u8 },
+    BSubK { dst: u8, lhs: u8, kidx: u8 },
+    BMulK { dst: u8, lhs: u8, kidx: u8 },
+    BDivK { dst: u8, lhs: u8, kidx: u8 },
   }
 }
 
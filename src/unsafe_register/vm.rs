@@ -1,6 +1,15 @@
+use std::iter::Peekable;
+use std::slice::Iter;
+
 use super::compiler::Bytecode;
 use super::compiler::ConstPool;
 use super::compiler::StackSize;
+use super::op::Op;
+use crate::expr::BinaryOp;
+use crate::expr::UnaryOp;
+use crate::int_mode::IntMode;
+use crate::int_mode::Value;
+use crate::trap::Trap;
 
 macro_rules! set {
     ($a:ident, $i:expr, $v:expr) => {
@@ -38,8 +47,105 @@ pub fn eval(ops: &Bytecode, pool: &ConstPool, stack_size: StackSize) -> i64 {
             super::op::Op::UMinus(n) => {
                 set!(stack, n.dst, -get!(stack, n.rhs))
             }
+            super::op::Op::BAddI(n) => {
+                set!(stack, n.dst, get!(stack, n.lhs) + n.imm as i64)
+            }
+            super::op::Op::BSubI(n) => {
+                set!(stack, n.dst, get!(stack, n.lhs) - n.imm as i64)
+            }
+            super::op::Op::BMulI(n) => {
+                set!(stack, n.dst, get!(stack, n.lhs) * n.imm as i64)
+            }
+            super::op::Op::BDivI(n) => {
+                set!(stack, n.dst, get!(stack, n.lhs) / n.imm as i64)
+            }
+            super::op::Op::BAddK(n) => {
+                set!(stack, n.dst, get!(stack, n.lhs) + get!(pool, n.kidx))
+            }
+            super::op::Op::BSubK(n) => {
+                set!(stack, n.dst, get!(stack, n.lhs) - get!(pool, n.kidx))
+            }
+            super::op::Op::BMulK(n) => {
+                set!(stack, n.dst, get!(stack, n.lhs) * get!(pool, n.kidx))
+            }
+            super::op::Op::BDivK(n) => {
+                set!(stack, n.dst, get!(stack, n.lhs) / get!(pool, n.kidx))
+            }
         }
     }
 
     get!(stack, 0)
 }
+
+/// Same as `eval`, but with the integer type and overflow behavior of `mode`.
+pub fn eval_with(
+    ops: &Bytecode,
+    pool: &ConstPool,
+    stack_size: StackSize,
+    mode: IntMode,
+) -> Result<Value, Trap> {
+    let mut stack = vec![mode.int(0)?; stack_size];
+
+    let mut ops = ops.iter().peekable();
+    while let Some(op) = ops.next() {
+        let binary = |op, lhs: u8, right| mode.binary(op, get!(stack, lhs), right);
+        let (dst, value) = match op {
+            Op::LInt(n) => literal(mode, n.dst, n.val as i64, &mut ops)?,
+            Op::LConst(n) => literal(mode, n.dst, get!(pool, n.idx), &mut ops)?,
+            Op::BAdd(n) => (n.dst, binary(BinaryOp::Add, n.lhs, get!(stack, n.rhs))?),
+            Op::BSub(n) => (n.dst, binary(BinaryOp::Sub, n.lhs, get!(stack, n.rhs))?),
+            Op::BMul(n) => (n.dst, binary(BinaryOp::Mul, n.lhs, get!(stack, n.rhs))?),
+            Op::BDiv(n) => (n.dst, binary(BinaryOp::Div, n.lhs, get!(stack, n.rhs))?),
+            Op::UMinus(n) => (n.dst, mode.unary(UnaryOp::Minus, get!(stack, n.rhs))?),
+            Op::BAddI(n) => (
+                n.dst,
+                binary(BinaryOp::Add, n.lhs, mode.int(n.imm as i64)?)?,
+            ),
+            Op::BSubI(n) => (
+                n.dst,
+                binary(BinaryOp::Sub, n.lhs, mode.int(n.imm as i64)?)?,
+            ),
+            Op::BMulI(n) => (
+                n.dst,
+                binary(BinaryOp::Mul, n.lhs, mode.int(n.imm as i64)?)?,
+            ),
+            Op::BDivI(n) => (
+                n.dst,
+                binary(BinaryOp::Div, n.lhs, mode.int(n.imm as i64)?)?,
+            ),
+            Op::BAddK(n) => (
+                n.dst,
+                binary(BinaryOp::Add, n.lhs, mode.int(get!(pool, n.kidx))?)?,
+            ),
+            Op::BSubK(n) => (
+                n.dst,
+                binary(BinaryOp::Sub, n.lhs, mode.int(get!(pool, n.kidx))?)?,
+            ),
+            Op::BMulK(n) => (
+                n.dst,
+                binary(BinaryOp::Mul, n.lhs, mode.int(get!(pool, n.kidx))?)?,
+            ),
+            Op::BDivK(n) => (
+                n.dst,
+                binary(BinaryOp::Div, n.lhs, mode.int(get!(pool, n.kidx))?)?,
+            ),
+        };
+        set!(stack, dst, value);
+    }
+
+    Ok(get!(stack, 0))
+}
+
+/// Converts a literal, along with a negation of it right after it, see `IntMode::literal`.
+/// Also returns the register to write it to.
+fn literal(
+    mode: IntMode,
+    dst: u8,
+    value: i64,
+    ops: &mut Peekable<Iter<Op>>,
+) -> Result<(u8, Value), Trap> {
+    match ops.next_if(|op| matches!(op, Op::UMinus(n) if n.rhs == dst)) {
+        Some(Op::UMinus(n)) => Ok((n.dst, mode.literal(value, true)?)),
+        _ => Ok((dst, mode.literal(value, false)?)),
+    }
+}
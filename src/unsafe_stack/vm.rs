@@ -33,6 +33,10 @@ pub fn eval(ops: &Bytecode, pool: &ConstPool) -> i64 {
                 let right = unsafe { stack.pop().unwrap_unchecked() };
                 stack.push(-right);
             }
+            Op::AddImm(value) => unsafe { *stack.last_mut().unwrap_unchecked() += *value as i64 },
+            Op::SubImm(value) => unsafe { *stack.last_mut().unwrap_unchecked() -= *value as i64 },
+            Op::MulImm(value) => unsafe { *stack.last_mut().unwrap_unchecked() *= *value as i64 },
+            Op::DivImm(value) => unsafe { *stack.last_mut().unwrap_unchecked() /= *value as i64 },
         }
     }
 
@@ -0,0 +1,138 @@
+use std::path::PathBuf;
+use std::process::Command;
+
+use calc::expr::Expr;
+use calc::source::SourceDb;
+
+fn parse(src: &str) -> Expr {
+    let mut db = SourceDb::default();
+    let file = db.add("<test>", src);
+    calc::parser::parse(db.get(file)).unwrap()
+}
+
+/// A fresh directory for the files of one test.
+fn dir(name: &str) -> PathBuf {
+    let dir = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join(name);
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+/// Whether `tool` can be run, so tests needing it can be skipped where it's missing.
+fn available(tool: &str) -> bool {
+    Command::new(tool)
+        .arg("--version")
+        .output()
+        .is_ok_and(|output| output.status.success())
+}
+
+/// Exit code, standard output and standard error of a program.
+fn run(mut command: Command) -> (Option<i32>, String, String) {
+    let output = command.output().unwrap();
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    (output.status.code(), stdout, stderr)
+}
+
+/// Assembles and links `source` with `as` and `ld` from `prefix`, like `aarch64-linux-gnu-`.
+fn build(source: &str, prefix: &str, name: &str) -> PathBuf {
+    let dir = dir(name);
+    let (src, obj, bin) = (dir.join("main.s"), dir.join("main.o"), dir.join("main"));
+    std::fs::write(&src, source).unwrap();
+    let status = Command::new(format!("{prefix}as"))
+        .arg("-o")
+        .arg(&obj)
+        .arg(&src)
+        .status()
+        .unwrap();
+    assert!(status.success(), "{prefix}as failed on {}", src.display());
+    let status = Command::new(format!("{prefix}ld"))
+        .arg("-o")
+        .arg(&bin)
+        .arg(&obj)
+        .status()
+        .unwrap();
+    assert!(status.success(), "{prefix}ld failed on {}", obj.display());
+    bin
+}
+
+const EXPRS: [(&str, &str); 6] = [
+    ("1 + 2 * 3", "9"),
+    ("-7 / 2", "-3"),
+    ("0", "0"),
+    ("100000 * 100000 - 9999999999", "-999989999900000"),
+    ("-9223372036854775807 - 1", "-9223372036854775808"),
+    ("9223372036854775807", "9223372036854775807"),
+];
+
+const TRAPS: [(&str, &str); 3] = [
+    ("1 / (2 - 2)", "attempt to divide by zero"),
+    ("9223372036854775807 + 1", "attempt to add with overflow"),
+    (
+        "(-9223372036854775807 - 1) / -1",
+        "attempt to divide with overflow",
+    ),
+];
+
+/// Builds and runs every expression, given how to get the source and run the binary.
+fn check(
+    name: &str,
+    prefix: &str,
+    source: fn(&Expr) -> calc::error::Result<String>,
+    runner: &[&str],
+) {
+    let command = |bin: PathBuf| {
+        let mut command = Command::new(runner.first().map_or(bin.as_os_str(), |r| r.as_ref()));
+        if !runner.is_empty() {
+            command.arg(&bin);
+        }
+        command
+    };
+    for (i, (src, expected)) in EXPRS.iter().enumerate() {
+        let expr = parse(src);
+        assert_eq!(calc::folder::fold(&expr).to_string(), *expected);
+        let bin = build(&source(&expr).unwrap(), prefix, &format!("{name}_{i}"));
+        let (code, stdout, stderr) = run(command(bin));
+        assert_eq!(code, Some(0), "`{src}`: {stderr}");
+        assert_eq!(stdout, format!("{expected}\n"), "`{src}`");
+    }
+    for (i, (src, trap)) in TRAPS.iter().enumerate() {
+        let bin = build(
+            &source(&parse(src)).unwrap(),
+            prefix,
+            &format!("{name}_trap_{i}"),
+        );
+        let (code, stdout, stderr) = run(command(bin));
+        assert_eq!(code, Some(101), "`{src}`");
+        assert_eq!(stdout, "", "`{src}`");
+        assert_eq!(stderr, format!("{trap}\n"), "`{src}`");
+    }
+}
+
+#[test]
+fn x86_64_runs() {
+    if !(cfg!(all(target_arch = "x86_64", target_os = "linux")) && available("as")) {
+        eprintln!("skipped: needs `as` and `ld` on x86-64 Linux");
+        return;
+    }
+    check("x86_64", "", calc::codegen::asm::x86_64::source, &[]);
+}
+
+#[test]
+fn aarch64_runs() {
+    let native = cfg!(all(target_arch = "aarch64", target_os = "linux"));
+    let (prefix, runner): (&str, &[&str]) = if native {
+        ("", &[])
+    } else {
+        ("aarch64-linux-gnu-", &["qemu-aarch64"])
+    };
+    if !available(&format!("{prefix}as")) || !runner.iter().all(|r| available(r)) {
+        eprintln!("skipped: needs an AArch64 `as` and `ld`, and `qemu-aarch64` on other hosts");
+        return;
+    }
+    check(
+        "aarch64",
+        prefix,
+        calc::codegen::asm::aarch64::source,
+        runner,
+    );
+}
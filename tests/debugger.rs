@@ -0,0 +1,126 @@
+use calc::debugger::Debugger;
+use calc::expr::Expr;
+use calc::source::SourceDb;
+use calc::span::Span;
+use calc::trap::Trap;
+
+fn parse(src: &str) -> (Expr, Vec<Span>) {
+    let mut db = SourceDb::default();
+    let file = db.add("<test>", src);
+    calc::parser::parse_spanned(db.get(file), 64).unwrap()
+}
+
+/// Source of every instruction, in order.
+fn sources<'a>(debugger: &Debugger, src: &'a str, len: usize) -> Vec<&'a str> {
+    (0..len)
+        .map(|index| {
+            let span = debugger.span(index);
+            &src[span.start..span.end]
+        })
+        .collect()
+}
+
+#[test]
+fn widens_spans_over_parentheses() {
+    for (src, expected) in [
+        ("(1 + 2) * 3", vec!["1", "2", "1 + 2", "3", "(1 + 2) * 3"]),
+        ("3 * (1 + 2)", vec!["3", "1", "2", "1 + 2", "3 * (1 + 2)"]),
+        ("-(4)", vec!["4", "-(4)"]),
+        (
+            "((1)) + (2 * 3)",
+            vec!["1", "2", "3", "2 * 3", "((1)) + (2 * 3)"],
+        ),
+        // parentheses in comments aren't tokens
+        ("(# (\n1) + 2", vec!["1", "2", "(# (\n1) + 2"]),
+        (
+            "(1 # )\n+ 2) * 3",
+            vec!["1", "2", "1 # )\n+ 2", "3", "(1 # )\n+ 2) * 3"],
+        ),
+    ] {
+        let (_, spans) = parse(src);
+        let texts: Vec<&str> = spans
+            .iter()
+            .map(|span| &src[span.start..span.end])
+            .collect();
+        assert_eq!(texts, expected, "`{src}`");
+    }
+}
+
+#[test]
+fn maps_instructions_to_their_source() {
+    let src = "(1 + 2) * (3 + 40000)";
+    let (expr, spans) = parse(src);
+
+    let debugger = Debugger::stack(&expr, &spans);
+    assert_eq!(
+        sources(&debugger, src, 7),
+        ["1", "2", "1 + 2", "3", "40000", "3 + 40000", src]
+    );
+
+    // operands folded into an instruction have no instruction of their own
+    let debugger = Debugger::register(&expr, &spans).unwrap();
+    assert_eq!(debugger.disassemble(3), "BAddK dst=1 lhs=1 kidx=0");
+    assert_eq!(
+        sources(&debugger, src, 5),
+        ["1", "1 + 2", "3", "3 + 40000", src]
+    );
+}
+
+#[test]
+fn trap_leaves_the_instruction_next() {
+    let src = "1 / (2 - 2)";
+    let (expr, spans) = parse(src);
+    for mut debugger in [
+        Debugger::stack(&expr, &spans),
+        Debugger::register(&expr, &spans).unwrap(),
+    ] {
+        assert_eq!(debugger.run(), Err(Trap::DivByZero));
+        let pc = debugger.pc();
+        let state = debugger.state();
+        let span = debugger.span(pc);
+        assert_eq!(&src[span.start..span.end], src);
+
+        assert_eq!(debugger.step(), Err(Trap::DivByZero));
+        assert_eq!(debugger.pc(), pc);
+        assert_eq!(debugger.state(), state);
+        assert!(!debugger.finished());
+        assert_eq!(debugger.result(), None);
+    }
+}
+
+#[test]
+fn checks_breakpoint_bounds() {
+    let (expr, spans) = parse("1 + 2 * 3");
+    let mut debugger = Debugger::stack(&expr, &spans);
+    assert_eq!(
+        debugger.toggle_breakpoint(5),
+        Err("no instruction 5, the last one is 4".to_string())
+    );
+    assert_eq!(debugger.toggle_breakpoint(4), Ok(true));
+    assert_eq!(debugger.toggle_breakpoint(4), Ok(false));
+    assert_eq!(debugger.toggle_breakpoint(0), Ok(true));
+}
+
+#[test]
+fn run_stops_at_breakpoints() {
+    let (expr, spans) = parse("1 + 2 * 3");
+    for mut debugger in [
+        Debugger::stack(&expr, &spans),
+        Debugger::register(&expr, &spans).unwrap(),
+    ] {
+        debugger.toggle_breakpoint(0).unwrap();
+        debugger.toggle_breakpoint(2).unwrap();
+        // the breakpoint on the next instruction doesn't stop it from running
+        debugger.run().unwrap();
+        assert_eq!(debugger.pc(), 2);
+        assert!(
+            debugger.listing().contains("*> 2"),
+            "{}",
+            debugger.listing()
+        );
+        debugger.run().unwrap();
+        assert!(debugger.finished());
+        // `+` binds tighter than `*`
+        assert_eq!(debugger.result(), Some(9));
+    }
+}
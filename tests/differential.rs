@@ -0,0 +1,54 @@
+use calc::expr::Binary;
+use calc::expr::BinaryOp;
+use calc::expr::Expr;
+use calc::expr::Unary;
+use calc::expr::UnaryOp;
+use proptest::prelude::*;
+
+/// Mostly small literals so division and overflow stay interesting,
+/// with the occasional large one to exercise constant pools.
+fn int() -> impl Strategy<Value = i64> {
+    prop_oneof![
+        4 => -128i64..=128,
+        1 => (i64::MIN + 1)..=i64::MAX,
+    ]
+}
+
+fn expr() -> impl Strategy<Value = Expr> {
+    let binary_op = prop_oneof![
+        Just(BinaryOp::Add),
+        Just(BinaryOp::Sub),
+        Just(BinaryOp::Mul),
+        Just(BinaryOp::Div),
+    ];
+    let unary_op = prop_oneof![Just(UnaryOp::Plus), Just(UnaryOp::Minus)];
+
+    int().prop_map(Expr::Int).prop_recursive(12, 256, 2, move |inner| {
+        prop_oneof![
+            2 => (inner.clone(), binary_op.clone(), inner.clone()).prop_map(|(left, op, right)| {
+                Expr::Binary(Box::new(Binary { left, op, right }))
+            }),
+            1 => (unary_op.clone(), inner).prop_map(|(op, right)| {
+                Expr::Unary(Box::new(Unary { op, right }))
+            }),
+        ]
+    })
+}
+
+proptest! {
+    #![proptest_config(ProptestConfig::with_cases(512))]
+
+    #[test]
+    fn backends_agree(expr in expr()) {
+        if let Err(mismatch) = calc::differential::check(&expr) {
+            panic!("{mismatch}");
+        }
+    }
+
+    #[test]
+    fn display_round_trips(expr in expr()) {
+        if let Err(mismatch) = calc::differential::check_round_trip(&expr) {
+            panic!("{mismatch}");
+        }
+    }
+}
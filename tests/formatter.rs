@@ -0,0 +1,47 @@
+use calc::expr::Expr;
+use calc::generator::Config;
+use calc::generator::Generator;
+use calc::source::SourceDb;
+
+/// Non-negative literals, since negative ones read back as a unary minus.
+fn exprs(seed: u64, count: usize) -> Vec<Expr> {
+    let config = Config {
+        nodes: 48,
+        literals: 0..=1000,
+        ..Default::default()
+    };
+    let mut generator = Generator::new(seed, config);
+    (0..count).map(|_| generator.generate()).collect()
+}
+
+#[test]
+fn wrapped_output_round_trips_as_a_program() {
+    for width in [0, 10, 30, 80] {
+        let exprs = exprs(width as u64, 50);
+        let text = exprs
+            .iter()
+            .map(|expr| calc::formatter::format(expr, width))
+            .collect::<Vec<_>>()
+            .join("\n");
+        let mut db = SourceDb::default();
+        let file = db.add("<test>", text.clone());
+        let program = match calc::parser::parse_program(db.get(file)) {
+            Ok(program) => program,
+            Err(e) => panic!("{}\n{text}", e.report(&db)),
+        };
+        assert_eq!(program.stmts, exprs, "width {width}:\n{text}");
+    }
+}
+
+#[test]
+fn breaks_after_operators() {
+    let mut db = SourceDb::default();
+    let file = db.add("<test>", "111 + 222 - 333 * 444");
+    let expr = calc::parser::parse(db.get(file)).unwrap();
+    // `*` binds the loosest, so the sum is its left operand
+    assert_eq!(calc::formatter::format(&expr, 17), "111 + 222 - 333 *\n444");
+    assert_eq!(
+        calc::formatter::format(&expr, 16),
+        "111 +\n222 -\n333 *\n444"
+    );
+}
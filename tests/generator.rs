@@ -0,0 +1,104 @@
+use calc::expr::BinaryOp;
+use calc::expr::Expr;
+use calc::expr::Folder;
+use calc::expr::UnaryOp;
+use calc::generator::Config;
+use calc::generator::Generator;
+use calc::generator::Shape;
+use proptest::prelude::*;
+
+struct Count {
+    nodes: usize,
+    divisions: usize,
+}
+
+impl Folder for Count {
+    type Output = ();
+
+    fn fold_binary(&mut self, op: BinaryOp, _: (), _: ()) {
+        self.nodes += 1;
+        self.divisions += (op == BinaryOp::Div) as usize;
+    }
+
+    fn fold_unary(&mut self, _: UnaryOp, _: ()) {
+        self.nodes += 1;
+    }
+
+    fn fold_int(&mut self, _: i64) {
+        self.nodes += 1;
+    }
+}
+
+fn count(expr: &Expr) -> Count {
+    let mut count = Count {
+        nodes: 0,
+        divisions: 0,
+    };
+    expr.fold(&mut count);
+    count
+}
+
+fn config() -> impl Strategy<Value = Config> {
+    let ops = proptest::sample::subsequence(
+        vec![BinaryOp::Add, BinaryOp::Sub, BinaryOp::Mul, BinaryOp::Div],
+        0..=4,
+    );
+    let shape = prop_oneof![
+        Just(Shape::Random),
+        Just(Shape::Balanced),
+        Just(Shape::LeftDeep),
+        Just(Shape::RightDeep),
+    ];
+    let literals = prop_oneof![Just(-128..=127), Just(i64::MIN + 1..=i64::MAX)];
+    (
+        1usize..=12,
+        1usize..=200,
+        shape,
+        ops,
+        any::<bool>(),
+        literals,
+    )
+        .prop_map(|(depth, nodes, shape, ops, unary, literals)| Config {
+            depth,
+            nodes,
+            shape,
+            ops,
+            unary,
+            literals,
+            safe: false,
+        })
+}
+
+proptest! {
+    #[test]
+    fn same_seed_same_exprs(seed: u64, config in config()) {
+        let mut a = Generator::new(seed, config.clone());
+        let mut b = Generator::new(seed, config);
+        for _ in 0..4 {
+            prop_assert_eq!(a.generate(), b.generate());
+        }
+    }
+
+    #[test]
+    fn respects_depth_and_nodes(seed: u64, config in config()) {
+        let expr = Generator::new(seed, config.clone()).generate();
+        let depth = calc::differential::depth(&expr);
+        prop_assert!(depth <= config.depth);
+        let count = count(&expr);
+        // only a full tree can't be made deeper to fit more nodes
+        prop_assert!(count.nodes == config.nodes || depth == config.depth);
+        if !config.ops.contains(&BinaryOp::Div) {
+            prop_assert_eq!(count.divisions, 0);
+        }
+    }
+
+    #[test]
+    fn safe_exprs_evaluate(seed: u64, config in config()) {
+        let config = Config { safe: true, ..config };
+        let expr = Generator::new(seed, config).generate();
+        match calc::differential::check(&expr) {
+            Ok(outcome) => prop_assert!(outcome.is_ok(), "`{}`: {:?}", expr, outcome),
+            Err(mismatch) => panic!("{mismatch}"),
+        }
+    }
+}
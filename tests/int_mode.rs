@@ -0,0 +1,279 @@
+use std::io::Write;
+use std::process::Command;
+use std::process::Stdio;
+
+use calc::expr::Expr;
+use calc::generator::Config;
+use calc::generator::Generator;
+use calc::int_mode::IntMode;
+use calc::source::SourceDb;
+use calc::trap::Trap;
+
+type Outcome = Result<String, Trap>;
+
+fn parse(src: &str) -> Expr {
+    let mut db = SourceDb::default();
+    let file = db.add("<test>", src);
+    calc::parser::parse(db.get(file)).unwrap()
+}
+
+/// Evaluates `expr` with `folder::fold_with` followed by every VM with an `eval_with`.
+fn eval_all(expr: &Expr, mode: IntMode) -> Vec<(&'static str, Outcome)> {
+    let mut outcomes = Vec::new();
+    let mut run = |name, value: Result<calc::int_mode::Value, Trap>| {
+        outcomes.push((name, value.map(|value| value.to_string())))
+    };
+
+    run("fold_with", calc::folder::fold_with(expr, mode));
+
+    let ops = calc::rpn::compiler::compile(expr);
+    run("rpn", calc::rpn::vm::eval_with(&ops, mode));
+
+    let (ops, pool) = calc::stack::compiler::compile(expr);
+    run("stack", calc::stack::vm::eval_with(&ops, &pool, mode));
+    let ops = calc::stack::peephole::optimize_checked(&ops);
+    run(
+        "stack_peephole",
+        calc::stack::vm::eval_with(&ops, &pool, mode),
+    );
+
+    let (ops, pool, stack_size) = calc::register::compiler::compile(expr).unwrap();
+    run(
+        "register",
+        calc::register::vm::eval_with(&ops, &pool, stack_size, mode),
+    );
+    let code = calc::threaded_register::vm::thread_with(&ops);
+    run(
+        "threaded_register",
+        calc::threaded_register::vm::eval_with(&code, &pool, stack_size, mode),
+    );
+
+    let (ops, pool, stack_size) = calc::unsafe_register::compiler::compile(expr).unwrap();
+    run(
+        "unsafe_register",
+        calc::unsafe_register::vm::eval_with(&ops, &pool, stack_size, mode),
+    );
+
+    outcomes
+}
+
+/// Evaluates `src` with `folder::parse_and_fold_with`, which reports traps as errors.
+fn eval_source(src: &str, mode: IntMode) -> Result<String, String> {
+    let mut db = SourceDb::default();
+    let file = db.add("<test>", src);
+    let value = calc::folder::parse_and_fold_with(db.get(file), 64, mode)
+        .and_then(|mut values| values.pop().unwrap());
+    value
+        .map(|value| value.to_string())
+        .map_err(|e| e.message().to_string())
+}
+
+#[track_caller]
+fn check(spec: &str, src: &str, expected: Result<&str, Trap>) {
+    let mode = spec.parse().unwrap();
+    let expected = expected.map(str::to_string);
+    for (name, outcome) in eval_all(&parse(src), mode) {
+        assert_eq!(outcome, expected, "`{src}` as `{spec}` with {name}");
+    }
+    let expected = expected.map_err(|trap| trap.to_string());
+    assert_eq!(eval_source(src, mode), expected, "`{src}` as `{spec}`");
+}
+
+#[test]
+fn wraps() {
+    for (spec, src, expected) in [
+        ("u8 wrapping", "255 + 1", "0"),
+        ("u8 wrapping", "0 - 1", "255"),
+        ("u8 wrapping", "-1", "255"),
+        ("i8 wrapping", "127 + 1", "-128"),
+        ("i8 wrapping", "-128 / -1", "-128"),
+        ("i8 wrapping", "--128", "-128"),
+        ("u16 wrapping", "200 * 400", "14464"),
+        ("i16 wrapping", "32767 + 1", "-32768"),
+        ("i32 wrapping", "-3000000000", "1294967296"),
+        (
+            "i64 wrapping",
+            "9223372036854775807 + 1",
+            "-9223372036854775808",
+        ),
+        ("u64 wrapping", "0 - 1", "18446744073709551615"),
+    ] {
+        check(spec, src, Ok(expected));
+    }
+}
+
+#[test]
+fn saturates() {
+    for (spec, src, expected) in [
+        ("u8 saturating", "255 + 1", "255"),
+        ("u8 saturating", "0 - 1", "0"),
+        ("u8 saturating", "-1", "0"),
+        ("i8 saturating", "127 + 1", "127"),
+        ("i8 saturating", "-128 / -1", "127"),
+        ("i8 saturating", "--128", "127"),
+        ("i8 saturating", "300", "127"),
+        ("i32 saturating", "2147483647 * 2", "2147483647"),
+        ("i32 saturating", "-3000000000", "-2147483648"),
+        ("u128 saturating", "0 - 1", "0"),
+    ] {
+        check(spec, src, Ok(expected));
+    }
+}
+
+#[test]
+fn checks() {
+    for (spec, src, expected) in [
+        ("u8 checked", "255 + 1", Err(Trap::AddOverflow)),
+        ("u8 checked", "0 - 1", Err(Trap::SubOverflow)),
+        ("u8 checked", "16 * 16", Err(Trap::MulOverflow)),
+        ("i8 checked", "-128 / -1", Err(Trap::DivOverflow)),
+        ("i8 checked", "--128", Err(Trap::NegOverflow)),
+        ("u8 checked", "-(0 + 1)", Err(Trap::NegOverflow)),
+        ("u8 checked", "1 / 0", Err(Trap::DivByZero)),
+        ("u8 wrapping", "1 / 0", Err(Trap::DivByZero)),
+        ("u32 checked", "4294967295 + 1", Err(Trap::AddOverflow)),
+        (
+            "i64 checked",
+            "9223372036854775807 + 1",
+            Err(Trap::AddOverflow),
+        ),
+        ("u8 checked", "255 + 0", Ok("255")),
+        (
+            "i128 checked",
+            "9223372036854775807 * 9223372036854775807",
+            Ok("85070591730234615847396907784232501249"),
+        ),
+    ] {
+        check(spec, src, expected);
+    }
+}
+
+#[test]
+fn reads_a_negated_literal_as_one_literal() {
+    for (spec, src, expected) in [
+        ("i8 checked", "-128", Ok("-128")),
+        ("i8 checked", "1 + -128", Ok("-127")),
+        ("i8 checked", "128", Err(Trap::LiteralOverflow)),
+        ("i16 checked", "-32768", Ok("-32768")),
+        ("i32 checked", "-2147483648", Ok("-2147483648")),
+        ("i32 checked", "2147483648", Err(Trap::LiteralOverflow)),
+        ("u8 checked", "-1", Err(Trap::LiteralOverflow)),
+        ("u8 checked", "-0", Ok("0")),
+        ("u8 checked", "256", Err(Trap::LiteralOverflow)),
+    ] {
+        check(spec, src, expected);
+    }
+}
+
+#[test]
+fn vms_agree_with_fold() {
+    let config = Config {
+        depth: 6,
+        nodes: 12,
+        literals: -300..=300,
+        ..Default::default()
+    };
+    let mut generator = Generator::new(0, config);
+    let mut modes = Vec::new();
+    for ty in ["i8", "u8", "i16", "u16", "i32", "u64", "i128"] {
+        for overflow in ["wrapping", "saturating", "checked"] {
+            modes.push(format!("{ty} {overflow}").parse::<IntMode>().unwrap());
+        }
+    }
+    for _ in 0..500 {
+        let expr = generator.generate();
+        for &mode in &modes {
+            let mut outcomes = eval_all(&expr, mode).into_iter();
+            let (_, expected) = outcomes.next().unwrap();
+            for (name, outcome) in outcomes {
+                assert_eq!(outcome, expected, "`{expr}` as `{mode}` with {name}");
+            }
+        }
+    }
+}
+
+/// `MIN` and `MAX` of every type, and literals just outside of them.
+/// Nothing above `u128::MAX` parses at all.
+const BOUNDS: [(&str, [&str; 2], [&str; 2]); 10] = [
+    ("i8", ["-128", "127"], ["-129", "128"]),
+    ("i16", ["-32768", "32767"], ["-32769", "32768"]),
+    (
+        "i32",
+        ["-2147483648", "2147483647"],
+        ["-2147483649", "2147483648"],
+    ),
+    (
+        "i64",
+        ["-9223372036854775808", "9223372036854775807"],
+        ["-9223372036854775809", "9223372036854775808"],
+    ),
+    (
+        "i128",
+        [
+            "-170141183460469231731687303715884105728",
+            "170141183460469231731687303715884105727",
+        ],
+        [
+            "-170141183460469231731687303715884105729",
+            "170141183460469231731687303715884105728",
+        ],
+    ),
+    ("u8", ["0", "255"], ["-1", "256"]),
+    ("u16", ["0", "65535"], ["-1", "65536"]),
+    ("u32", ["0", "4294967295"], ["-1", "4294967296"]),
+    (
+        "u64",
+        ["0", "18446744073709551615"],
+        ["-1", "18446744073709551616"],
+    ),
+    (
+        "u128",
+        ["0", "340282366920938463463374607431768211455"],
+        ["-1", "-340282366920938463463374607431768211455"],
+    ),
+];
+
+#[test]
+fn reads_literals_up_to_the_bounds_of_each_type() {
+    for (ty, bounds, outside) in BOUNDS {
+        let mode = format!("{ty} checked").parse().unwrap();
+        for bound in bounds {
+            assert_eq!(eval_source(bound, mode), Ok(bound.to_string()), "{ty}");
+        }
+        for outside in outside {
+            let overflow = Err(Trap::LiteralOverflow.to_string());
+            assert_eq!(eval_source(outside, mode), overflow, "`{outside}` as {ty}");
+        }
+    }
+    let mode = "u128 wrapping".parse().unwrap();
+    assert_eq!(
+        eval_source("340282366920938463463374607431768211455 + 2", mode),
+        Ok("1".to_string())
+    );
+    assert_eq!(
+        eval_source("340282366920938463463374607431768211456", mode),
+        Err("number too large to fit in target type".to_string())
+    );
+}
+
+#[test]
+fn repl_shows_and_sets_the_mode() {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_calc"))
+        .arg("repl")
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+    let input = ":mode\n:mode u16 wrapping\n:mode\n65535 + 1\n";
+    child
+        .stdin
+        .take()
+        .unwrap()
+        .write_all(input.as_bytes())
+        .unwrap();
+    let output = child.wait_with_output().unwrap();
+    assert_eq!(
+        String::from_utf8(output.stdout).unwrap(),
+        "int\nu16 wrapping\n0\n"
+    );
+}
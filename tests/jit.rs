@@ -0,0 +1,70 @@
+use calc::differential::catch;
+use calc::source::SourceDb;
+
+/// Result of the JIT and of the tree-walking interpreter, which panics on traps.
+fn outcomes(src: &str) -> (Result<i64, String>, Result<i64, String>) {
+    let mut db = SourceDb::default();
+    let file = db.add("<test>", src);
+    let expr = calc::parser::parse(db.get(file)).unwrap();
+    let (ops, pool, stack_size) = calc::jit::compiler::compile(&expr).unwrap();
+    let program = calc::jit::vm::jit(ops, pool, stack_size);
+    #[cfg(all(target_arch = "x86_64", unix))]
+    assert!(program.is_native());
+    let jit = catch(|| calc::jit::vm::eval(&program));
+    let fold = catch(|| calc::folder::fold(&expr));
+    (jit, fold)
+}
+
+#[track_caller]
+fn check(src: &str) {
+    let (jit, fold) = outcomes(src);
+    assert_eq!(jit, fold, "`{src}`");
+}
+
+#[test]
+fn matches_fold() {
+    for src in [
+        "1 + 2 * 3",
+        "7 / 2",
+        "-7 / 2",
+        "-(3 - 10)",
+        "100000 * 100000 - 9999999999",
+        "1 - (2 - (3 - (4 - (5 - (6 - (7 - (8 - (9 - (10 - (11 - (12 - 13)))))))))))",
+        "(1 + 2) * (3 + 4) / (5 - 6)",
+        "-9223372036854775807 - 1",
+    ] {
+        check(src);
+    }
+}
+
+#[test]
+fn divides_by_zero() {
+    for src in ["1 / 0", "1 / (2 - 2)", "1 / (0 * 99999999999)"] {
+        let (jit, fold) = outcomes(src);
+        assert_eq!(jit, Err("attempt to divide by zero".to_string()), "`{src}`");
+        assert_eq!(jit, fold);
+    }
+}
+
+#[test]
+fn divides_with_overflow() {
+    let (jit, fold) = outcomes("(-9223372036854775807 - 1) / -1");
+    assert_eq!(jit, Err("attempt to divide with overflow".to_string()));
+    assert_eq!(jit, fold);
+}
+
+/// Overflow checks are only emitted with debug assertions, like Rust's,
+/// so either both trap or both wrap.
+#[test]
+fn overflows_like_fold() {
+    for src in [
+        "9223372036854775807 + 1",
+        "-9223372036854775807 - 2",
+        "4611686018427387904 * 2",
+        "-(-9223372036854775807 - 1)",
+    ] {
+        let (jit, fold) = outcomes(src);
+        assert_eq!(jit.is_err(), cfg!(debug_assertions), "`{src}`: {jit:?}");
+        assert_eq!(jit, fold, "`{src}`");
+    }
+}
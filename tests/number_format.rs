@@ -0,0 +1,156 @@
+use calc::number_format::Notation;
+use calc::number_format::NumberFormat;
+use calc::number_format::Width;
+
+#[track_caller]
+fn check(spec: &str, value: i64, expected: &str) {
+    let format: NumberFormat = spec.parse().unwrap();
+    assert_eq!(format.format(value), expected, "{value} as `{spec}`");
+}
+
+#[test]
+fn parses_specs() {
+    assert_eq!("".parse(), Ok(NumberFormat::default()));
+    assert_eq!(
+        "hex, group u16".parse(),
+        Ok(NumberFormat {
+            notation: Notation::Hex,
+            group: true,
+            width: Some(Width {
+                bits: 16,
+                signed: false,
+            }),
+        })
+    );
+    // the last notation wins
+    assert_eq!(
+        "bin oct".parse::<NumberFormat>().unwrap().notation,
+        Notation::Oct
+    );
+    for spec in ["dec", "hex", "oct", "bin", "sci", "dec group i32", "bin u1"] {
+        let format: NumberFormat = spec.parse().unwrap();
+        assert_eq!(format.to_string(), spec);
+    }
+}
+
+#[test]
+fn rejects_bad_specs() {
+    assert_eq!(
+        "octal".parse::<NumberFormat>(),
+        Err("unknown format option `octal`".to_string())
+    );
+    for width in ["i0", "u65", "i", "ux"] {
+        assert_eq!(
+            width.parse::<NumberFormat>(),
+            Err(format!(
+                "invalid width `{width}`, expected `i1` to `i64` or `u1` to `u64`"
+            ))
+        );
+    }
+}
+
+#[test]
+fn renders_notations() {
+    for (spec, value, expected) in [
+        ("dec", 255, "255"),
+        ("hex", 255, "0xff"),
+        ("oct", 8, "0o10"),
+        ("bin", 5, "0b101"),
+        ("sci", 1_234_000, "1.234e6"),
+        ("sci", 7, "7e0"),
+        ("sci", 1000, "1e3"),
+        ("sci", 0, "0e0"),
+        ("dec", i64::MIN, "-9223372036854775808"),
+        ("hex", i64::MIN, "-0x8000000000000000"),
+    ] {
+        check(spec, value, expected);
+    }
+}
+
+#[test]
+fn renders_negative_numbers_with_a_sign() {
+    for (spec, expected) in [
+        ("dec", "-255"),
+        ("hex", "-0xff"),
+        ("oct", "-0o377"),
+        ("bin", "-0b11111111"),
+        ("sci", "-2.55e2"),
+    ] {
+        check(spec, -255, expected);
+    }
+}
+
+#[test]
+fn groups_digits() {
+    for (spec, value, expected) in [
+        ("dec group", 1_000_000, "1_000_000"),
+        ("dec group", 100_000, "100_000"),
+        ("dec group", 999, "999"),
+        ("dec group", -1234, "-1_234"),
+        ("hex group", 0xffff_ffff, "0xffff_ffff"),
+        ("hex group", 0x1_0000, "0x1_0000"),
+        ("oct group", 0o7777, "0o7_777"),
+        ("bin group", 0b1_0101, "0b1_0101"),
+        // the exponent already says how long the number is
+        ("sci group", 1_234_000, "1.234e6"),
+    ] {
+        check(spec, value, expected);
+    }
+}
+
+#[test]
+fn reinterprets_at_a_width() {
+    for (spec, value, expected) in [
+        ("u8", -1, "255"),
+        ("i8", 255, "-1"),
+        ("i8", 128, "-128"),
+        ("i8", 127, "127"),
+        ("u8", 256, "0"),
+        ("i1", 1, "-1"),
+        ("u1", 3, "1"),
+        ("u16", 0x12345, "9029"),
+        ("i32", 0x8000_0000, "-2147483648"),
+        ("u32", -1, "4294967295"),
+        ("i64", -1, "-1"),
+        ("u64", -1, "18446744073709551615"),
+        ("u64", i64::MIN, "9223372036854775808"),
+    ] {
+        check(spec, value, expected);
+    }
+}
+
+#[test]
+fn combines_widths_with_notations() {
+    for (spec, value, expected) in [
+        ("hex u8", -1, "0xff"),
+        ("hex i8", -1, "-0x1"),
+        ("bin u4", -2, "0b1110"),
+        ("bin i4", -2, "-0b10"),
+        ("oct u16", -1, "0o177777"),
+        ("hex group u32", -1, "0xffff_ffff"),
+        ("bin group u8", 0xa5, "0b1010_0101"),
+        ("hex u64", -1, "0xffffffffffffffff"),
+        ("sci u16", -1, "6.5535e4"),
+    ] {
+        check(spec, value, expected);
+    }
+}
+
+#[test]
+fn renders_floats() {
+    for (spec, value, expected) in [
+        ("dec", 3.2, "3.2"),
+        ("dec", 1000.0, "1000"),
+        ("dec group", 1e6, "1_000_000"),
+        ("hex", 255.0, "0xff"),
+        // only integral values have digits in other bases
+        ("hex", 0.5, "0.5"),
+        ("sci", 1234.5, "1.2345e3"),
+        ("sci", -0.00025, "-2.5e-4"),
+        ("sci", 1e30, "1e30"),
+        ("dec", 1e30, "1000000000000000000000000000000"),
+    ] {
+        let format: NumberFormat = spec.parse().unwrap();
+        assert_eq!(format.format_f64(value), expected, "{value} as `{spec}`");
+    }
+}
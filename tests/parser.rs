@@ -0,0 +1,116 @@
+use std::process::Command;
+
+use calc::error::Error;
+use calc::source::SourceDb;
+
+fn parse(src: &str) -> Result<i64, Error> {
+    let mut db = SourceDb::default();
+    let file = db.add("<test>", src);
+    calc::parser::parse(db.get(file)).map(|expr| calc::folder::fold(&expr))
+}
+
+/// The message of the error, and the part of `src` it points at.
+#[track_caller]
+fn error(src: &str) -> (String, &str) {
+    let e = parse(src).unwrap_err();
+    (e.message().to_string(), &src[e.span().start..e.span().end])
+}
+
+#[test]
+fn parses_prefixed_literals() {
+    for (src, value) in [
+        ("0x1f", 0x1f),
+        ("0XFF", 0xff),
+        ("0o17", 0o17),
+        ("0O7", 0o7),
+        ("0b101", 0b101),
+        ("0B1", 1),
+        ("0x7fff_ffff_ffff_ffff", i64::MAX),
+        ("0", 0),
+        ("007", 7),
+    ] {
+        assert_eq!(parse(src).unwrap(), value, "`{src}`");
+    }
+}
+
+#[test]
+fn ignores_separators() {
+    for (src, value) in [
+        ("1_000_000", 1_000_000),
+        ("1__0", 10),
+        ("0x_ff", 0xff),
+        ("0b1010_1010", 0b1010_1010),
+        // any number of separators after the first digit, even at the end
+        ("1_", 1),
+    ] {
+        assert_eq!(parse(src).unwrap(), value, "`{src}`");
+    }
+}
+
+#[test]
+fn skips_comments() {
+    for (src, value) in [
+        ("1 + 2 # three", 3),
+        ("1 + 2 // three", 3),
+        ("# nothing but\n4", 4),
+        ("(1 + // one\n 2)", 3),
+    ] {
+        assert_eq!(parse(src).unwrap(), value, "`{src}`");
+    }
+}
+
+#[test]
+fn rejects_malformed_literals() {
+    for (src, message, at) in [
+        ("0x", "missing digits after `0x`", "0x"),
+        ("1 + 0b", "missing digits after `0b`", "0b"),
+        ("0x_", "missing digits after `0x`", "0x_"),
+        ("0b2", "invalid digit `2` in base 2 literal", "0b2"),
+        ("2 * 0o78", "invalid digit `8` in base 8 literal", "0o78"),
+        ("0xfg", "invalid digit `g` in base 16 literal", "0xfg"),
+        (
+            "9223372036854775808",
+            "number too large to fit in target type",
+            "9223372036854775808",
+        ),
+        (
+            "1 + 0x1_0000_0000_0000_0000",
+            "number too large to fit in target type",
+            "0x1_0000_0000_0000_0000",
+        ),
+    ] {
+        assert_eq!(error(src), (message.to_string(), at), "`{src}`");
+    }
+}
+
+#[test]
+fn rejects_trailing_letters_as_a_unit() {
+    assert_eq!(error("12abc"), ("unexpected unit `abc`".to_string(), "abc"));
+}
+
+/// The snippet under the error pads past `u16::MAX` columns.
+#[test]
+fn reports_errors_on_long_lines() {
+    let path = std::path::Path::new(env!("CARGO_TARGET_TMPDIR")).join("long_line.calc");
+    std::fs::write(&path, "-".repeat(70000) + "1").unwrap();
+    let output = Command::new(env!("CARGO_BIN_EXE_calc"))
+        .arg("run")
+        .arg(&path)
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(1));
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    let lines: Vec<_> = stderr.lines().collect();
+    assert_eq!(
+        lines[0], "expression nested too deeply, the limit is 4096:",
+        "{stderr}"
+    );
+    let column = lines[1]
+        .rsplit(':')
+        .next()
+        .unwrap()
+        .parse::<usize>()
+        .unwrap();
+    assert!(column > 65536, "{stderr}");
+    assert_eq!(lines[3], format!("  {}^", " ".repeat(column - 1)));
+}
@@ -0,0 +1,63 @@
+use calc::stack::op::Op;
+use calc::stack::peephole::optimize;
+use calc::stack::peephole::optimize_checked;
+
+#[test]
+fn fuses_literal_operands() {
+    for (op, fused) in [
+        (Op::BAdd, Op::AddImm(2)),
+        (Op::BSub, Op::SubImm(2)),
+        (Op::BMul, Op::MulImm(2)),
+        (Op::BDiv, Op::DivImm(2)),
+    ] {
+        let ops = vec![Op::LConst(0), Op::LInt(2), op];
+        assert_eq!(optimize(&ops), vec![Op::LConst(0), fused]);
+    }
+}
+
+#[test]
+fn negates_literals() {
+    let ops = vec![Op::LInt(5), Op::UMinus];
+    assert_eq!(optimize(&ops), vec![Op::LInt(-5)]);
+}
+
+#[test]
+fn keeps_negation_of_i16_min() {
+    // `-i16::MIN` doesn't fit an `LInt`
+    let ops = vec![Op::LInt(i16::MIN), Op::UMinus];
+    assert_eq!(optimize(&ops), ops);
+}
+
+#[test]
+fn removes_double_negation() {
+    let ops = vec![Op::LConst(0), Op::UMinus, Op::UMinus];
+    assert_eq!(optimize(&ops), vec![Op::LConst(0)]);
+}
+
+#[test]
+fn rewrites_cascade() {
+    let ops = vec![Op::LInt(1), Op::UMinus, Op::UMinus];
+    assert_eq!(optimize(&ops), vec![Op::LInt(1)]);
+    let ops = vec![Op::LInt(1), Op::LInt(2), Op::UMinus, Op::BAdd];
+    assert_eq!(optimize(&ops), vec![Op::LInt(1), Op::AddImm(-2)]);
+}
+
+#[test]
+fn leaves_other_sequences_alone() {
+    let ops = vec![Op::LInt(1), Op::LConst(0), Op::BAdd, Op::UMinus];
+    assert_eq!(optimize(&ops), ops);
+}
+
+#[test]
+fn checked_variant_keeps_negations() {
+    let ops = vec![
+        Op::LConst(0),
+        Op::UMinus,
+        Op::UMinus,
+        Op::LInt(5),
+        Op::UMinus,
+    ];
+    assert_eq!(optimize_checked(&ops), ops);
+    let ops = vec![Op::LConst(0), Op::LInt(2), Op::BMul];
+    assert_eq!(optimize_checked(&ops), vec![Op::LConst(0), Op::MulImm(2)]);
+}
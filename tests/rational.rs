@@ -0,0 +1,162 @@
+use std::process::Command;
+
+use calc::expr::Expr;
+use calc::parser::Precedence;
+use calc::rational::Rational;
+use calc::source::SourceDb;
+use calc::trap::Trap;
+
+/// Parses like rational mode does, with `*` and `/` binding tighter than `+` and `-`.
+fn parse(src: &str) -> Expr {
+    let mut db = SourceDb::default();
+    let file = db.add("<test>", src);
+    let mut program =
+        calc::parser::parse_program_with_precedence(db.get(file), 64, Precedence::MulFirst)
+            .unwrap();
+    program.stmts.pop().unwrap()
+}
+
+/// Runs `src` as a file in rational mode, returning what it printed.
+fn run(src: &str) -> String {
+    let path = std::path::Path::new(env!("CARGO_TARGET_TMPDIR")).join("rational.calc");
+    std::fs::write(&path, src).unwrap();
+    let output = Command::new(env!("CARGO_BIN_EXE_calc"))
+        .args(["--mode", "rational", "run"])
+        .arg(&path)
+        .output()
+        .unwrap();
+    assert!(output.status.success(), "`{src}`: {output:?}");
+    String::from_utf8(output.stdout).unwrap()
+}
+
+#[track_caller]
+fn check(numer: i64, denom: i64, expected: (i64, i64)) {
+    let value = Rational::new(numer, denom).unwrap();
+    assert_eq!((value.numer(), value.denom()), expected, "{numer}/{denom}");
+}
+
+#[test]
+fn normalizes() {
+    for (numer, denom, expected) in [
+        (6, 4, (3, 2)),
+        (2, -4, (-1, 2)),
+        (-2, -4, (1, 2)),
+        (0, -7, (0, 1)),
+        (5, 1, (5, 1)),
+        (i64::MIN, 2, (i64::MIN / 2, 1)),
+        (i64::MAX, i64::MAX, (1, 1)),
+    ] {
+        check(numer, denom, expected);
+    }
+}
+
+#[test]
+fn rejects_a_zero_denominator() {
+    assert_eq!(Rational::new(1, 0), Err(Trap::DivByZero));
+    assert_eq!(Rational::new(0, 0), Err(Trap::DivByZero));
+    let zero = Rational::from_int(0);
+    assert_eq!(
+        Rational::from_int(1).checked_div(zero),
+        Err(Trap::DivByZero)
+    );
+}
+
+#[test]
+fn traps_on_overflow() {
+    let max = Rational::from_int(i64::MAX);
+    let min = Rational::from_int(i64::MIN);
+    let half = Rational::new(1, 2).unwrap();
+    // moving the sign to the numerator overflows
+    assert_eq!(Rational::new(i64::MIN, -1), Err(Trap::DivOverflow));
+    assert_eq!(
+        max.checked_add(Rational::from_int(1)),
+        Err(Trap::AddOverflow)
+    );
+    assert_eq!(
+        min.checked_sub(Rational::from_int(1)),
+        Err(Trap::SubOverflow)
+    );
+    assert_eq!(
+        max.checked_mul(Rational::from_int(2)),
+        Err(Trap::MulOverflow)
+    );
+    assert_eq!(max.checked_div(half), Err(Trap::DivOverflow));
+    assert_eq!(min.checked_neg(), Err(Trap::NegOverflow));
+    // the denominator overflows even though both values are small
+    let small = Rational::new(1, i64::MAX).unwrap();
+    assert_eq!(small.checked_mul(half), Err(Trap::MulOverflow));
+    // products only have to fit once they're reduced
+    assert_eq!(
+        max.checked_mul(half.checked_mul(half).unwrap())
+            .unwrap()
+            .denom(),
+        4
+    );
+    assert_eq!(
+        max.checked_add(half).map(|v| v.numer()),
+        Err(Trap::AddOverflow)
+    );
+}
+
+#[test]
+fn formats_decimals() {
+    for (numer, denom, places, expected) in [
+        (1, 3, 3, "0.333…"),
+        (-5, 2, 10, "-2.5"),
+        (1, 1, 5, "1"),
+        (0, 1, 5, "0"),
+        (-1, 3, 0, "-0…"),
+        (1, 8, 3, "0.125"),
+        (1, 8, 2, "0.12…"),
+        (i64::MIN, 1, 2, "-9223372036854775808"),
+    ] {
+        let value = Rational::new(numer, denom).unwrap();
+        assert_eq!(value.to_decimal(places), expected, "{value}");
+    }
+}
+
+#[test]
+fn adds_fractions() {
+    for (src, expected) in [
+        ("1/3 + 1/6", (1, 2)),
+        ("1 + 2 * 3", (7, 1)),
+        ("7 / 2 - 1", (5, 2)),
+        ("1 / 2 / 2", (1, 4)),
+        ("(1 + 2) / 4", (3, 4)),
+    ] {
+        let value = calc::folder::fold_rational(&parse(src)).unwrap();
+        assert_eq!((value.numer(), value.denom()), expected, "`{src}`");
+    }
+    assert_eq!(run("1/3 + 1/6"), "1/2 = 0.5\n");
+    assert_eq!(run("1 + 2 * 3\n7 / 2"), "7\n7/2 = 3.5\n");
+}
+
+#[test]
+fn stack_vm_agrees_with_fold() {
+    for src in [
+        "1 / 3 + 1 / 6",
+        "7 / 2 * 2",
+        "-(1 / 2) / 3",
+        "1 / (2 - 2)",
+        "9223372036854775807 + 1 / 2",
+        "--(0 - 9223372036854775807 - 1)",
+        "-(1 / 3) - -(1 / 6)",
+        "(0 - 9223372036854775807 - 1) / -1",
+        "100000 * 100000 / 3 * 100000 * 100000",
+    ] {
+        let expr = parse(src);
+        let expected = calc::folder::fold_rational(&expr);
+        let (ops, pool) = calc::stack::compiler::compile(&expr);
+        let optimized = calc::stack::peephole::optimize_checked(&ops);
+        assert_eq!(
+            calc::stack::vm::eval_rational(&ops, &pool),
+            expected,
+            "`{src}`"
+        );
+        assert_eq!(
+            calc::stack::vm::eval_rational(&optimized, &pool),
+            expected,
+            "`{src}` optimized"
+        );
+    }
+}
@@ -0,0 +1,83 @@
+use std::process::Command;
+
+use calc::source::SourceDb;
+
+/// `1 + (1 + (...))`, where every right operand but the innermost needs its own register.
+fn nested(depth: usize) -> String {
+    format!("{}1{}", "1 + (".repeat(depth), ")".repeat(depth))
+}
+
+fn parse(src: &str) -> calc::expr::Expr {
+    let mut db = SourceDb::default();
+    let file = db.add("<test>", src);
+    calc::parser::parse(db.get(file)).unwrap()
+}
+
+/// The error of a failed compile, whose bytecode isn't `Debug`.
+fn error<T>(result: calc::error::Result<T>) -> calc::error::Error {
+    match result {
+        Ok(_) => panic!("compiled"),
+        Err(e) => e,
+    }
+}
+
+#[test]
+fn compiles_up_to_the_last_register() {
+    let expr = parse(&nested(256));
+    let (ops, pool, stack_size) = calc::register::compiler::compile(&expr).unwrap();
+    assert_eq!(stack_size, 256);
+    assert_eq!(calc::register::vm::eval(&ops, &pool, stack_size), 257);
+
+    let (ops, pool, stack_size) = calc::unsafe_register::compiler::compile(&expr).unwrap();
+    assert_eq!(
+        calc::unsafe_register::vm::eval(&ops, &pool, stack_size),
+        257
+    );
+}
+
+#[test]
+fn fails_once_registers_run_out() {
+    let expr = parse(&nested(257));
+    let message = "expression needs more than 256 registers";
+    let e = error(calc::register::compiler::compile(&expr));
+    assert_eq!(e.message(), message);
+    let e = error(calc::unsafe_register::compiler::compile(&expr));
+    assert_eq!(e.message(), message);
+    let e = error(calc::jit::compiler::compile(&expr));
+    assert_eq!(e.message(), message);
+}
+
+#[test]
+fn points_at_the_operand_without_a_register() {
+    let src = nested(300);
+    let mut db = SourceDb::default();
+    let file = db.add("<test>", src.as_str());
+    let (expr, spans) = calc::parser::parse_spanned(db.get(file), 1000).unwrap();
+
+    let e = error(calc::register::compiler::compile_spanned(&expr, &spans));
+    // the right operand of the 256th `+`
+    assert_eq!(e.span().start, 5 * 256);
+    assert_eq!(e.span().end, src.len() - 256);
+    let e = error(calc::unsafe_register::compiler::compile_spanned(
+        &expr, &spans,
+    ));
+    assert_eq!(e.span().start, 5 * 256);
+}
+
+#[test]
+fn cli_reports_the_error() {
+    for cmd in ["eval", "debug"] {
+        let output = Command::new(env!("CARGO_BIN_EXE_calc"))
+            .args([cmd, "--backend", "register", &nested(300)])
+            .output()
+            .unwrap();
+        let stderr = String::from_utf8(output.stderr).unwrap();
+        assert_eq!(output.status.code(), Some(1), "{cmd}: {stderr}");
+        assert!(
+            stderr.starts_with("expression needs more than 256 registers:\n"),
+            "{cmd}: {stderr}"
+        );
+        assert!(stderr.contains("--> <expr>"), "{cmd}: {stderr}");
+        assert!(!stderr.contains("panicked"), "{cmd}: {stderr}");
+    }
+}
@@ -0,0 +1,95 @@
+use calc::expr::Expr;
+use calc::generator::Config;
+use calc::generator::Generator;
+use calc::source::SourceDb;
+use calc::stats::Stats;
+
+fn parse(src: &str) -> Expr {
+    let mut db = SourceDb::default();
+    let file = db.add("<test>", src);
+    calc::parser::parse(db.get(file)).unwrap()
+}
+
+fn stats(ops: &[(&'static str, u64)], max_depth: usize, const_loads: u64) -> Stats {
+    Stats {
+        ops: ops.iter().copied().collect(),
+        max_depth,
+        const_loads,
+    }
+}
+
+#[test]
+fn counts_stack_vm_steps() {
+    let expr = parse("(1 + 2) * (3 + 100000)");
+    let (ops, pool) = calc::stack::compiler::compile(&expr);
+    let mut observed = Stats::default();
+    let value = calc::stack::vm::eval_observed(&ops, &pool, &mut observed);
+    assert_eq!(value, 300009);
+    // `1 2 + 3 100000 + *`, with the large literal in the constant pool
+    let expected = stats(
+        &[("LInt", 3), ("LConst", 1), ("BAdd", 2), ("BMul", 1)],
+        3,
+        1,
+    );
+    assert_eq!(observed, expected);
+    assert_eq!(observed.instructions(), 7);
+}
+
+#[test]
+fn counts_register_vm_steps() {
+    let expr = parse("(1 + 2) * (3 + 100000)");
+    let (ops, pool, stack_size) = calc::register::compiler::compile(&expr).unwrap();
+    let mut observed = Stats::default();
+    let value = calc::register::vm::eval_observed(&ops, &pool, stack_size, &mut observed);
+    assert_eq!(value, 300009);
+    // literal right operands are folded into the instructions
+    let expected = stats(
+        &[("LInt", 2), ("BAddI", 1), ("BAddK", 1), ("BMul", 1)],
+        2,
+        1,
+    );
+    assert_eq!(observed, expected);
+}
+
+#[test]
+fn observing_nothing_gives_the_same_value() {
+    let config = Config {
+        safe: true,
+        literals: -100_000..=100_000,
+        ..Default::default()
+    };
+    let mut generator = Generator::new(0, config);
+    for _ in 0..200 {
+        let expr = generator.generate();
+
+        let (ops, pool) = calc::stack::compiler::compile(&expr);
+        let value = calc::stack::vm::eval(&ops, &pool);
+        assert_eq!(
+            calc::stack::vm::eval_observed(&ops, &pool, &mut ()),
+            value,
+            "`{expr}`"
+        );
+        let mut stats = Stats::default();
+        assert_eq!(
+            calc::stack::vm::eval_observed(&ops, &pool, &mut stats),
+            value,
+            "`{expr}`"
+        );
+        assert_eq!(stats.instructions(), ops.len() as u64, "`{expr}`");
+
+        let (ops, pool, stack_size) = calc::register::compiler::compile(&expr).unwrap();
+        let value = calc::register::vm::eval(&ops, &pool, stack_size);
+        assert_eq!(
+            calc::register::vm::eval_observed(&ops, &pool, stack_size, &mut ()),
+            value,
+            "`{expr}`"
+        );
+        let mut stats = Stats::default();
+        assert_eq!(
+            calc::register::vm::eval_observed(&ops, &pool, stack_size, &mut stats),
+            value,
+            "`{expr}`"
+        );
+        assert_eq!(stats.instructions(), ops.len() as u64, "`{expr}`");
+    }
+}
@@ -0,0 +1,137 @@
+use std::path::PathBuf;
+use std::process::Command;
+
+use calc::differential::catch;
+use calc::expr::Expr;
+use calc::source::SourceDb;
+
+fn parse(src: &str) -> Expr {
+    let mut db = SourceDb::default();
+    let file = db.add("<test>", src);
+    calc::parser::parse(db.get(file)).unwrap()
+}
+
+/// A fresh directory for the files of one test.
+fn dir(name: &str) -> PathBuf {
+    let dir = PathBuf::from(env!("CARGO_TARGET_TMPDIR")).join(name);
+    std::fs::create_dir_all(&dir).unwrap();
+    dir
+}
+
+/// Runs a compiled program, returning what it printed, or its error message if it failed.
+fn run(mut command: Command) -> Result<String, String> {
+    let output = command.output().unwrap();
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    if output.status.success() {
+        Ok(stdout.trim().to_string())
+    } else {
+        Err(stderr)
+    }
+}
+
+fn compile_rust(expr: &Expr, name: &str) -> Command {
+    let dir = dir(name);
+    let src = dir.join("main.rs");
+    let bin = dir.join("main");
+    let code =
+        calc::codegen::rust::source(expr) + "\nfn main() {\n    println!(\"{}\", eval());\n}\n";
+    std::fs::write(&src, code).unwrap();
+    let status = Command::new("rustc")
+        .args(["--edition", "2021", "-O", "-o"])
+        .arg(&bin)
+        .arg(&src)
+        .status()
+        .unwrap();
+    assert!(status.success(), "rustc failed on `{expr}`");
+    Command::new(bin)
+}
+
+fn compile_c(expr: &Expr, name: &str) -> Command {
+    let dir = dir(name);
+    let src = dir.join("main.c");
+    let bin = dir.join("main");
+    let code = calc::codegen::c::source(expr)
+        + "\nint main(void) {\n    printf(\"%lld\\n\", (long long)eval());\n    return 0;\n}\n";
+    std::fs::write(&src, code).unwrap();
+    let status = Command::new("cc")
+        .args(["-O2", "-o"])
+        .arg(&bin)
+        .arg(&src)
+        .status()
+        .unwrap();
+    assert!(status.success(), "cc failed on `{expr}`");
+    Command::new(bin)
+}
+
+const EXPRS: [&str; 6] = [
+    "1 + 2 * 3",
+    "-7 / 2",
+    "-(3 - 10) * +4",
+    "100000 * 100000 - 9999999999",
+    "-9223372036854775807 - 1",
+    "(1 + 2) * (3 + 4) / (5 - 6)",
+];
+
+/// Checked regardless of the build profile, so these trap just like `fold` in a debug build.
+const TRAPS: [(&str, &str); 5] = [
+    ("9223372036854775807 + 1", "attempt to add with overflow"),
+    (
+        "-(-9223372036854775807 - 1)",
+        "attempt to negate with overflow",
+    ),
+    (
+        "4611686018427387904 * 2",
+        "attempt to multiply with overflow",
+    ),
+    ("1 / (2 - 2)", "attempt to divide by zero"),
+    (
+        "(-9223372036854775807 - 1) / -1",
+        "attempt to divide with overflow",
+    ),
+];
+
+#[test]
+fn rust_matches_fold() {
+    for (i, src) in EXPRS.iter().enumerate() {
+        let expr = parse(src);
+        let output = run(compile_rust(&expr, &format!("rust_{i}")));
+        assert_eq!(output, Ok(calc::folder::fold(&expr).to_string()), "`{src}`");
+    }
+}
+
+#[test]
+fn rust_traps() {
+    for (i, (src, trap)) in TRAPS.iter().enumerate() {
+        let expr = parse(src);
+        let output = run(compile_rust(&expr, &format!("rust_trap_{i}")));
+        assert!(
+            output.as_ref().is_err_and(|e| e.contains(trap)),
+            "`{src}`: {output:?}"
+        );
+        if cfg!(debug_assertions) {
+            assert_eq!(catch(|| calc::folder::fold(&expr)), Err(trap.to_string()));
+        }
+    }
+}
+
+#[test]
+fn c_matches_fold() {
+    for (i, src) in EXPRS.iter().enumerate() {
+        let expr = parse(src);
+        let output = run(compile_c(&expr, &format!("c_{i}")));
+        assert_eq!(output, Ok(calc::folder::fold(&expr).to_string()), "`{src}`");
+    }
+}
+
+#[test]
+fn c_traps() {
+    for (i, (src, trap)) in TRAPS.iter().enumerate() {
+        let expr = parse(src);
+        let output = run(compile_c(&expr, &format!("c_trap_{i}")));
+        assert!(
+            output.as_ref().is_err_and(|e| e.contains(trap)),
+            "`{src}`: {output:?}"
+        );
+    }
+}
@@ -0,0 +1,179 @@
+use std::process::Command;
+
+use calc::error::Error;
+use calc::source::SourceDb;
+use calc::units::Quantity;
+
+/// Value of every statement, up to the first error.
+fn eval(src: &str) -> Result<Vec<String>, (String, String)> {
+    let mut db = SourceDb::default();
+    let file = db.add("<test>", src);
+    let error = |e: Error| {
+        let span = &src[e.span().start..e.span().end];
+        (e.message().to_string(), span.to_string())
+    };
+    let stmts = calc::parser::parse_units_program(db.get(file), 1000).map_err(error)?;
+    stmts
+        .iter()
+        .map(|(stmt, spans)| {
+            let value = calc::folder::fold_units(stmt, spans).map_err(error)?;
+            Ok(value.to_string())
+        })
+        .collect()
+}
+
+#[track_caller]
+fn check(src: &str, expected: &str) {
+    assert_eq!(eval(src), Ok(vec![expected.to_string()]), "`{src}`");
+}
+
+#[track_caller]
+fn check_error(src: &str, message: &str, at: &str) {
+    let expected = Err((message.to_string(), at.to_string()));
+    assert_eq!(eval(src), expected, "`{src}`");
+}
+
+#[test]
+fn combines_units() {
+    for (src, expected) in [
+        ("3 km + 200 m", "3.2 km"),
+        ("200 m + 3 km", "3200 m"),
+        ("10 MB / 2 s", "5 MB/s"),
+        ("2 m * 3 m", "6 m^2"),
+        ("1 kg * 1 m / 1 s / 1 A", "1 kg m/(s A)"),
+        ("1 km / 1 m", "1000"),
+        ("-(3 s)", "-3 s"),
+        ("5 / 1 s", "5/s"),
+    ] {
+        check(src, expected);
+    }
+}
+
+/// A unit only applies to the operand right before it.
+#[test]
+fn binds_units_tighter_than_operators() {
+    for (src, expected) in [
+        ("1/3 km", "0.3333333333333333/km"),
+        ("(1/3) km", "0.3333333333333333 km"),
+    ] {
+        check(src, expected);
+    }
+    check_error("2 + 1 km", "cannot add `1` and `km`", "2 + 1 km");
+}
+
+#[test]
+fn parses_exponents() {
+    for (src, expected) in [
+        ("3 m^2", "3 m^2"),
+        ("3 m^2 * 2 m", "6 m^3"),
+        ("10 m / 2 s^2", "5 m/s^2"),
+        ("2 s^-1", "2/s"),
+        ("1 m^0 + 1", "2"),
+        ("1 m^2 in cm^2", "10000 cm^2"),
+        ("1 N in kg m/s^2", "1 kg m/s^2"),
+    ] {
+        check(src, expected);
+    }
+}
+
+#[test]
+fn output_parses_again() {
+    for src in [
+        "2 m * 3 m",
+        "6 MB / 2 s / 1 s",
+        "4 / 2 s",
+        "3 kg * 2 m / 1 s^2",
+        "3 km in m",
+        "3 kg * 1 m / 1 s / 1 A",
+    ] {
+        let value = eval(src).unwrap().pop().unwrap();
+        check(&value, &value);
+    }
+}
+
+#[test]
+fn converts() {
+    for (src, expected) in [
+        ("5 mi in km", "8.04672 km"),
+        ("1 h to s", "3600 s"),
+        ("(1 GiB in MiB) / 2", "512 MiB"),
+        ("1000 in km/m", "1"),
+    ] {
+        check(src, expected);
+    }
+}
+
+#[test]
+fn rejects_mismatched_dimensions() {
+    check_error("1 m + 1 s", "cannot add `m` and `s`", "1 m + 1 s");
+    check_error(
+        "2 * (1 kg - 1 A)",
+        "cannot subtract `kg` and `A`",
+        "1 kg - 1 A",
+    );
+    check_error("3 m in s", "cannot convert `m` to `s`", "3 m in s");
+    check_error("1 m / 0", "attempt to divide by zero", "1 m / 0");
+    check_error("1 parsec", "unknown unit `parsec`", "parsec");
+}
+
+#[test]
+fn rejects_exponents_out_of_range() {
+    check_error("1 m^128", "exponent must be between -128 and 127", "128");
+    let message = "unit exponents must be between -128 and 127";
+    check_error("1 J^127", message, "J^127");
+    check_error("1 m^127 * 1 m", message, "1 m^127 * 1 m");
+    check_error("1 / 1 m^-128", message, "1 / 1 m^-128");
+
+    // every `m` after a value multiplies in another one
+    let src = format!("1{}", " m".repeat(130));
+    let (message, _) = eval(&src).unwrap_err();
+    assert_eq!(message, "unit exponents must be between -128 and 127");
+}
+
+#[test]
+fn formats_values() {
+    let value = eval("1500 m + 0 km").unwrap();
+    assert_eq!(value, vec!["1500 m"]);
+    let quantity = Quantity::from_int(1500);
+    assert_eq!(quantity.format(&"sci".parse().unwrap()), "1.5e3");
+    assert_eq!(quantity.format(&"hex".parse().unwrap()), "0x5dc");
+}
+
+#[test]
+fn prints_results_before_an_error() {
+    let path = std::path::Path::new(env!("CARGO_TARGET_TMPDIR")).join("units.calc");
+    std::fs::write(&path, "1 km in m\n1 m + 1 s\n2 m").unwrap();
+    let output = Command::new(env!("CARGO_BIN_EXE_calc"))
+        .args(["--mode", "units", "run"])
+        .arg(&path)
+        .output()
+        .unwrap();
+    assert_eq!(output.status.code(), Some(1));
+    assert_eq!(String::from_utf8(output.stdout).unwrap(), "1000 m\n");
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(stderr.starts_with("cannot add `m` and `s`:\n"), "{stderr}");
+}
+
+#[test]
+fn formatter_round_trips_units() {
+    for src in [
+        "3 km + 200 m",
+        "(5 mi in km) * 2",
+        "10 m / 2 s^2 in km/h^2",
+        "2 s^-1",
+    ] {
+        let mut db = SourceDb::default();
+        let file = db.add("<test>", src);
+        let (expr, _) = calc::parser::parse_units_program(db.get(file), 64)
+            .unwrap()
+            .pop()
+            .unwrap();
+        let formatted = calc::formatter::format_units(&expr, usize::MAX);
+        let file = db.add("<formatted>", formatted.as_str());
+        let (again, _) = calc::parser::parse_units_program(db.get(file), 64)
+            .unwrap()
+            .pop()
+            .unwrap();
+        assert_eq!(again, expr, "`{src}` formatted as `{formatted}`");
+    }
+}
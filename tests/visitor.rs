@@ -0,0 +1,108 @@
+use calc::expr::Binary;
+use calc::expr::BinaryOp;
+use calc::expr::Expr;
+use calc::expr::Unary;
+use calc::expr::UnaryOp;
+use calc::expr::Visitor;
+use calc::source::SourceDb;
+
+fn parse(src: &str) -> Expr {
+    let mut db = SourceDb::default();
+    let file = db.add("<test>", src);
+    calc::parser::parse(db.get(file)).unwrap()
+}
+
+/// Records every call, in order.
+#[derive(Default)]
+struct Trace(Vec<String>);
+
+impl Visitor for Trace {
+    fn enter_binary(&mut self, expr: &Binary) {
+        self.0.push(format!("enter {}", expr.op));
+    }
+
+    fn leave_binary(&mut self, expr: &Binary) {
+        self.0.push(format!("leave {}", expr.op));
+    }
+
+    fn enter_unary(&mut self, expr: &Unary) {
+        self.0.push(format!("enter {}", expr.op));
+    }
+
+    fn leave_unary(&mut self, expr: &Unary) {
+        self.0.push(format!("leave {}", expr.op));
+    }
+
+    fn visit_int(&mut self, value: i64) {
+        self.0.push(value.to_string());
+    }
+}
+
+/// Counts the nodes of a tree.
+#[derive(Default)]
+struct Count(usize);
+
+impl Visitor for Count {
+    fn leave_binary(&mut self, _: &Binary) {
+        self.0 += 1;
+    }
+
+    fn leave_unary(&mut self, _: &Unary) {
+        self.0 += 1;
+    }
+
+    fn visit_int(&mut self, _: i64) {
+        self.0 += 1;
+    }
+}
+
+/// Deep enough to overflow the stack of a recursive walk.
+const DEPTH: usize = 1_000_000;
+
+#[test]
+fn visits_in_evaluation_order() {
+    let mut trace = Trace::default();
+    parse("-(1 - 2) * 3").visit(&mut trace);
+    assert_eq!(
+        trace.0,
+        ["enter *", "enter -", "enter -", "1", "2", "leave -", "leave -", "3", "leave *"]
+    );
+}
+
+#[test]
+fn visits_deeply_nested_unary() {
+    let mut expr = Expr::Int(1);
+    for _ in 0..DEPTH {
+        expr = Expr::Unary(Box::new(Unary {
+            op: UnaryOp::Minus,
+            right: expr,
+        }));
+    }
+    let mut count = Count::default();
+    expr.visit(&mut count);
+    assert_eq!(count.0, DEPTH + 1);
+}
+
+#[test]
+fn visits_deeply_nested_binary() {
+    let mut expr = Expr::Int(0);
+    for i in 0..DEPTH {
+        let (left, right) = if i % 2 == 0 {
+            (expr, Expr::Int(1))
+        } else {
+            (Expr::Int(1), expr)
+        };
+        expr = Expr::Binary(Box::new(Binary {
+            left,
+            op: BinaryOp::Add,
+            right,
+        }));
+    }
+    let mut count = Count::default();
+    expr.visit(&mut count);
+    assert_eq!(count.0, 2 * DEPTH + 1);
+
+    // the wasm emitters are visitors too
+    let text = calc::codegen::wasm::text(&expr);
+    assert_eq!(text.matches("i64.add").count(), DEPTH);
+}
@@ -0,0 +1,91 @@
+use calc::expr::Expr;
+use calc::generator::Config;
+use calc::generator::Generator;
+use calc::source::SourceDb;
+use wasmi::core::TrapCode;
+use wasmi::Engine;
+use wasmi::Linker;
+use wasmi::Module;
+use wasmi::Store;
+
+fn parse(src: &str) -> Expr {
+    let mut db = SourceDb::default();
+    let file = db.add("<test>", src);
+    calc::parser::parse(db.get(file)).unwrap()
+}
+
+/// Instantiates a module and calls its `eval` export.
+fn run(wasm: &[u8]) -> Result<i64, TrapCode> {
+    let engine = Engine::default();
+    let module = Module::new(&engine, wasm).unwrap();
+    let mut store = Store::new(&engine, ());
+    let instance = Linker::<()>::new(&engine)
+        .instantiate(&mut store, &module)
+        .unwrap()
+        .start(&mut store)
+        .unwrap();
+    let eval = instance.get_typed_func::<(), i64>(&store, "eval").unwrap();
+    eval.call(&mut store, ())
+        .map_err(|e| e.as_trap_code().unwrap())
+}
+
+/// Runs both the binary and the text output, which must agree.
+fn eval(expr: &Expr) -> Result<i64, TrapCode> {
+    let binary = run(&calc::codegen::wasm::binary(expr));
+    let text = run(&wat::parse_str(calc::codegen::wasm::text(expr)).unwrap());
+    assert_eq!(binary, text, "`{expr}`");
+    binary
+}
+
+#[test]
+fn matches_fold() {
+    for src in [
+        "1 + 2 * 3",
+        "7 / 2",
+        "-7 / 2",
+        "-(3 - 10)",
+        "+5",
+        "100000 * 100000 - 9999999999",
+        "-9223372036854775807 - 1",
+        "(1 + 2) * (3 + 4) / (5 - 6)",
+    ] {
+        let expr = parse(src);
+        assert_eq!(eval(&expr), Ok(calc::folder::fold(&expr)), "`{src}`");
+    }
+}
+
+/// Generated in safe mode, so `fold` doesn't overflow or divide by zero.
+#[test]
+fn matches_fold_on_generated_exprs() {
+    let config = Config {
+        nodes: 64,
+        safe: true,
+        ..Default::default()
+    };
+    let mut generator = Generator::new(0, config);
+    for _ in 0..200 {
+        let expr = generator.generate();
+        assert_eq!(eval(&expr), Ok(calc::folder::fold(&expr)), "`{expr}`");
+    }
+}
+
+#[test]
+fn traps_on_division() {
+    assert_eq!(
+        eval(&parse("1 / (2 - 2)")),
+        Err(TrapCode::IntegerDivisionByZero)
+    );
+    assert_eq!(
+        eval(&parse("(-9223372036854775807 - 1) / -1")),
+        Err(TrapCode::IntegerOverflow)
+    );
+}
+
+/// Unlike `fold`, which panics in debug builds, `add`, `sub` and `mul` wrap.
+#[test]
+fn wraps_on_overflow() {
+    assert_eq!(eval(&parse("9223372036854775807 + 1")), Ok(i64::MIN));
+    assert_eq!(eval(&parse("-9223372036854775807 - 2")), Ok(i64::MAX));
+    assert_eq!(eval(&parse("4611686018427387904 * 2")), Ok(i64::MIN));
+    assert_eq!(eval(&parse("-(-9223372036854775807 - 1)")), Ok(i64::MIN));
+}